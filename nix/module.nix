@@ -30,6 +30,83 @@ in
       # probably using a key file  and systemd credentials
       description = "Discord bot token";
     };
+    maxReminders = lib.mkOption {
+      type = lib.types.nullOr lib.types.ints.positive;
+      default = null;
+      description = "Maximum number of reminders stored across all users. Unlimited if null.";
+    };
+    maxDeliveryAttempts = lib.mkOption {
+      type = lib.types.ints.positive;
+      default = 5;
+      description = "Number of failed deliveries after which a reminder is given up on.";
+    };
+    commandTimeout = lib.mkOption {
+      type = lib.types.ints.positive;
+      default = 60;
+      description = "Seconds a command can run before the user is told it is taking too long.";
+    };
+    logChannelId = lib.mkOption {
+      type = lib.types.nullOr lib.types.str;
+      default = null;
+      example = "1234567890123456789";
+      description = "Discord channel id to post important events, like failed reminder deliveries, to.";
+    };
+    selfCheckUserId = lib.mkOption {
+      type = lib.types.nullOr lib.types.str;
+      default = null;
+      example = "1234567890123456789";
+      description = "Discord user id to DM at startup, to check the bot can send DMs.";
+    };
+    quakeAlertChannelId = lib.mkOption {
+      type = lib.types.nullOr lib.types.str;
+      default = null;
+      example = "1234567890123456789";
+      description = "Discord channel id to post new quakes to.";
+    };
+    quakeAlertMmi = lib.mkOption {
+      type = lib.types.ints.between 0 8;
+      default = 4;
+      description = "Minimum intensity of quakes to post alerts for.";
+    };
+    quakePollMin = lib.mkOption {
+      type = lib.types.ints.positive;
+      default = 60;
+      description = "Shortest wait in seconds between geonet polls for quake alerts, used after large quakes.";
+    };
+    quakePollMax = lib.mkOption {
+      type = lib.types.ints.positive;
+      default = 600;
+      description = "Longest wait in seconds between geonet polls for quake alerts, used when it is quiet.";
+    };
+    embedColour = lib.mkOption {
+      type = lib.types.nullOr lib.types.str;
+      default = null;
+      example = "#5865F2";
+      description = "Hex colour for embeds that don't have a colour of their own. Discord's blurple if null.";
+    };
+    embedFooter = lib.mkOption {
+      type = lib.types.nullOr lib.types.str;
+      default = null;
+      description = "Footer text for embeds that don't have a footer of their own.";
+    };
+    embedFooterIcon = lib.mkOption {
+      type = lib.types.nullOr lib.types.str;
+      default = null;
+      description = "https url of an icon to show beside the embed footer. Needs embedFooter to be set.";
+    };
+    disabledFeatures = lib.mkOption {
+      type = lib.types.listOf (
+        lib.types.enum [
+          "dice"
+          "quakes"
+          "quake-alerts"
+          "reminders"
+          "volcanoes"
+        ]
+      );
+      default = [ ];
+      description = "Features to turn off. Their commands aren't registered with Discord.";
+    };
   };
 
   config = lib.mkIf cfg.enable {
@@ -92,6 +169,35 @@ in
       environment = {
         DISCORD_TOKEN = cfg.discordToken;
         DATABASE_URL = cfg.databaseUrl;
+        MAX_DELIVERY_ATTEMPTS = toString cfg.maxDeliveryAttempts;
+        COMMAND_TIMEOUT_SECS = toString cfg.commandTimeout;
+        QUAKE_ALERT_MMI = toString cfg.quakeAlertMmi;
+        QUAKE_POLL_MIN_SECS = toString cfg.quakePollMin;
+        QUAKE_POLL_MAX_SECS = toString cfg.quakePollMax;
+      }
+      // lib.optionalAttrs (cfg.maxReminders != null) {
+        MAX_REMINDERS = toString cfg.maxReminders;
+      }
+      // lib.optionalAttrs (cfg.logChannelId != null) {
+        LOG_CHANNEL_ID = cfg.logChannelId;
+      }
+      // lib.optionalAttrs (cfg.selfCheckUserId != null) {
+        SELF_CHECK_USER_ID = cfg.selfCheckUserId;
+      }
+      // lib.optionalAttrs (cfg.quakeAlertChannelId != null) {
+        QUAKE_ALERT_CHANNEL_ID = cfg.quakeAlertChannelId;
+      }
+      // lib.optionalAttrs (cfg.embedColour != null) {
+        EMBED_COLOUR = cfg.embedColour;
+      }
+      // lib.optionalAttrs (cfg.embedFooter != null) {
+        EMBED_FOOTER = cfg.embedFooter;
+      }
+      // lib.optionalAttrs (cfg.embedFooterIcon != null) {
+        EMBED_FOOTER_ICON = cfg.embedFooterIcon;
+      }
+      // lib.optionalAttrs (cfg.disabledFeatures != [ ]) {
+        DISABLED_FEATURES = lib.concatStringsSep "," cfg.disabledFeatures;
       };
     };
 
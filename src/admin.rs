@@ -0,0 +1,23 @@
+use super::errors::Error;
+use super::Context;
+
+/// Bot administration commands
+///
+/// These are restricted to the bot's owners. Each subcommand also checks this itself,
+/// and the whole group is hidden from server members who aren't administrators.
+#[poise::command(
+    slash_command,
+    owners_only,
+    default_member_permissions = "ADMINISTRATOR",
+    subcommands(
+        "crate::analytics::analytics",
+        "crate::errors::errors",
+        "crate::reminders::purge_expired",
+        "crate::reminders::inspect_reminder",
+        "crate::reminders::audit_log"
+    )
+)]
+pub(crate) async fn admin(ctx: Context<'_>) -> Result<(), Error> {
+    ctx.say("Please use a subcommand").await?;
+    Ok(())
+}
@@ -0,0 +1,259 @@
+use super::database::{to_db_id, StatementCache};
+use super::embeds;
+use super::errors::Error;
+use super::Context;
+use chrono::{DateTime, Duration, Utc};
+use poise::serenity_prelude::futures::future;
+use std::sync::Arc;
+use tokio_postgres::{types::Type, Client, Statement};
+
+/// Helper struct for recording and summarising command usage.
+/// Use the methods on this struct rather than directly acessing the fields.
+pub(crate) struct AnalyticsDatabase {
+    /// The database client used to interact with postgres
+    client: Arc<Client>,
+    /// A prepared database statement that records a command invocation
+    record: Statement,
+    /// A prepared database statement that summarises invocations per command since a given time
+    summarise: Statement,
+    /// A prepared database statement that records a command error
+    record_error: Statement,
+    /// A prepared database statement that selects the most recent command errors
+    recent_errors: Statement,
+}
+
+/// An error a command ran into, as stored in the database
+pub(crate) struct CommandError {
+    pub(crate) command: String,
+    pub(crate) user_id: i64,
+    pub(crate) error: String,
+    pub(crate) occurred_at: DateTime<Utc>,
+}
+
+/// Usage of a single command over some period
+struct CommandUsage {
+    command: String,
+    invocations: i64,
+    failures: i64,
+    users: i64,
+}
+
+impl AnalyticsDatabase {
+    /// Set up the analytics table and statements on the connection the cache is for
+    pub(crate) async fn new(statements: &StatementCache) -> Result<Self, Error> {
+        let client = statements.client();
+        // Init table if required
+        client
+            .execute(
+                "CREATE TABLE IF NOT EXISTS command_log (
+                            id BIGSERIAL PRIMARY KEY,
+                            command TEXT NOT NULL,
+                            user_id BIGINT NOT NULL,
+                            guild_id BIGINT,
+                            invoked_at TIMESTAMPTZ NOT NULL,
+                            success BOOLEAN NOT NULL
+                        )",
+                &[],
+            )
+            .await?;
+        client
+            .execute(
+                "CREATE TABLE IF NOT EXISTS command_errors (
+                            id BIGSERIAL PRIMARY KEY,
+                            command TEXT NOT NULL,
+                            user_id BIGINT NOT NULL,
+                            error TEXT NOT NULL,
+                            occurred_at TIMESTAMPTZ NOT NULL
+                        )",
+                &[],
+            )
+            .await?;
+
+        let (record, summarise, record_error, recent_errors) = future::try_join4(
+            statements.prepare_typed(
+                "INSERT INTO command_log (command, user_id, guild_id, invoked_at, success)
+                    values ($1, $2, $3, $4, $5)",
+                &[
+                    Type::TEXT,
+                    Type::INT8,
+                    Type::INT8,
+                    Type::TIMESTAMPTZ,
+                    Type::BOOL,
+                ],
+            ),
+            statements.prepare_typed(
+                "SELECT command, COUNT(*), COUNT(*) FILTER (WHERE NOT success), COUNT(DISTINCT user_id)
+                    FROM command_log WHERE invoked_at >= $1
+                    GROUP BY command ORDER BY COUNT(*) DESC",
+                &[Type::TIMESTAMPTZ],
+            ),
+            statements.prepare_typed(
+                "INSERT INTO command_errors (command, user_id, error, occurred_at)
+                    values ($1, $2, $3, $4)",
+                &[Type::TEXT, Type::INT8, Type::TEXT, Type::TIMESTAMPTZ],
+            ),
+            statements.prepare_typed(
+                "SELECT command, user_id, error, occurred_at FROM command_errors
+                    ORDER BY occurred_at DESC LIMIT $1",
+                &[Type::INT8],
+            ),
+        )
+        .await?;
+
+        Ok(AnalyticsDatabase {
+            client,
+            record,
+            summarise,
+            record_error,
+            recent_errors,
+        })
+    }
+
+    /// Record a single command invocation
+    async fn record(
+        &self,
+        command: &str,
+        user_id: i64,
+        guild_id: Option<i64>,
+        invoked_at: DateTime<Utc>,
+        success: bool,
+    ) -> Result<(), Error> {
+        self.client
+            .execute(
+                &self.record,
+                &[&command, &user_id, &guild_id, &invoked_at, &success],
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Summarise usage of each command since the given time, most used first
+    async fn summarise(&self, since: DateTime<Utc>) -> Result<Vec<CommandUsage>, Error> {
+        let rows = self.client.query(&self.summarise, &[&since]).await?;
+        Ok(rows
+            .iter()
+            .map(|row| CommandUsage {
+                command: row.get(0),
+                invocations: row.get(1),
+                failures: row.get(2),
+                users: row.get(3),
+            })
+            .collect())
+    }
+
+    /// Record an error from a command
+    async fn record_error(&self, error: &CommandError) -> Result<(), Error> {
+        self.client
+            .execute(
+                &self.record_error,
+                &[
+                    &error.command,
+                    &error.user_id,
+                    &error.error,
+                    &error.occurred_at,
+                ],
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Get up to `limit` command errors, newest first
+    pub(crate) async fn recent_errors(&self, limit: i64) -> Result<Vec<CommandError>, Error> {
+        let rows = self.client.query(&self.recent_errors, &[&limit]).await?;
+        Ok(rows
+            .iter()
+            .map(|row| CommandError {
+                command: row.get(0),
+                user_id: row.get(1),
+                error: row.get(2),
+                occurred_at: row.get(3),
+            })
+            .collect())
+    }
+}
+
+/// Record a command invocation in the background.
+/// This never blocks the command and failures are only printed,
+/// so analytics can't break anything.
+pub(crate) fn record_invocation(ctx: Context<'_>, success: bool) {
+    // nowhere to record it without a database
+    let Some(analytics) = ctx.data().analytics.clone() else {
+        return;
+    };
+    let command = ctx.command().qualified_name.clone();
+    let user_id = to_db_id(ctx.author().id);
+    let guild_id = ctx.guild_id().map(to_db_id);
+    let invoked_at = ctx.created_at().to_utc();
+
+    tokio::spawn(async move {
+        if let Err(e) = analytics
+            .record(&command, user_id, guild_id, invoked_at, success)
+            .await
+        {
+            println!("Unable to record command analytics: {e:?}");
+        }
+    });
+}
+
+/// Store a command error in the background so operators can look it up later.
+/// Like [`record_invocation`] this never blocks and failures are only printed,
+/// so a broken database can't get in the way of reporting the original error.
+pub(crate) fn record_error(ctx: Context<'_>, error: &Error) {
+    let Some(analytics) = ctx.data().analytics.clone() else {
+        return;
+    };
+    let error = CommandError {
+        command: ctx.command().qualified_name.clone(),
+        user_id: to_db_id(ctx.author().id),
+        error: error.to_string(),
+        occurred_at: ctx.created_at().to_utc(),
+    };
+
+    tokio::spawn(async move {
+        if let Err(e) = analytics.record_error(&error).await {
+            println!("Unable to record command error: {e:?}");
+        }
+    });
+}
+
+/// Show how much each command has been used
+#[poise::command(slash_command, owners_only, ephemeral)]
+pub(crate) async fn analytics(
+    ctx: Context<'_>,
+    #[description = "Number of days to look back over, defaults to 7"]
+    #[min = 1]
+    #[max = 365]
+    days: Option<i64>,
+) -> Result<(), Error> {
+    let days = days.unwrap_or(7);
+    let since = Utc::now() - Duration::days(days);
+    let usage = ctx
+        .data()
+        .analytics
+        .as_ref()
+        .ok_or("Analytics are disabled because the bot has no database")?
+        .summarise(since)
+        .await?;
+    if usage.is_empty() {
+        ctx.say(format!("No commands used in the last {days} days"))
+            .await?;
+        return Ok(());
+    }
+
+    // There are only a handful of commands so this comfortably fits in one embed
+    let embed = usage.iter().fold(
+        embeds::branded_embed().title(format!("Command usage over the last {days} days")),
+        |embed, u| {
+            embed.field(
+                &u.command,
+                format!(
+                    "{} uses, {} failed, {} users",
+                    u.invocations, u.failures, u.users
+                ),
+                true,
+            )
+        },
+    );
+    ctx.send(poise::CreateReply::default().embed(embed)).await?;
+    Ok(())
+}
@@ -0,0 +1,297 @@
+use super::database::{to_db_id, StatementCache};
+use super::errors::Error;
+use super::Context;
+use poise::serenity_prelude::UserId;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tokio_postgres::{types::Type, Client, Statement};
+
+/// Most attributes one person can store
+const MAX_ATTRIBUTES: i64 = 25;
+/// Longest name an attribute can have
+const MAX_NAME_LENGTH: usize = 16;
+/// Largest value, positive or negative, an attribute can hold
+const MAX_VALUE: i32 = 1000;
+
+/// A person's attributes, keyed by lowercase name
+pub(crate) type Attributes = BTreeMap<String, i32>;
+
+/// Check an attribute name is short and simple enough to write in braces, returning it in lowercase
+fn normalise_name(name: &str) -> Result<String, Error> {
+    let name = name.trim().to_lowercase();
+    if name.is_empty()
+        || name.chars().count() > MAX_NAME_LENGTH
+        || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    {
+        return Err(format!(
+            "Attribute names must be 1 to {MAX_NAME_LENGTH} letters, numbers or underscores"
+        )
+        .into());
+    }
+    Ok(name)
+}
+
+/// Replace each `{name}` in a dice expression with the value of that attribute,
+/// e.g. `1d20+{dex}` becomes `1d20+3`. Names are case insensitive.
+/// Negative values are bracketed so they read correctly wherever they land.
+fn substitute_attributes(dice: &str, attributes: &Attributes) -> Result<String, Error> {
+    let mut output = String::with_capacity(dice.len());
+    let mut rest = dice;
+    while let Some(start) = rest.find('{') {
+        output.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let end = after
+            .find('}')
+            .ok_or("An attribute reference is missing its closing `}`")?;
+        let name = after[..end].trim().to_lowercase();
+        let value = attributes.get(&name).ok_or(format!(
+            "You don't have an attribute called `{name}`, set it with /sheet"
+        ))?;
+        let value = if *value < 0 {
+            format!("({value})")
+        } else {
+            value.to_string()
+        };
+        output.push_str(&value);
+        rest = &after[end + 1..];
+    }
+    if rest.contains('}') {
+        return Err("An attribute reference is missing its opening `{`".into());
+    }
+    output.push_str(rest);
+    Ok(output)
+}
+
+/// Helper struct for storing each user's character sheet attributes.
+/// Use the methods on this struct rather than directly acessing the fields.
+pub(crate) struct CharacterSheetDatabase {
+    /// The database client used to interact with postgres
+    client: Arc<Client>,
+    /// A prepared database statement that fetches all of a user's attributes
+    get: Statement,
+    /// A prepared database statement that sets an attribute, unless the user has too many already
+    set: Statement,
+    /// A prepared database statement that deletes an attribute
+    remove: Statement,
+}
+
+impl CharacterSheetDatabase {
+    /// Set up the attributes table and statements on the connection the cache is for
+    pub(crate) async fn new(statements: &StatementCache) -> Result<Self, Error> {
+        let client = statements.client();
+        // Init table if required
+        client
+            .execute(
+                "CREATE TABLE IF NOT EXISTS character_attributes (
+                            user_id BIGINT NOT NULL,
+                            name TEXT NOT NULL,
+                            value INTEGER NOT NULL,
+                            PRIMARY KEY (user_id, name)
+                        )",
+                &[],
+            )
+            .await?;
+
+        let get = statements
+            .prepare_typed(
+                "SELECT name, value FROM character_attributes WHERE user_id = $1",
+                &[Type::INT8],
+            )
+            .await?;
+        let set = statements
+            .prepare_typed(
+                "INSERT INTO character_attributes (user_id, name, value)
+                    SELECT $1, $2, $3 WHERE EXISTS (
+                        SELECT 1 FROM character_attributes WHERE user_id = $1 AND name = $2
+                    ) OR (SELECT COUNT(*) FROM character_attributes WHERE user_id = $1) < $4
+                    ON CONFLICT (user_id, name) DO UPDATE SET value = EXCLUDED.value",
+                &[Type::INT8, Type::TEXT, Type::INT4, Type::INT8],
+            )
+            .await?;
+        let remove = statements
+            .prepare_typed(
+                "DELETE FROM character_attributes WHERE user_id = $1 AND name = $2",
+                &[Type::INT8, Type::TEXT],
+            )
+            .await?;
+
+        Ok(CharacterSheetDatabase {
+            client,
+            get,
+            set,
+            remove,
+        })
+    }
+
+    /// Get all of a user's attributes
+    async fn get(&self, user_id: UserId) -> Result<Attributes, Error> {
+        let rows = self.client.query(&self.get, &[&to_db_id(user_id)]).await?;
+        Ok(rows.iter().map(|row| (row.get(0), row.get(1))).collect())
+    }
+
+    /// Set one of a user's attributes.
+    /// Fails if it's a new attribute and they already have [`MAX_ATTRIBUTES`].
+    async fn set(&self, user_id: UserId, name: &str, value: i32) -> Result<(), Error> {
+        let changed = self
+            .client
+            .execute(
+                &self.set,
+                &[&to_db_id(user_id), &name, &value, &MAX_ATTRIBUTES],
+            )
+            .await?;
+        if changed == 0 {
+            return Err(format!(
+                "You can have at most {MAX_ATTRIBUTES} attributes, remove one to make room"
+            )
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Remove one of a user's attributes, returning whether they had it
+    async fn remove(&self, user_id: UserId, name: &str) -> Result<bool, Error> {
+        let removed = self
+            .client
+            .execute(&self.remove, &[&to_db_id(user_id), &name])
+            .await?;
+        Ok(removed > 0)
+    }
+}
+
+/// Fill in any `{attribute}` references in a dice expression from the sheet of whoever used a command.
+/// Expressions without references are returned as they are, without touching the database.
+pub(crate) async fn fill_in_attributes(ctx: Context<'_>, dice: String) -> Result<String, Error> {
+    if !dice.contains(['{', '}']) {
+        return Ok(dice);
+    }
+    let database = ctx
+        .data()
+        .character_sheets
+        .as_ref()
+        .ok_or("Attributes are disabled because the bot has no database")?;
+    let attributes = database.get(ctx.author().id).await?;
+    substitute_attributes(&dice, &attributes)
+}
+
+/// Save attributes like dex to use in rolls as {dex}, or list the ones you have
+#[poise::command(slash_command, ephemeral)]
+pub(crate) async fn sheet(
+    ctx: Context<'_>,
+    #[description = "Attribute to set or remove, e.g. dex, leave out to list them all"]
+    #[max_length = 16]
+    attribute: Option<String>,
+    #[description = "Value to give the attribute, e.g. 3"]
+    #[min = -1000]
+    #[max = 1000]
+    value: Option<i32>,
+    #[description = "Remove the attribute instead"] remove: Option<bool>,
+) -> Result<(), Error> {
+    let database = ctx
+        .data()
+        .character_sheets
+        .as_ref()
+        .ok_or("Attributes are disabled because the bot has no database")?;
+    let author = ctx.author().id;
+
+    let Some(attribute) = attribute else {
+        let attributes = database.get(author).await?;
+        if attributes.is_empty() {
+            ctx.say(
+                "You haven't saved any attributes, add one like `/sheet attribute:dex value:3`",
+            )
+            .await?;
+            return Ok(());
+        }
+        let lines: Vec<String> = attributes
+            .iter()
+            .map(|(name, value)| format!("`{{{name}}}` {value}"))
+            .collect();
+        ctx.say(format!("Your attributes\n{}", lines.join("\n")))
+            .await?;
+        return Ok(());
+    };
+
+    let name = normalise_name(&attribute)?;
+    match (value, remove.unwrap_or(false)) {
+        (Some(_), true) => Err("Give a value or remove the attribute, not both".into()),
+        (None, false) => Err(format!("Give a value for `{name}`, or remove it").into()),
+        (None, true) => {
+            if database.remove(author, &name).await? {
+                ctx.say(format!("Removed `{name}`")).await?;
+            } else {
+                ctx.say(format!("You don't have an attribute called `{name}`"))
+                    .await?;
+            }
+            Ok(())
+        }
+        (Some(value), false) => {
+            if value.abs() > MAX_VALUE {
+                return Err(format!("Values must be between -{MAX_VALUE} and {MAX_VALUE}").into());
+            }
+            database.set(author, &name, value).await?;
+            ctx.say(format!(
+                "`{name}` is now {value}, use it in rolls as `{{{name}}}`"
+            ))
+            .await?;
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{normalise_name, substitute_attributes, Attributes};
+
+    fn attributes() -> Attributes {
+        Attributes::from([
+            ("dex".to_string(), 3),
+            ("str".to_string(), -1),
+            ("prof".to_string(), 2),
+        ])
+    }
+
+    #[test]
+    fn attributes_are_substituted() {
+        let attributes = attributes();
+        assert_eq!(
+            substitute_attributes("1d20+{dex}+{ PROF }", &attributes).unwrap(),
+            "1d20+3+2"
+        );
+        assert_eq!(
+            substitute_attributes("1d20 + {str}", &attributes).unwrap(),
+            "1d20 + (-1)"
+        );
+        assert_eq!(
+            substitute_attributes("2d6kh1", &attributes).unwrap(),
+            "2d6kh1"
+        );
+        // the result is still a valid expression
+        assert!(substitute_attributes("1d20+{str}", &attributes)
+            .unwrap()
+            .parse::<tyche::Expr>()
+            .is_ok());
+    }
+
+    #[test]
+    fn bad_references_are_errors() {
+        let attributes = attributes();
+        assert_eq!(
+            substitute_attributes("1d20+{wis}", &attributes)
+                .unwrap_err()
+                .to_string(),
+            "You don't have an attribute called `wis`, set it with /sheet"
+        );
+        assert!(substitute_attributes("1d20+{dex", &attributes).is_err());
+        assert!(substitute_attributes("1d20+dex}", &attributes).is_err());
+    }
+
+    #[test]
+    fn names_are_simple() {
+        assert_eq!(normalise_name(" Dex ").unwrap(), "dex");
+        assert_eq!(normalise_name("spell_dc").unwrap(), "spell_dc");
+        assert!(normalise_name("").is_err());
+        assert!(normalise_name("d e x").is_err());
+        assert!(normalise_name("{dex}").is_err());
+        assert!(normalise_name(&"a".repeat(17)).is_err());
+    }
+}
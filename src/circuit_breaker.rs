@@ -0,0 +1,148 @@
+use super::errors::Error;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+
+/// Mutable state of a [`CircuitBreaker`]
+struct BreakerState {
+    /// Number of failures since the last success
+    failures: u32,
+    /// If the breaker has tripped, calls are refused until this instant.
+    /// Once it passes calls are let through again to test if the service has recovered.
+    open_until: Option<Instant>,
+    /// How long the breaker stays open when it trips
+    cooldown: Duration,
+}
+
+/// Stops us hammering a service that is down.
+/// After enough consecutive failures the breaker trips and calls fail immediately
+/// for a cooldown period. After that a single call is let through to test the service,
+/// with the rest still refused until it finishes. A success resets the breaker,
+/// while another failure trips it straight away with double the cooldown.
+pub(crate) struct CircuitBreaker {
+    /// Name of the service, for error messages
+    name: &'static str,
+    /// Number of consecutive failures that trips the breaker
+    threshold: u32,
+    /// Cooldown after the breaker first trips
+    base_cooldown: Duration,
+    /// Cooldown stops doubling once it reaches this
+    max_cooldown: Duration,
+    state: Mutex<BreakerState>,
+    /// Set while a call is testing whether the service has recovered
+    probing: AtomicBool,
+}
+
+/// Lets another call test the service once dropped, even if the test call was cancelled
+struct Probe<'a>(&'a AtomicBool);
+
+impl Drop for Probe<'_> {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::Release);
+    }
+}
+
+impl CircuitBreaker {
+    /// Create a new, untripped, circuit breaker
+    pub(crate) fn new(
+        name: &'static str,
+        threshold: u32,
+        base_cooldown: Duration,
+        max_cooldown: Duration,
+    ) -> Self {
+        CircuitBreaker {
+            name,
+            threshold,
+            base_cooldown,
+            max_cooldown,
+            state: Mutex::new(BreakerState {
+                failures: 0,
+                open_until: None,
+                cooldown: base_cooldown,
+            }),
+            probing: AtomicBool::new(false),
+        }
+    }
+
+    /// Run a request through the breaker.
+    /// If the breaker is open, or another request is already testing the service,
+    /// the request is never started and an error is returned instead.
+    pub(crate) async fn call<T>(
+        &self,
+        request: impl Future<Output = Result<T, Error>>,
+    ) -> Result<T, Error> {
+        // held until the result is recorded, so the next call sees how the test went
+        let _probe = self.admit()?;
+        let result = request.await;
+        match result {
+            Ok(_) => self.record_success(),
+            Err(_) => self.record_failure(),
+        }
+        result
+    }
+
+    /// Lock the state. A panic while holding the lock can't leave the state
+    /// inconsistent, so we ignore poisoning.
+    fn lock(&self) -> MutexGuard<'_, BreakerState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Check whether a call may go ahead. Once the cooldown is over the first call
+    /// becomes the probe, and others are turned away until it's done.
+    fn admit(&self) -> Result<Option<Probe<'_>>, Error> {
+        let state = self.lock();
+        let Some(until) = state.open_until else {
+            return Ok(None);
+        };
+        let wait = until.saturating_duration_since(Instant::now());
+        if !wait.is_zero() {
+            return Err(format!(
+                "{} appears to be down, try again in {} seconds",
+                self.name,
+                wait.as_secs().max(1)
+            )
+            .into());
+        }
+        if self.probing.swap(true, Ordering::Acquire) {
+            return Err(format!(
+                "{} appears to be down, checking whether it's back now",
+                self.name
+            )
+            .into());
+        }
+        Ok(Some(Probe(&self.probing)))
+    }
+
+    /// Reset the breaker after a successful call
+    fn record_success(&self) {
+        let mut state = self.lock();
+        if state.open_until.is_some() {
+            println!("{} has recovered, closing circuit breaker", self.name);
+        }
+        state.failures = 0;
+        state.open_until = None;
+        state.cooldown = self.base_cooldown;
+    }
+
+    /// Count a failed call, tripping the breaker if needed
+    fn record_failure(&self) {
+        let mut state = self.lock();
+        state.failures += 1;
+
+        if state.open_until.is_some() {
+            // we were testing the waters after a cooldown and it's still broken, so back off harder
+            state.cooldown = (state.cooldown * 2).min(self.max_cooldown);
+        } else if state.failures < self.threshold {
+            return;
+        }
+
+        println!(
+            "{} failed {} times in a row, opening circuit breaker for {} seconds",
+            self.name,
+            state.failures,
+            state.cooldown.as_secs()
+        );
+        state.open_until = Some(Instant::now() + state.cooldown);
+    }
+}
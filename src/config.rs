@@ -0,0 +1,251 @@
+use super::embeds::Theme;
+use poise::serenity_prelude::{ChannelId, Colour, UserId};
+use std::time::Duration;
+
+/// Longest footer text we accept for embeds. Discord allows more, but the footer
+/// is shown on nearly every embed and eats into their character limit.
+const MAX_FOOTER: usize = 256;
+
+/// Parts of the bot operators can turn off with `DISABLED_FEATURES`.
+/// Disabled commands aren't registered with Discord, so users don't see them at all.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Feature {
+    /// `/roll` and the other dice commands
+    Dice,
+    /// The `/quake`, `/quakes` and `/strongmotion` commands
+    Quakes,
+    /// Posting new quakes to `QUAKE_ALERT_CHANNEL_ID`, and sending them to subscribers
+    QuakeAlerts,
+    /// The `/remindme` commands. Reminders already made are still delivered.
+    Reminders,
+    /// The `/volcano` command
+    Volcanoes,
+}
+
+impl Feature {
+    /// Every feature, for looking them up by name
+    const ALL: [Feature; 5] = [
+        Feature::Dice,
+        Feature::Quakes,
+        Feature::QuakeAlerts,
+        Feature::Reminders,
+        Feature::Volcanoes,
+    ];
+
+    /// Name of the feature in `DISABLED_FEATURES`
+    fn name(self) -> &'static str {
+        match self {
+            Feature::Dice => "dice",
+            Feature::Quakes => "quakes",
+            Feature::QuakeAlerts => "quake-alerts",
+            Feature::Reminders => "reminders",
+            Feature::Volcanoes => "volcanoes",
+        }
+    }
+}
+
+/// Parse a comma separated list of feature names, ignoring case and blank entries
+fn parse_features(list: &str) -> Result<Vec<Feature>, String> {
+    list.split(',')
+        .map(|name| name.trim().to_lowercase())
+        .filter(|name| !name.is_empty())
+        .map(|name| {
+            Feature::ALL
+                .into_iter()
+                .find(|feature| feature.name() == name)
+                .ok_or_else(|| {
+                    let known: Vec<&str> = Feature::ALL.iter().map(|f| f.name()).collect();
+                    format!(
+                        "Unknown feature `{name}`, the features are {}",
+                        known.join(", ")
+                    )
+                })
+        })
+        .collect()
+}
+
+/// Runtime configuration, read from environment variables at launch
+pub(crate) struct Config {
+    /// Bot token from the Discord developer portal
+    pub(crate) discord_token: String,
+    /// Postgres connection string, see [`tokio_postgres::Config`] for the format.
+    /// Without one the bot runs with reminders and analytics disabled.
+    pub(crate) database_url: Option<String>,
+    /// Maximum number of reminders stored across all users, if there is one
+    pub(crate) max_reminders: Option<i64>,
+    /// Maximum number of reminders each user can have, if there is one
+    pub(crate) max_user_reminders: Option<i64>,
+    /// Number of failed deliveries after which a reminder is given up on
+    pub(crate) max_delivery_attempts: i32,
+    /// How long a command can run before the user is told it is taking too long
+    pub(crate) command_timeout: Duration,
+    /// Channel to post important events like failed reminder deliveries to, if there is one
+    pub(crate) log_channel: Option<ChannelId>,
+    /// User to DM at startup to check that DMs work, if there is one
+    pub(crate) self_check_user: Option<UserId>,
+    /// Channel to post new quakes to, if there is one
+    pub(crate) quake_alert_channel: Option<ChannelId>,
+    /// Minimum intensity of quakes to post alerts for
+    pub(crate) quake_alert_mmi: i8,
+    /// Shortest and longest waits between polls for quake alerts
+    pub(crate) quake_poll_bounds: (Duration, Duration),
+    /// Colour and footer for the bot's embeds
+    pub(crate) theme: Theme,
+    /// Features the operator has turned off
+    pub(crate) disabled_features: Vec<Feature>,
+}
+
+impl Config {
+    /// Load the configuration from the environment.
+    /// Panics if a required variable is missing or a variable can't be parsed,
+    /// because there's no sensible way to continue with a broken config.
+    pub(crate) fn from_env() -> Self {
+        // Load the discord token. If it doesn't exist then panic.
+        let discord_token = std::env::var("DISCORD_TOKEN").expect("missing DISCORD_TOKEN");
+
+        // Load the database URL. If it doesn't exist we run without a database.
+        let database_url = std::env::var("DATABASE_URL").ok();
+
+        let max_reminders = std::env::var("MAX_REMINDERS").ok().map(|v| {
+            v.parse()
+                .ok()
+                .filter(|&n: &i64| n > 0)
+                .expect("MAX_REMINDERS must be a positive integer")
+        });
+
+        let max_user_reminders = std::env::var("MAX_REMINDERS_PER_USER").ok().map(|v| {
+            v.parse()
+                .ok()
+                .filter(|&n: &i64| n > 0)
+                .expect("MAX_REMINDERS_PER_USER must be a positive integer")
+        });
+
+        let max_delivery_attempts = std::env::var("MAX_DELIVERY_ATTEMPTS").map_or(5, |v| {
+            v.parse()
+                .ok()
+                .filter(|&n: &i32| n > 0)
+                .expect("MAX_DELIVERY_ATTEMPTS must be a positive integer")
+        });
+
+        let command_timeout = seconds_var("COMMAND_TIMEOUT_SECS", Duration::from_mins(1));
+
+        let log_channel = std::env::var("LOG_CHANNEL_ID").ok().map(|v| {
+            v.parse()
+                .ok()
+                .filter(|&id: &u64| id != 0)
+                .map(ChannelId::new)
+                .expect("LOG_CHANNEL_ID must be a Discord channel id")
+        });
+
+        let self_check_user = std::env::var("SELF_CHECK_USER_ID").ok().map(|v| {
+            v.parse()
+                .ok()
+                .filter(|&id: &u64| id != 0)
+                .map(UserId::new)
+                .expect("SELF_CHECK_USER_ID must be a Discord user id")
+        });
+
+        let quake_alert_channel = std::env::var("QUAKE_ALERT_CHANNEL_ID").ok().map(|v| {
+            v.parse()
+                .ok()
+                .filter(|&id: &u64| id != 0)
+                .map(ChannelId::new)
+                .expect("QUAKE_ALERT_CHANNEL_ID must be a Discord channel id")
+        });
+
+        let quake_alert_mmi = std::env::var("QUAKE_ALERT_MMI").map_or(4, |v| {
+            v.parse()
+                .ok()
+                .filter(|n: &i8| (0..=8).contains(n))
+                .expect("QUAKE_ALERT_MMI must be an integer from 0 to 8")
+        });
+
+        let quake_poll_bounds = (
+            seconds_var("QUAKE_POLL_MIN_SECS", Duration::from_mins(1)),
+            seconds_var("QUAKE_POLL_MAX_SECS", Duration::from_mins(10)),
+        );
+        assert!(
+            quake_poll_bounds.0 <= quake_poll_bounds.1,
+            "QUAKE_POLL_MIN_SECS can't be more than QUAKE_POLL_MAX_SECS"
+        );
+
+        let mut theme = Theme::default();
+        if let Ok(v) = std::env::var("EMBED_COLOUR") {
+            theme.colour = u32::from_str_radix(v.trim_start_matches('#'), 16)
+                .ok()
+                .filter(|&n| n <= 0xFF_FFFF)
+                .map(Colour::new)
+                .expect("EMBED_COLOUR must be a hex colour like #5865F2");
+        }
+        theme.footer = std::env::var("EMBED_FOOTER").ok().inspect(|v| {
+            assert!(
+                (1..=MAX_FOOTER).contains(&v.chars().count()),
+                "EMBED_FOOTER must be between 1 and {MAX_FOOTER} characters"
+            );
+        });
+        theme.footer_icon = std::env::var("EMBED_FOOTER_ICON").ok().inspect(|v| {
+            assert!(
+                theme.footer.is_some(),
+                "EMBED_FOOTER_ICON needs EMBED_FOOTER to be set, Discord only shows icons beside footer text"
+            );
+            reqwest::Url::parse(v)
+                .ok()
+                .filter(|url| url.scheme() == "https")
+                .expect("EMBED_FOOTER_ICON must be an https url");
+        });
+
+        let disabled_features = std::env::var("DISABLED_FEATURES").map_or_else(
+            |_| Vec::new(),
+            |v| parse_features(&v).unwrap_or_else(|e| panic!("DISABLED_FEATURES: {e}")),
+        );
+
+        Config {
+            discord_token,
+            database_url,
+            max_reminders,
+            max_user_reminders,
+            max_delivery_attempts,
+            command_timeout,
+            log_channel,
+            self_check_user,
+            quake_alert_channel,
+            quake_alert_mmi,
+            quake_poll_bounds,
+            theme,
+            disabled_features,
+        }
+    }
+
+    /// Whether the operator has left a feature turned on
+    pub(crate) fn enabled(&self, feature: Feature) -> bool {
+        !self.disabled_features.contains(&feature)
+    }
+}
+
+/// Read a positive number of seconds from an environment variable, or use `default` if it isn't set.
+/// Panics if the variable is set but isn't a positive integer.
+fn seconds_var(name: &str, default: Duration) -> Duration {
+    std::env::var(name).map_or(default, |v| {
+        v.parse().ok().filter(|&n: &u64| n > 0).map_or_else(
+            || panic!("{name} must be a positive integer"),
+            Duration::from_secs,
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_features, Feature};
+
+    #[test]
+    fn features_parse_by_name() {
+        assert_eq!(
+            parse_features(" Quakes, quake-alerts,,").unwrap(),
+            [Feature::Quakes, Feature::QuakeAlerts]
+        );
+        assert_eq!(parse_features("").unwrap(), []);
+        assert!(parse_features("dice,weather")
+            .unwrap_err()
+            .contains("Unknown feature `weather`"));
+    }
+}
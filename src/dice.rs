@@ -1,26 +1,1577 @@
+use super::character_sheet::fill_in_attributes;
+use super::embeds;
 use super::errors::Error;
+use super::roll_history;
+use super::roll_settings::settings_for;
 use super::Context;
+use crate::serenity;
+use poise::serenity_prelude::{Colour, GuildId};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Mutex, PoisonError};
 use tyche::dice::roller::FastRand;
+use tyche::dice::{Dice, DieRoll, Modifier, Roller};
+use tyche::expr::Evaled;
 use tyche::Expr;
 
+/// Rolls dice using the operating system's cryptographically secure random number generator.
+/// Slower than [`FastRand`], but the results can't be predicted from previous rolls.
+#[derive(Debug, Default)]
+struct SecureRoller {
+    /// Used instead if the operating system's generator ever fails
+    fallback: FastRand,
+}
+
+impl Roller for SecureRoller {
+    fn roll_die(&mut self, sides: u8) -> DieRoll {
+        if sides == 0 {
+            return DieRoll::new(0);
+        }
+
+        // throw away bytes from the incomplete block at the top of the range,
+        // otherwise low numbers would come up slightly more often
+        let limit = 256 - (256 % u16::from(sides));
+        loop {
+            let mut byte = [0u8];
+            if let Err(e) = getrandom::fill(&mut byte) {
+                // panicking here would take the whole bot down, so settle for a less secure roll
+                println!("Secure random number generator failed, falling back to fastrand: {e}");
+                return self.fallback.roll_die(sides);
+            }
+            if u16::from(byte[0]) < limit {
+                return DieRoll::new(byte[0] % sides + 1);
+            }
+        }
+    }
+}
+
+/// Largest seed handed out, so seeds fit in a Discord integer option
+const MAX_SEED: u64 = (1 << 53) - 1;
+
+/// Hands out seeds for fast rolls from a master generator for each guild.
+/// Every fast roll gets its own seed, which is shown with the result
+/// so the roll can be checked later by rolling again with the same seed.
+#[derive(Default)]
+pub(crate) struct SeedBank {
+    /// Master generators keyed by guild, with `None` covering direct messages
+    generators: Mutex<HashMap<Option<GuildId>, fastrand::Rng>>,
+}
+
+impl SeedBank {
+    /// Draw the seed for the next roll in a guild
+    fn next_seed(&self, guild: Option<GuildId>) -> u64 {
+        // a panic while holding the lock can't leave a generator half updated, so ignore poisoning
+        let mut generators = self
+            .generators
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        generators.entry(guild).or_default().u64(..=MAX_SEED)
+    }
+}
+
+/// Available dice rollers
+#[derive(Debug, Default, Clone, Copy, poise::ChoiceParameter)]
+pub(crate) enum RollerChoice {
+    #[default]
+    #[name = "fast"]
+    Fast,
+    #[name = "secure"]
+    Secure,
+}
+
+impl RollerChoice {
+    /// Convert to the number stored in the database
+    pub(crate) fn to_db(self) -> i16 {
+        match self {
+            RollerChoice::Fast => 0,
+            RollerChoice::Secure => 1,
+        }
+    }
+
+    /// Convert from the number stored in the database.
+    /// Unknown values are treated as the fast roller.
+    pub(crate) fn from_db(value: i16) -> Self {
+        match value {
+            1 => RollerChoice::Secure,
+            _ => RollerChoice::Fast,
+        }
+    }
+}
+
+/// Most dice a single roll may use, counting extra dice from explosions and rerolls
+const MAX_DICE_ROLLED: usize = 2000;
+
+/// Wraps a roller to count how many dice it rolls
+struct CountingRoller<'a, R: Roller> {
+    inner: &'a mut R,
+    rolled: usize,
+}
+
+impl<R: Roller> Roller for CountingRoller<'_, R> {
+    fn roll_die(&mut self, sides: u8) -> DieRoll {
+        self.rolled += 1;
+        self.inner.roll_die(sides)
+    }
+}
+
+/// Parse a tyche dice expression and roll it with the given roller.
+/// Returns the total and a description of each die rolled.
+/// Dropped dice (e.g. from `4d6kh3`) are marked with `(d)` in the description.
+/// Rolls that use more than [`MAX_DICE_ROLLED`] dice are rejected rather than flooding
+/// the channel. Expressions asking for too many are turned away before anything is rolled,
+/// and long chains of exploding dice once they go over.
+fn evaluate(dice: &str, roller: &mut impl Roller) -> Result<(i32, String), Error> {
+    let (total, description, _) = evaluate_detailed(dice, roller)?;
+    Ok((total, description))
+}
+
+/// Like [`evaluate`], but also returns each set of dice rolled, for exporting
+fn evaluate_detailed(
+    dice: &str,
+    roller: &mut impl Roller,
+) -> Result<(i32, String, Vec<DiceExport>), Error> {
+    let expr: Expr = dice.parse()?;
+    let asked_for = dice_in(&expr);
+    if asked_for > MAX_DICE_ROLLED {
+        return Err(format!(
+            "That roll asks for {asked_for} dice, the most allowed is {MAX_DICE_ROLLED}"
+        )
+        .into());
+    }
+    let mut roller = CountingRoller {
+        inner: roller,
+        rolled: 0,
+    };
+    let roll = expr.eval(&mut roller)?;
+    if roller.rolled > MAX_DICE_ROLLED {
+        return Err(format!(
+            "That roll used {} dice, the most allowed is {MAX_DICE_ROLLED}",
+            roller.rolled
+        )
+        .into());
+    }
+    let description = roll.to_string();
+    let total = roll.calc()?;
+    let mut sets = Vec::new();
+    collect_dice(&roll, &mut sets);
+    Ok((total, description, sets))
+}
+
+/// How many dice an expression rolls before any explode or are rerolled
+fn dice_in(expr: &Expr) -> usize {
+    match expr {
+        Expr::Dice(dice) => usize::from(dice.count),
+        Expr::Neg(x) => dice_in(x),
+        Expr::Add(a, b)
+        | Expr::Sub(a, b)
+        | Expr::Mul(a, b)
+        | Expr::DivDown(a, b)
+        | Expr::DivUp(a, b) => dice_in(a) + dice_in(b),
+        // plain numbers have no dice. Anything tyche adds in future is still
+        // caught by counting the dice as they're rolled
+        _ => 0,
+    }
+}
+
+/// A single die in an exported roll
+#[derive(Debug, Serialize)]
+struct DieExport {
+    value: u8,
+    /// Dropped by a modifier like `kh`, so not counted in the total
+    dropped: bool,
+    /// Added by a modifier like `x`, rather than being one of the dice first rolled
+    added: bool,
+}
+
+/// One set of dice in an exported roll, e.g. the `4d6kh3` in `4d6kh3 + 2`
+#[derive(Debug, Serialize)]
+struct DiceExport {
+    dice: String,
+    /// Sides on each die, which is already in `dice` for anyone reading the export
+    #[serde(skip)]
+    sides: u8,
+    rolls: Vec<DieExport>,
+}
+
+/// A roll in a form other tools can read, see [`OutputChoice::Json`]
+#[derive(Debug, Serialize)]
+struct RollExport<'a> {
+    expression: &'a str,
+    total: i32,
+    /// Total after resistance and halving, if either was asked for
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mitigated: Option<i32>,
+    dice: Vec<DiceExport>,
+    /// Seed for fast rolls, which can be passed back to `/roll` to repeat the roll
+    seed: Option<u64>,
+    /// Whether the seed was given by the user, making this a replay rather than a fresh roll
+    replayed: bool,
+}
+
+/// Gather every set of dice in an evaluated expression, from left to right
+fn collect_dice(evaled: &Evaled, sets: &mut Vec<DiceExport>) {
+    match evaled {
+        Evaled::Dice(rolled) => sets.push(DiceExport {
+            dice: rolled.dice.to_string(),
+            sides: rolled.dice.sides,
+            rolls: rolled
+                .rolls
+                .iter()
+                .map(|roll| DieExport {
+                    value: roll.val,
+                    dropped: roll.is_dropped(),
+                    added: roll.is_additional(),
+                })
+                .collect(),
+        }),
+        Evaled::Neg(inner) => collect_dice(inner, sets),
+        Evaled::Add(a, b)
+        | Evaled::Sub(a, b)
+        | Evaled::Mul(a, b)
+        | Evaled::DivDown(a, b)
+        | Evaled::DivUp(a, b) => {
+            collect_dice(a, sets);
+            collect_dice(b, sets);
+        }
+        // plain numbers have no dice, and tyche may add new kinds of expression in future
+        _ => {}
+    }
+}
+
+/// Whether a roll is a natural 20, for the leaderboard.
+/// Only rolls that keep a single d20 count, so rolling a pile of d20s
+/// at once can't rack up natural 20s faster than rolling them one by one.
+fn natural_twenty(sets: &[DiceExport]) -> bool {
+    let mut kept = sets
+        .iter()
+        .filter(|set| set.sides == 20)
+        .flat_map(|set| &set.rolls)
+        .filter(|die| !die.dropped);
+    matches!((kept.next(), kept.next()), (Some(die), None) if die.value == 20)
+}
+
+/// Format an exported roll as a JSON code block, or as a file if it's too long for a message
+fn export_reply(export: &RollExport) -> Result<poise::CreateReply, Error> {
+    let json = serde_json::to_string_pretty(export)?;
+    let block = format!("```json\n{json}\n```");
+    if block.chars().count() <= embeds::MAX_MESSAGE_LENGTH {
+        return Ok(poise::CreateReply::default().content(block));
+    }
+    Ok(poise::CreateReply::default()
+        .content("That roll is too big for a message, so here it is as a file")
+        .attachment(serenity::CreateAttachment::bytes(json, "roll.json")))
+}
+
+/// Work out the lowest and highest totals a dice expression can produce.
+/// Explosions and rerolls are ignored, so exploding dice can go over the maximum.
+/// Returns `None` if the range can't be worked out, e.g. when dividing by something that could be zero.
+fn expr_range(expr: &Expr) -> Option<(i64, i64)> {
+    /// Combine two ranges with an operation, taking the extremes of every pairing of their ends
+    fn corners(
+        (a_min, a_max): (i64, i64),
+        (b_min, b_max): (i64, i64),
+        op: impl Fn(i64, i64) -> Option<i64>,
+    ) -> Option<(i64, i64)> {
+        let results = [
+            op(a_min, b_min)?,
+            op(a_min, b_max)?,
+            op(a_max, b_min)?,
+            op(a_max, b_max)?,
+        ];
+        Some((*results.iter().min()?, *results.iter().max()?))
+    }
+
+    Some(match expr {
+        Expr::Num(n) => (i64::from(*n), i64::from(*n)),
+        Expr::Dice(dice) => dice_range(dice),
+        Expr::Neg(x) => {
+            let (min, max) = expr_range(x)?;
+            (-max, -min)
+        }
+        Expr::Add(a, b) => corners(expr_range(a)?, expr_range(b)?, i64::checked_add)?,
+        Expr::Sub(a, b) => corners(expr_range(a)?, expr_range(b)?, i64::checked_sub)?,
+        Expr::Mul(a, b) => corners(expr_range(a)?, expr_range(b)?, i64::checked_mul)?,
+        Expr::DivDown(a, b) | Expr::DivUp(a, b) => {
+            let divisor = expr_range(b)?;
+            if divisor.0 <= 0 && divisor.1 >= 0 {
+                return None;
+            }
+            corners(expr_range(a)?, divisor, i64::checked_div)?
+        }
+        // tyche may add new kinds of expression in future
+        _ => return None,
+    })
+}
+
+/// Lowest and highest totals for a set of dice, see [`expr_range`]
+fn dice_range(dice: &Dice) -> (i64, i64) {
+    let mut kept = dice.count;
+    let mut lowest_face = 1;
+    let mut highest_face = dice.sides;
+    for modifier in &dice.modifiers {
+        match *modifier {
+            Modifier::KeepHigh(n) | Modifier::KeepLow(n) => kept = kept.min(n),
+            Modifier::Min(n) => lowest_face = lowest_face.max(n),
+            Modifier::Max(n) => highest_face = highest_face.min(n),
+            _ => (),
+        }
+    }
+    let kept = i64::from(kept);
+    (
+        kept * i64::from(lowest_face.min(highest_face)),
+        kept * i64::from(highest_face),
+    )
+}
+
+/// Work out the average total of a dice expression, i.e. what it comes to over many rolls.
+/// Each die counts as its average face, so `2d6+3` averages 10.
+/// Dice with modifiers and division are rejected, because
+/// their averages can't be found by swapping dice for average faces.
+fn expected_value(expr: &Expr) -> Result<f64, Error> {
+    Ok(match expr {
+        Expr::Num(n) => f64::from(*n),
+        Expr::Dice(dice) => {
+            if !dice.modifiers.is_empty() {
+                return Err("Dice with modifiers like keep or explode can't be averaged".into());
+            }
+            f64::from(dice.count) * (f64::from(dice.sides) + 1.0) / 2.0
+        }
+        Expr::Neg(x) => -expected_value(x)?,
+        Expr::Add(a, b) => expected_value(a)? + expected_value(b)?,
+        Expr::Sub(a, b) => expected_value(a)? - expected_value(b)?,
+        // separate dice are independent, so their averages multiply
+        Expr::Mul(a, b) => expected_value(a)? * expected_value(b)?,
+        Expr::DivDown(..) | Expr::DivUp(..) => {
+            return Err("Rolls with division can't be averaged, because of the rounding".into())
+        }
+        // tyche may add new kinds of expression in future
+        _ => return Err("That roll is too complex to average".into()),
+    })
+}
+
+/// Pick an embed colour for a roll based on where the total falls in its possible range:
+/// green for the top third, red for the bottom third and gold in between.
+/// Rolls that can only have one result are blurple.
+fn roll_colour(expr: &Expr, total: i32) -> Colour {
+    let Some((min, max)) = expr_range(expr).filter(|(min, max)| min < max) else {
+        return Colour::BLURPLE;
+    };
+    let position = i64::from(total).clamp(min, max) - min;
+    let span = max - min;
+    if position * 3 >= span * 2 {
+        Colour::DARK_GREEN
+    } else if position * 3 <= span {
+        Colour::RED
+    } else {
+        Colour::GOLD
+    }
+}
+
+/// How to present a roll
+#[derive(Debug, Default, Clone, Copy, poise::ChoiceParameter)]
+pub(crate) enum OutputChoice {
+    #[default]
+    #[name = "text"]
+    Text,
+    #[name = "embed"]
+    Embed,
+    #[name = "json"]
+    Json,
+}
+
+impl OutputChoice {
+    /// Convert to the number stored in the database
+    pub(crate) fn to_db(self) -> i16 {
+        match self {
+            OutputChoice::Text => 0,
+            OutputChoice::Embed => 1,
+            OutputChoice::Json => 2,
+        }
+    }
+
+    /// Convert from the number stored in the database.
+    /// Unknown values are treated as text.
+    pub(crate) fn from_db(value: i16) -> Self {
+        match value {
+            1 => OutputChoice::Embed,
+            2 => OutputChoice::Json,
+            _ => OutputChoice::Text,
+        }
+    }
+}
+
+/// When to halve damage, relative to applying resistance
+#[derive(Debug, poise::ChoiceParameter)]
+enum HalveChoice {
+    #[name = "before resistance"]
+    Before,
+    #[name = "after resistance"]
+    After,
+}
+
+/// Apply flat resistance and optional halving (rounding down) to a damage roll.
+/// Damage never drops below zero.
+/// Returns the final damage and a description of each step.
+fn mitigate(total: i32, resist: i32, halve: Option<&HalveChoice>) -> (i32, String) {
+    let mut damage = total.max(0);
+    let mut steps = vec![damage.to_string()];
+
+    if let Some(HalveChoice::Before) = halve {
+        damage /= 2;
+        steps.push(format!("halved: {damage}"));
+    }
+    if resist != 0 {
+        damage = damage.saturating_sub(resist).max(0);
+        steps.push(format!("resist {resist}: {damage}"));
+    }
+    if let Some(HalveChoice::After) = halve {
+        damage /= 2;
+        steps.push(format!("halved: {damage}"));
+    }
+
+    (damage, steps.join(" → "))
+}
+
 /// Roll some dice based on a tyche dice expression
+///
+/// Supports keeping or dropping dice, e.g. `2d20kh` for advantage,
+/// `2d20kl` for disadvantage and `4d6kh3` to drop the lowest of four d6.
+/// Dice explode with `x`, rolling again on a max and adding it, e.g. `3d6x`.
+/// Use `xo` to only explode once or `x>4` to explode on 5 or more.
+/// Extra dice from explosions are listed after the dice that were first rolled.
+/// With `average` the dice aren't rolled, the average total is shown instead.
+/// Attributes saved with `/sheet` can be used in braces, e.g. `1d20+{dex}`.
 #[poise::command(slash_command)]
+// every argument is an option on the command
+#[allow(clippy::too_many_arguments)]
 pub(crate) async fn roll(
     ctx: Context<'_>,
-    #[description = "Tyche compatible dice string"] dice: String,
+    #[description = "Tyche compatible dice string, e.g. 2d20kh (advantage) or 4d6kh3 (drop lowest)"]
+    dice: String,
+    #[description = "Flat damage reduction to subtract from the total"]
+    #[min = 0]
+    resist: Option<i32>,
+    #[description = "Halve the total, rounding down"] halve: Option<HalveChoice>,
+    #[description = "fast (default) or secure: OS randomness, slower but cryptographically strong"]
+    roller: Option<RollerChoice>,
+    #[description = "text (default), embed: a colour coded card, or json: for other tools"]
+    output: Option<OutputChoice>,
+    #[description = "Repeat an earlier fast roll by reusing its seed"]
+    #[min = 0]
+    seed: Option<u64>,
+    #[description = "Show the average total instead of rolling"] average: Option<bool>,
 ) -> Result<(), Error> {
-    // let the server know we're working on it
-    ctx.defer().await?;
+    let dice = fill_in_attributes(ctx, dice).await?;
+    if average == Some(true) {
+        if resist.is_some() || halve.is_some() || seed.is_some() {
+            return Err("Averages can't be combined with resist, halve or seed".into());
+        }
+        let mean = expected_value(&dice.parse()?)?;
+        ctx.say(format!("`{dice}` averages **{mean}**")).await?;
+        return Ok(());
+    }
+
+    // fill in anything left out from the user's settings
+    // a seed means they want a fast roll, whatever roller they usually use
+    let settings = settings_for(ctx).await;
+    let roller = roller.or_else(|| seed.map(|_| RollerChoice::Fast));
+    // a roll repeated from a known seed is a replay, so it doesn't get onto the leaderboard
+    let replayed = seed.is_some();
+    let roller = roller.or(settings.roller).unwrap_or_default();
+    let output = output.or(settings.output);
 
     // parse expression and roll dice
-    let expr: Expr = dice.parse()?;
     // creating a new roller every time is maybe a bit wasteful but it avoids any scope or lifetime issues
-    let mut roller = FastRand::default();
-    let roll = expr.eval(&mut roller)?;
-    let description = roll.to_string();
-    let total = roll.calc()?;
+    let ((total, description, sets), seed) = match roller {
+        RollerChoice::Fast => {
+            let seed = seed.unwrap_or_else(|| ctx.data().dice_seeds.next_seed(ctx.guild_id()));
+            let roll = evaluate_detailed(&dice, &mut FastRand::with_seed(seed))?;
+            (roll, Some(seed))
+        }
+        RollerChoice::Secure => {
+            if seed.is_some() {
+                return Err("Seeds only work with the fast roller".into());
+            }
+            let roll = evaluate_detailed(&dice, &mut SecureRoller::default())?;
+            (roll, None)
+        }
+    };
+
+    if !replayed && natural_twenty(&sets) {
+        roll_history::record_natural_twenty(ctx);
+    }
+
+    // respond to user, showing the arithmetic if any mitigation was requested
+    let mitigation = (resist.is_some() || halve.is_some())
+        .then(|| mitigate(total, resist.unwrap_or(0), halve.as_ref()));
+    match output.unwrap_or_default() {
+        OutputChoice::Text => {
+            let mut extra = match mitigation {
+                Some((damage, steps)) => format!("\nAfter mitigation: **{damage}** ({steps})"),
+                None => String::new(),
+            };
+            if let Some(seed) = seed {
+                // anyone can pick a seed with a good roll, so replays must not pass for fresh rolls
+                let label = seed_label(seed, replayed);
+                extra = if replayed {
+                    format!("{extra}\n**{label}**")
+                } else {
+                    format!("{extra}\n-# {label}")
+                };
+            }
+            ctx.say(format_roll(total, description, &extra)).await?;
+        }
+        OutputChoice::Embed => {
+            // the expression already parsed successfully above so this won't fail
+            let expr: Expr = dice.parse()?;
+            let colour = roll_colour(&expr, total);
+            let title = if replayed {
+                format!("Replay: {dice}")
+            } else {
+                dice
+            };
+            let mut embed = roll_embed(title, description, &sets, total, colour);
+            if let Some((damage, steps)) = mitigation {
+                embed = embed.field("After mitigation", format!("**{damage}** ({steps})"), true);
+            }
+            if let Some(seed) = seed {
+                embed = embed.footer(serenity::CreateEmbedFooter::new(seed_label(seed, replayed)));
+            }
+            ctx.send(poise::CreateReply::default().embed(embed)).await?;
+        }
+        OutputChoice::Json => {
+            let export = RollExport {
+                expression: &dice,
+                total,
+                mitigated: mitigation.map(|(damage, _)| damage),
+                dice: sets,
+                seed,
+                replayed,
+            };
+            ctx.send(export_reply(&export)?).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Summarise each set of dice in a roll as its count, lowest, highest and sum,
+/// for when there are too many dice to list. Dropped dice are left out.
+fn summarise_dice(sets: &[DiceExport]) -> String {
+    sets.iter()
+        .map(|set| {
+            let kept: Vec<i64> = set
+                .rolls
+                .iter()
+                .filter(|die| !die.dropped)
+                .map(|die| i64::from(die.value))
+                .collect();
+            match (kept.iter().min(), kept.iter().max()) {
+                (Some(min), Some(max)) => format!(
+                    "{}: {} dice, min {min}, max {max}, sum {}",
+                    set.dice,
+                    kept.len(),
+                    kept.iter().sum::<i64>()
+                ),
+                _ => format!("{}: no dice kept", set.dice),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// The breakdown shown in a roll's embed. Big dice pools have breakdowns too long
+/// for an embed, so those are collapsed into a [`summarise_dice`] summary instead,
+/// which is itself cut short if the roll has a huge number of sets of dice.
+fn embed_breakdown(description: String, sets: &[DiceExport]) -> String {
+    const NOTE: &str = "(too many dice to show each one)\n";
+
+    if description.chars().count() <= embeds::MAX_DESCRIPTION {
+        return description;
+    }
+    let summary = embeds::truncate(
+        summarise_dice(sets),
+        embeds::MAX_DESCRIPTION - NOTE.chars().count(),
+    );
+    format!("{NOTE}{summary}")
+}
+
+/// Build the embed for a roll, see [`OutputChoice::Embed`]
+fn roll_embed(
+    dice: String,
+    description: String,
+    sets: &[DiceExport],
+    total: i32,
+    colour: Colour,
+) -> serenity::CreateEmbed {
+    embeds::branded_embed()
+        .title(embeds::truncate(dice, embeds::MAX_TITLE))
+        .description(embed_breakdown(description, sets))
+        .colour(colour)
+        .field("Total", format!("**{total}**"), true)
+}
+
+/// How the seed of a fast roll is shown with the result.
+/// Replays are labelled as such, since the user could have picked the seed for its result.
+fn seed_label(seed: u64, replayed: bool) -> String {
+    if replayed {
+        format!("Replay of seed {seed}, not a fresh roll")
+    } else {
+        format!("Seed {seed}")
+    }
+}
+
+/// Build the reply for a roll as `{total} = {description}{extra}`.
+/// Big dice pools can have very long descriptions, so the description is
+/// cut short if the reply wouldn't otherwise fit in a Discord message.
+fn format_roll(total: i32, description: String, extra: &str) -> String {
+    const NOTE: &str = "\n(breakdown truncated to fit in a message)";
+
+    let reply = format!("{total} = {description}{extra}");
+    if reply.chars().count() <= embeds::MAX_MESSAGE_LENGTH {
+        return reply;
+    }
+
+    let prefix = format!("{total} = ");
+    let available = embeds::MAX_MESSAGE_LENGTH
+        .saturating_sub(prefix.chars().count() + extra.chars().count() + NOTE.chars().count());
+    let description = embeds::truncate(description, available);
+    format!("{prefix}{description}{extra}{NOTE}")
+}
+
+/// Split an initiative entry like `Goblin:+2` into a name and modifier.
+/// Entries without a modifier get +0.
+fn parse_combatant(entry: &str) -> Result<(String, i32), String> {
+    let (name, modifier) = match entry.rsplit_once(':') {
+        Some((name, modifier)) => {
+            let modifier = modifier
+                .trim()
+                .parse()
+                .map_err(|_| format!("`{}` isn't a whole number", modifier.trim()))?;
+            (name.trim(), modifier)
+        }
+        None => (entry.trim(), 0),
+    };
+    if name.is_empty() {
+        return Err("missing a name".to_string());
+    }
+    Ok((name.to_string(), modifier))
+}
+
+/// Roll `1d20 + modifier` for every combatant and sort them from highest to lowest.
+/// Ties go to the higher modifier. Entries that can't be parsed are returned separately
+/// so one typo doesn't spoil the whole roll.
+fn roll_initiative(
+    combatants: &str,
+    roller: &mut impl Roller,
+) -> (Vec<(String, i32, String)>, Vec<String>) {
+    let mut order = Vec::new();
+    let mut problems = Vec::new();
+
+    for entry in combatants.split(',').filter(|e| !e.trim().is_empty()) {
+        let (name, modifier) = match parse_combatant(entry) {
+            Ok(v) => v,
+            Err(e) => {
+                problems.push(format!("`{}`: {e}", entry.trim()));
+                continue;
+            }
+        };
+        let dice = match modifier {
+            0 => "1d20".to_string(),
+            m if m < 0 => format!("1d20 - {}", m.unsigned_abs()),
+            m => format!("1d20 + {m}"),
+        };
+        match evaluate(&dice, roller) {
+            Ok((total, description)) => order.push((name, modifier, total, description)),
+            Err(e) => problems.push(format!("`{}`: {e}", entry.trim())),
+        }
+    }
+
+    order.sort_by(|a, b| b.2.cmp(&a.2).then(b.1.cmp(&a.1)));
+    let order = order
+        .into_iter()
+        .map(|(name, _, total, description)| (name, total, description))
+        .collect();
+    (order, problems)
+}
+
+/// Roll initiative for a group of combatants
+///
+/// Rolls 1d20 plus each combatant's modifier and lists them from highest to lowest.
+#[poise::command(slash_command)]
+pub(crate) async fn initiative(
+    ctx: Context<'_>,
+    #[description = "Comma separated name:modifier pairs, e.g. Goblin:+2, Orc:-1, Alice:5"]
+    combatants: String,
+) -> Result<(), Error> {
+    let (order, problems) = roll_initiative(&combatants, &mut FastRand::default());
+    if order.is_empty() {
+        let mut message = "No combatants to roll for".to_string();
+        if !problems.is_empty() {
+            message = format!("{message}:\n{}", problems.join("\n"));
+        }
+        return Err(message.into());
+    }
+
+    let list = order
+        .iter()
+        .enumerate()
+        .map(|(i, (name, total, description))| {
+            format!("{}. **{name}** {total} ({description})", i + 1)
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let mut embed = embeds::branded_embed()
+        .title("Initiative")
+        .description(embeds::truncate(list, embeds::MAX_DESCRIPTION));
+    if !problems.is_empty() {
+        embed = embed.field(
+            "Skipped",
+            embeds::truncate(problems.join("\n"), embeds::MAX_FIELD_VALUE),
+            false,
+        );
+    }
+    ctx.send(poise::CreateReply::default().embed(embed)).await?;
+    Ok(())
+}
+
+/// Most times `/rolluntil` will roll before giving up
+const MAX_ATTEMPTS: usize = 100;
+
+/// Roll a dice expression over and over until the total hits `target`,
+/// or reaches it if `or_higher` is set. Returns every total rolled, in order,
+/// and whether the target came up before [`MAX_ATTEMPTS`] ran out.
+fn roll_until(
+    dice: &str,
+    target: i32,
+    or_higher: bool,
+    roller: &mut impl Roller,
+) -> Result<(Vec<i32>, bool), Error> {
+    let mut totals = Vec::new();
+    while totals.len() < MAX_ATTEMPTS {
+        let (total, _) = evaluate(dice, roller)?;
+        totals.push(total);
+        if total == target || (or_higher && total > target) {
+            return Ok((totals, true));
+        }
+    }
+    Ok((totals, false))
+}
+
+/// Roll dice repeatedly until a target comes up, counting the attempts
+#[poise::command(slash_command)]
+pub(crate) async fn rolluntil(
+    ctx: Context<'_>,
+    #[description = "Tyche compatible dice string, e.g. 1d6"] dice: String,
+    #[description = "Total to keep rolling for"] target: i32,
+    #[description = "Also stop on anything higher than the target"] or_higher: Option<bool>,
+) -> Result<(), Error> {
+    let (totals, found) = roll_until(
+        &dice,
+        target,
+        or_higher.unwrap_or(false),
+        &mut FastRand::default(),
+    )?;
+    let attempts = totals.len();
+    let sequence = totals
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(", ");
+    let summary = if let (true, Some(last)) = (found, totals.last()) {
+        let plural = if attempts == 1 { "" } else { "s" };
+        format!("Got {last} on `{dice}` after {attempts} roll{plural}")
+    } else {
+        format!("Gave up on `{dice}` after {attempts} rolls without getting {target}")
+    };
+    let reply = format!("{summary}\n{sequence}");
+    ctx.say(embeds::truncate(reply, embeds::MAX_MESSAGE_LENGTH))
+        .await?;
+    Ok(())
+}
+
+/// Most targets `/rollagainst` compares one roll to
+const MAX_TARGETS: usize = 25;
+
+/// Parse a comma separated list of targets, each a number or `name:number` like `Goblin:13`.
+/// Unnamed targets are named after their place in the list, e.g. `Target 2`.
+fn parse_targets(targets: &str) -> Result<Vec<(String, i32)>, Error> {
+    let parsed = targets
+        .split(',')
+        .filter(|e| !e.trim().is_empty())
+        .enumerate()
+        .map(|(i, entry)| {
+            let target = if entry.contains(':') {
+                parse_combatant(entry)
+            } else {
+                entry
+                    .trim()
+                    .parse()
+                    .map(|target| (format!("Target {}", i + 1), target))
+                    .map_err(|_| "isn't a whole number or name:number".to_string())
+            };
+            target.map_err(|e| format!("`{}` {e}", entry.trim()))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if parsed.is_empty() {
+        return Err("Give at least one target, e.g. 12, 15 or Goblin:13, Orc:15".into());
+    }
+    if parsed.len() > MAX_TARGETS {
+        return Err(format!(
+            "That's {} targets, the most allowed is {MAX_TARGETS}",
+            parsed.len()
+        )
+        .into());
+    }
+    Ok(parsed)
+}
 
-    // respond to user
-    ctx.say(format!("{total} = {description}")).await?;
+/// Lay out whether a total meets each target as a table for a code block.
+/// Meeting a target exactly counts as a hit, like attacks against armour class.
+fn target_table(total: i32, targets: &[(String, i32)]) -> String {
+    let width = targets
+        .iter()
+        .map(|(name, _)| name.chars().count())
+        .max()
+        .unwrap_or(0);
+    targets
+        .iter()
+        .map(|(name, target)| {
+            let result = if total >= *target { "hit" } else { "miss" };
+            format!("{name:<width$}  {target:>4}  {result}")
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Roll once and check the total against several targets, e.g. an attack against several ACs
+#[poise::command(slash_command)]
+pub(crate) async fn rollagainst(
+    ctx: Context<'_>,
+    #[description = "Tyche compatible dice string, e.g. 1d20 + 5"] dice: String,
+    #[description = "Comma separated targets to meet, e.g. 12, 15 or Goblin:13, Orc:15"]
+    targets: String,
+) -> Result<(), Error> {
+    let targets = parse_targets(&targets)?;
+    let (total, description) = evaluate(&dice, &mut FastRand::default())?;
+    let table = format!("\n```\n{}\n```", target_table(total, &targets));
+    ctx.say(format_roll(total, description, &table)).await?;
     Ok(())
 }
+
+/// Most dice `/rollpool` rolls at once, before any explode
+const MAX_POOL: u8 = 50;
+/// Lowest die that counts as a success when `/rollpool` isn't given a threshold
+const DEFAULT_SUCCESS_THRESHOLD: u8 = 8;
+
+/// How a dice pool went: the dice at or over the threshold, and the dice that came up 1
+#[derive(Debug, PartialEq, Eq)]
+struct PoolResult {
+    successes: usize,
+    ones: usize,
+}
+
+/// Count the successes and 1s in a pool's dice, including any added by exploding.
+/// Dropped dice don't count, though a plain pool never drops any.
+fn count_successes(rolls: &[DieExport], threshold: u8) -> PoolResult {
+    let kept = || rolls.iter().filter(|die| !die.dropped);
+    PoolResult {
+        successes: kept().filter(|die| die.value >= threshold).count(),
+        ones: kept().filter(|die| die.value == 1).count(),
+    }
+}
+
+/// Roll a pool of d10s, World of Darkness style, where 10s are rolled again if `explode` is set.
+/// Returns how the pool went and a description of every die rolled.
+fn roll_pool(
+    pool: u8,
+    threshold: u8,
+    explode: bool,
+    roller: &mut impl Roller,
+) -> Result<(PoolResult, String), Error> {
+    let dice = if explode {
+        format!("{pool}d10x")
+    } else {
+        format!("{pool}d10")
+    };
+    let (_, description, sets) = evaluate_detailed(&dice, roller)?;
+    let rolls = sets.first().map_or(&[][..], |set| &set.rolls);
+    Ok((count_successes(rolls, threshold), description))
+}
+
+/// Describe the result of a pool, e.g. `**2** successes`.
+/// If 1s subtract, a pool with no successes and at least one 1 is a botch,
+/// and otherwise the total doesn't go below zero.
+fn describe_pool(result: &PoolResult, ones_subtract: bool) -> String {
+    if ones_subtract && result.successes == 0 && result.ones > 0 {
+        return "**Botch**".to_string();
+    }
+    let net = if ones_subtract {
+        result.successes.saturating_sub(result.ones)
+    } else {
+        result.successes
+    };
+    match net {
+        0 => "**0** successes, a failure".to_string(),
+        1 => "**1** success".to_string(),
+        n => format!("**{n}** successes"),
+    }
+}
+
+/// Roll a pool of d10s and count the successes, like World of Darkness
+#[poise::command(slash_command)]
+pub(crate) async fn rollpool(
+    ctx: Context<'_>,
+    #[description = "Number of d10s to roll"]
+    #[min = 1]
+    #[max = 50]
+    pool: u8,
+    #[description = "Lowest die that counts as a success, defaults to 8"]
+    #[min = 2]
+    #[max = 10]
+    threshold: Option<u8>,
+    #[description = "Roll each 10 again, adding another die"] explode: Option<bool>,
+    #[description = "Each 1 cancels a success, with no successes and any 1s a botch"]
+    ones_subtract: Option<bool>,
+    #[description = "fast (default) or secure: OS randomness, slower but cryptographically strong"]
+    roller: Option<RollerChoice>,
+) -> Result<(), Error> {
+    if !(1..=MAX_POOL).contains(&pool) {
+        return Err(format!("Pools must be 1 to {MAX_POOL} dice, not {pool}").into());
+    }
+    let threshold = threshold.unwrap_or(DEFAULT_SUCCESS_THRESHOLD);
+    if !(2..=10).contains(&threshold) {
+        return Err(format!("The threshold must be from 2 to 10, not {threshold}").into());
+    }
+    let roller = match roller {
+        Some(roller) => roller,
+        None => settings_for(ctx).await.roller.unwrap_or_default(),
+    };
+    let explode = explode.unwrap_or(false);
+    let (result, description) = match roller {
+        RollerChoice::Fast => roll_pool(pool, threshold, explode, &mut FastRand::default())?,
+        RollerChoice::Secure => roll_pool(pool, threshold, explode, &mut SecureRoller::default())?,
+    };
+    let reply = format!(
+        "{} ({threshold}+) = {description}",
+        describe_pool(&result, ones_subtract.unwrap_or(false))
+    );
+    ctx.say(embeds::truncate(reply, embeds::MAX_MESSAGE_LENGTH))
+        .await?;
+    Ok(())
+}
+
+/// Number of Fudge dice in a Fate roll
+const FATE_DICE: usize = 4;
+
+/// Roll Fudge dice, which have two each of `-`, blank and `+` faces.
+/// Each die is -1, 0 or 1.
+fn roll_fate(roller: &mut impl Roller) -> [i32; FATE_DICE] {
+    std::array::from_fn(|_| i32::from(roller.roll_die(3).val) - 2)
+}
+
+/// Show a Fudge die the way it's printed
+fn fate_face(die: i32) -> &'static str {
+    match die.signum() {
+        1 => "+",
+        -1 => "-",
+        _ => "0",
+    }
+}
+
+/// Name a result on the Fate ladder, e.g. +2 is Fair.
+/// Results off either end of the ladder use its top or bottom rung.
+fn fate_ladder(total: i32) -> &'static str {
+    match total {
+        8.. => "Legendary",
+        7 => "Epic",
+        6 => "Fantastic",
+        5 => "Superb",
+        4 => "Great",
+        3 => "Good",
+        2 => "Fair",
+        1 => "Average",
+        0 => "Mediocre",
+        -1 => "Poor",
+        _ => "Terrible",
+    }
+}
+
+/// Roll four Fate/Fudge dice and read the result off the ladder
+#[poise::command(slash_command)]
+pub(crate) async fn fate(
+    ctx: Context<'_>,
+    #[description = "Skill or other bonus to add, e.g. 2 for Fair"]
+    #[min = -10]
+    #[max = 10]
+    modifier: Option<i32>,
+    #[description = "fast (default) or secure: OS randomness, slower but cryptographically strong"]
+    roller: Option<RollerChoice>,
+) -> Result<(), Error> {
+    let roller = match roller {
+        Some(roller) => roller,
+        None => settings_for(ctx).await.roller.unwrap_or_default(),
+    };
+    let dice = match roller {
+        RollerChoice::Fast => roll_fate(&mut FastRand::default()),
+        RollerChoice::Secure => roll_fate(&mut SecureRoller::default()),
+    };
+    let modifier = modifier.unwrap_or(0);
+    let total = dice.iter().sum::<i32>() + modifier;
+
+    let faces = dice.map(fate_face).join(" ");
+    let bonus = match modifier.signum() {
+        1 => format!(" + {modifier}"),
+        -1 => format!(" - {}", modifier.unsigned_abs()),
+        _ => String::new(),
+    };
+    ctx.say(format!(
+        "**{total:+}** {} = 4dF[{faces}]{bonus}",
+        fate_ladder(total)
+    ))
+    .await?;
+    Ok(())
+}
+
+/// How well a percentile roll went against its target, from best to worst
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PercentileTier {
+    Critical,
+    Extreme,
+    Hard,
+    Regular,
+    Failure,
+    Fumble,
+}
+
+impl PercentileTier {
+    /// Work out the tier of a d100 roll against a skill target, Call of Cthulhu style.
+    /// A 1 is always critical, a fifth of the target or under is extreme and half or under is hard.
+    /// A 100 always fumbles, as does anything over 95 when the target is under 50.
+    fn of(roll: i32, target: i32) -> Self {
+        if roll == 1 {
+            PercentileTier::Critical
+        } else if roll == 100 || (roll > 95 && target < 50) {
+            PercentileTier::Fumble
+        } else if roll <= target / 5 {
+            PercentileTier::Extreme
+        } else if roll <= target / 2 {
+            PercentileTier::Hard
+        } else if roll <= target {
+            PercentileTier::Regular
+        } else {
+            PercentileTier::Failure
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            PercentileTier::Critical => "Critical success",
+            PercentileTier::Extreme => "Extreme success",
+            PercentileTier::Hard => "Hard success",
+            PercentileTier::Regular => "Regular success",
+            PercentileTier::Failure => "Failure",
+            PercentileTier::Fumble => "Fumble",
+        }
+    }
+}
+
+/// Roll a d100 against a skill and report how well it went
+#[poise::command(slash_command)]
+pub(crate) async fn percentile(
+    ctx: Context<'_>,
+    #[description = "Skill value to roll under, e.g. 60"]
+    #[min = 1]
+    #[max = 100]
+    target: i32,
+    #[description = "fast (default) or secure: OS randomness, slower but cryptographically strong"]
+    roller: Option<RollerChoice>,
+) -> Result<(), Error> {
+    let roller = match roller {
+        Some(roller) => roller,
+        None => settings_for(ctx).await.roller.unwrap_or_default(),
+    };
+    let (roll, _) = match roller {
+        RollerChoice::Fast => evaluate("1d100", &mut FastRand::default())?,
+        RollerChoice::Secure => evaluate("1d100", &mut SecureRoller::default())?,
+    };
+    ctx.say(format!(
+        "**{roll}** vs {target}: {}",
+        PercentileTier::of(roll, target).name()
+    ))
+    .await?;
+    Ok(())
+}
+
+/// Ability scores rolled for a character
+const ABILITY_SCORES: usize = 6;
+/// The usual way to roll an ability score: four d6, dropping the lowest
+const ABILITY_DICE: &str = "4d6kh3";
+/// Total of the standard array, 15 14 13 12 10 8, which costs the usual 27 point buy
+const STANDARD_ARRAY_TOTAL: i32 = 72;
+
+/// Roll a set of ability scores, returning each total with its breakdown
+fn roll_abilities(roller: &mut impl Roller) -> Result<Vec<(i32, String)>, Error> {
+    (0..ABILITY_SCORES)
+        .map(|_| evaluate(ABILITY_DICE, roller))
+        .collect()
+}
+
+/// The modifier an ability score gives, e.g. +2 for 15
+fn ability_modifier(score: i32) -> i32 {
+    (score - 10).div_euclid(2)
+}
+
+/// How a set of ability scores compares to the standard array
+fn compare_to_standard(total: i32) -> String {
+    let difference = total - STANDARD_ARRAY_TOTAL;
+    match difference.signum() {
+        1 => format!("{difference} above the standard array ({STANDARD_ARRAY_TOTAL})"),
+        -1 => format!(
+            "{} below the standard array ({STANDARD_ARRAY_TOTAL})",
+            difference.unsigned_abs()
+        ),
+        _ => format!("level with the standard array ({STANDARD_ARRAY_TOTAL})"),
+    }
+}
+
+/// Roll six ability scores, each 4d6 dropping the lowest
+///
+/// The total is compared to the standard array, which is what point buy gives you,
+/// so you can tell whether the dice were kind.
+#[poise::command(slash_command)]
+pub(crate) async fn abilities(
+    ctx: Context<'_>,
+    #[description = "fast (default) or secure: OS randomness, slower but cryptographically strong"]
+    roller: Option<RollerChoice>,
+) -> Result<(), Error> {
+    let roller = match roller {
+        Some(roller) => roller,
+        None => settings_for(ctx).await.roller.unwrap_or_default(),
+    };
+    let scores = match roller {
+        RollerChoice::Fast => roll_abilities(&mut FastRand::default())?,
+        RollerChoice::Secure => roll_abilities(&mut SecureRoller::default())?,
+    };
+    let total: i32 = scores.iter().map(|(score, _)| score).sum();
+
+    let lines: Vec<String> = scores
+        .into_iter()
+        .map(|(score, description)| {
+            format!(
+                "**{score}** ({:+}) = {description}",
+                ability_modifier(score)
+            )
+        })
+        .collect();
+    ctx.say(format!(
+        "{}\nTotal **{total}**, {}",
+        lines.join("\n"),
+        compare_to_standard(total)
+    ))
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        ability_modifier, compare_to_standard, describe_pool, embed_breakdown, evaluate,
+        evaluate_detailed, expected_value, export_reply, expr_range, fate_face, fate_ladder,
+        format_roll, mitigate, natural_twenty, parse_combatant, parse_targets, roll_abilities,
+        roll_colour, roll_fate, roll_initiative, roll_pool, roll_until, seed_label, target_table,
+        HalveChoice, PercentileTier, PoolResult, RollExport, SecureRoller, SeedBank, MAX_ATTEMPTS,
+        MAX_DICE_ROLLED, MAX_SEED,
+    };
+    use crate::embeds::{MAX_DESCRIPTION, MAX_MESSAGE_LENGTH};
+    use poise::serenity_prelude::Colour;
+    use tyche::dice::roller::{FastRand, Iter};
+    use tyche::dice::Roller;
+
+    #[test]
+    fn keep_highest_drops_lowest() {
+        let mut roller = Iter::new([3, 6, 1, 4]);
+        let (total, description) = evaluate("4d6kh3", &mut roller).unwrap();
+        assert_eq!(total, 13);
+        assert_eq!(description, "4d6kh3[3, 6, 1 (d), 4]");
+    }
+
+    #[test]
+    fn keep_lowest_drops_highest() {
+        let mut roller = Iter::new([17, 5]);
+        let (total, description) = evaluate("2d20kl", &mut roller).unwrap();
+        assert_eq!(total, 5);
+        assert_eq!(description, "2d20kl[17 (d), 5]");
+    }
+
+    #[test]
+    fn keep_highest_many() {
+        let mut roller = Iter::new([2, 9, 7, 10, 1]);
+        let (total, _) = evaluate("5d10kh2 + 1", &mut roller).unwrap();
+        assert_eq!(total, 20);
+    }
+
+    #[test]
+    fn halving_order_matters() {
+        assert_eq!(mitigate(17, 3, Some(&HalveChoice::Before)).0, 5);
+        assert_eq!(mitigate(17, 3, Some(&HalveChoice::After)).0, 7);
+        assert_eq!(
+            mitigate(17, 3, Some(&HalveChoice::Before)).1,
+            "17 → halved: 8 → resist 3: 5"
+        );
+    }
+
+    #[test]
+    fn resistance_cannot_go_negative() {
+        assert_eq!(mitigate(4, 10, None), (0, "4 → resist 10: 0".to_string()));
+    }
+
+    #[test]
+    fn huge_rolls_fit_in_a_message() {
+        let mut roller = Iter::new(std::iter::repeat_n(6, 255 * 4));
+        let (total, description) = evaluate("255d6 + 255d6 + 255d6 + 255d6", &mut roller).unwrap();
+        assert!(description.chars().count() > MAX_MESSAGE_LENGTH);
+
+        let reply = format_roll(
+            total,
+            description,
+            "\nAfter mitigation: **1** (6120 → resist 6119: 1)",
+        );
+        assert_eq!(reply.chars().count(), MAX_MESSAGE_LENGTH);
+        assert!(reply.starts_with("6120 = "));
+        assert!(reply.ends_with("(breakdown truncated to fit in a message)"));
+    }
+
+    #[test]
+    fn huge_rolls_are_summarised_in_embeds() {
+        let mut roller = Iter::new([1, 6].repeat(255 * 2).into_iter().chain([20]));
+        let (_, description, sets) =
+            evaluate_detailed("255d6 + 255d6kh1 + 255d6 + 255d6 + 1d20", &mut roller).unwrap();
+        assert!(description.chars().count() > MAX_DESCRIPTION);
+
+        let breakdown = embed_breakdown(description, &sets);
+        assert!(breakdown.chars().count() <= MAX_DESCRIPTION);
+        assert_eq!(
+            breakdown,
+            "(too many dice to show each one)\n\
+            255d6: 255 dice, min 1, max 6, sum 890\n\
+            255d6kh: 1 dice, min 6, max 6, sum 6\n\
+            255d6: 255 dice, min 1, max 6, sum 890\n\
+            255d6: 255 dice, min 1, max 6, sum 895\n\
+            1d20: 1 dice, min 20, max 20, sum 20"
+        );
+    }
+
+    #[test]
+    fn summaries_of_many_sets_are_cut_short() {
+        let dice = vec!["10d6"; 150].join(" + ");
+        let mut roller = FastRand::with_seed(1);
+        let (_, description, sets) = evaluate_detailed(&dice, &mut roller).unwrap();
+        let breakdown = embed_breakdown(description, &sets);
+        assert_eq!(breakdown.chars().count(), MAX_DESCRIPTION);
+        assert!(breakdown.starts_with("(too many dice to show each one)\n10d6: 10 dice"));
+
+        let short = embed_breakdown("2d6[3, 4]".to_string(), &sets);
+        assert_eq!(short, "2d6[3, 4]");
+    }
+
+    #[test]
+    fn targets_can_be_named() {
+        assert_eq!(
+            parse_targets("12, Goblin:13,, Orc : 15").unwrap(),
+            [
+                ("Target 1".to_string(), 12),
+                ("Goblin".to_string(), 13),
+                ("Orc".to_string(), 15)
+            ]
+        );
+        assert_eq!(
+            parse_targets("12, Goblin").unwrap_err().to_string(),
+            "`Goblin` isn't a whole number or name:number"
+        );
+        assert!(parse_targets(" , ").is_err());
+        assert!(parse_targets(&vec!["10"; 26].join(",")).is_err());
+    }
+
+    #[test]
+    fn meeting_a_target_is_a_hit() {
+        let targets = [("Goblin".to_string(), 13), ("Ogre".to_string(), 14)];
+        assert_eq!(
+            target_table(13, &targets),
+            "Goblin    13  hit\nOgre      14  miss"
+        );
+    }
+
+    #[test]
+    fn pools_count_dice_at_the_threshold() {
+        let mut roller = Iter::new([10, 8, 7, 1, 3]);
+        let (result, description) = roll_pool(5, 8, false, &mut roller).unwrap();
+        assert_eq!(
+            result,
+            PoolResult {
+                successes: 2,
+                ones: 1
+            }
+        );
+        assert_eq!(description, "5d10[10, 8, 7, 1, 3]");
+        assert_eq!(describe_pool(&result, false), "**2** successes");
+        assert_eq!(describe_pool(&result, true), "**1** success");
+
+        // a lower threshold counts more dice
+        let mut roller = Iter::new([10, 8, 7, 1, 3]);
+        assert_eq!(roll_pool(5, 7, false, &mut roller).unwrap().0.successes, 3);
+    }
+
+    #[test]
+    fn tens_explode_into_more_successes() {
+        // the first 10 is rolled again, comes up 10 again, then 8
+        let mut roller = Iter::new([10, 2, 9, 10, 8]);
+        let (result, _) = roll_pool(3, 8, true, &mut roller).unwrap();
+        assert_eq!(result.successes, 4);
+    }
+
+    #[test]
+    fn ones_can_botch() {
+        let botch = PoolResult {
+            successes: 0,
+            ones: 2,
+        };
+        assert_eq!(describe_pool(&botch, true), "**Botch**");
+        assert_eq!(describe_pool(&botch, false), "**0** successes, a failure");
+        // more 1s than successes is a failure, not a botch
+        let cancelled = PoolResult {
+            successes: 1,
+            ones: 3,
+        };
+        assert_eq!(
+            describe_pool(&cancelled, true),
+            "**0** successes, a failure"
+        );
+    }
+
+    #[test]
+    fn small_rolls_are_untouched() {
+        assert_eq!(format_roll(7, "2d6[3, 4]".to_string(), ""), "7 = 2d6[3, 4]");
+    }
+
+    #[test]
+    fn ranges_follow_the_expression() {
+        let range = |dice: &str| expr_range(&dice.parse().unwrap());
+        assert_eq!(range("2d6 + 3"), Some((5, 15)));
+        assert_eq!(range("4d6kh3"), Some((3, 18)));
+        assert_eq!(range("10 - 1d4"), Some((6, 9)));
+        assert_eq!(range("2d20kl * -2"), Some((-40, -2)));
+        assert_eq!(range("1d6 / (1d2 - 1)"), None);
+    }
+
+    #[test]
+    fn only_single_kept_d20s_count_as_natural_twenties() {
+        let sets = |dice: &str, rolls: Vec<u8>| {
+            let (_, _, sets) = evaluate_detailed(dice, &mut Iter::new(rolls)).unwrap();
+            sets
+        };
+        assert!(natural_twenty(&sets("1d20 + 5", vec![20])));
+        assert!(natural_twenty(&sets("2d20kh + 1d6", vec![20, 5, 6])));
+        assert!(!natural_twenty(&sets("2d20kl", vec![20, 5])));
+        assert!(!natural_twenty(&sets("1d20", vec![19])));
+        // several kept d20s would make farming natural 20s easy
+        assert!(!natural_twenty(&sets("2d20", vec![20, 20])));
+        assert!(!natural_twenty(&sets("1d20 + 1d20", vec![20, 3])));
+        assert!(!natural_twenty(&sets("1d6", vec![6])));
+    }
+
+    #[test]
+    fn ability_scores_drop_the_lowest_die() {
+        let mut roller = Iter::new([6, 5, 4, 1].repeat(6));
+        let scores = roll_abilities(&mut roller).unwrap();
+        assert_eq!(scores.len(), 6);
+        assert!(scores.iter().all(|(score, _)| *score == 15));
+        assert_eq!(scores[0].1, "4d6kh3[6, 5, 4, 1 (d)]");
+    }
+
+    #[test]
+    fn ability_totals_compare_to_the_standard_array() {
+        assert_eq!(ability_modifier(15), 2);
+        assert_eq!(ability_modifier(8), -1);
+        assert_eq!(ability_modifier(3), -4);
+        assert_eq!(compare_to_standard(75), "3 above the standard array (72)");
+        assert_eq!(compare_to_standard(70), "2 below the standard array (72)");
+        assert_eq!(
+            compare_to_standard(72),
+            "level with the standard array (72)"
+        );
+    }
+
+    #[test]
+    fn averages_swap_dice_for_average_faces() {
+        let average = |dice: &str| expected_value(&dice.parse().unwrap());
+        assert_eq!(average("2d6 + 3").unwrap().to_string(), "10");
+        assert_eq!(average("1d20 - 1d4").unwrap().to_string(), "8");
+        assert_eq!(average("-1d8 * 2").unwrap().to_string(), "-9");
+        assert_eq!(average("1d6").unwrap().to_string(), "3.5");
+        assert!(average("4d6kh3").is_err());
+        assert!(average("1d6 / 2").is_err());
+    }
+
+    #[test]
+    fn colour_reflects_the_total() {
+        let expr = "1d20".parse().unwrap();
+        assert_eq!(roll_colour(&expr, 20), Colour::DARK_GREEN);
+        assert_eq!(roll_colour(&expr, 10), Colour::GOLD);
+        assert_eq!(roll_colour(&expr, 1), Colour::RED);
+        assert_eq!(roll_colour(&"5".parse().unwrap(), 5), Colour::BLURPLE);
+    }
+
+    #[test]
+    fn combatants_parse() {
+        assert_eq!(
+            parse_combatant(" Goblin : +2"),
+            Ok(("Goblin".to_string(), 2))
+        );
+        assert_eq!(parse_combatant("Orc:-1"), Ok(("Orc".to_string(), -1)));
+        assert_eq!(parse_combatant("Alice"), Ok(("Alice".to_string(), 0)));
+        assert!(parse_combatant("Bob:lots").is_err());
+        assert!(parse_combatant(":3").is_err());
+    }
+
+    #[test]
+    fn initiative_is_sorted() {
+        let mut roller = Iter::new([10, 15, 12, 12]);
+        let (order, problems) =
+            roll_initiative("Goblin:2, Orc:-1, Typo:x, Alice:5, Bob:3", &mut roller);
+        let names: Vec<_> = order
+            .iter()
+            .map(|(name, total, _)| (name.as_str(), *total))
+            .collect();
+        // Alice and Bob tie on 17 so the higher modifier goes first
+        assert_eq!(
+            names,
+            [("Alice", 17), ("Bob", 15), ("Orc", 14), ("Goblin", 12)]
+        );
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].starts_with("`Typo:x`"));
+    }
+
+    #[test]
+    fn seeds_repeat_rolls() {
+        let first = evaluate("10d20", &mut FastRand::with_seed(1234)).unwrap();
+        let second = evaluate("10d20", &mut FastRand::with_seed(1234)).unwrap();
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn replays_are_labelled() {
+        assert_eq!(seed_label(42, false), "Seed 42");
+        assert_eq!(seed_label(42, true), "Replay of seed 42, not a fresh roll");
+    }
+
+    #[test]
+    fn seeds_fit_in_a_discord_integer() {
+        let bank = SeedBank::default();
+        for _ in 0..1000 {
+            assert!(bank.next_seed(None) <= MAX_SEED);
+        }
+    }
+
+    #[test]
+    fn exploding_dice_list_the_extra_rolls() {
+        // both sixes explode, and the extra dice follow the original two
+        let mut roller = Iter::new([6, 6, 2, 3]);
+        let (total, description) = evaluate("2d6x", &mut roller).unwrap();
+        assert_eq!(total, 17);
+        assert_eq!(description, "2d6x[6, 6, 2, 3]");
+    }
+
+    #[test]
+    fn rolling_until_stops_on_the_target() {
+        let mut roller = Iter::new([2, 5, 6, 1]);
+        let (totals, found) = roll_until("1d6", 6, false, &mut roller).unwrap();
+        assert_eq!(totals, [2, 5, 6]);
+        assert!(found);
+
+        let mut roller = Iter::new([2, 5, 6, 1]);
+        let (totals, found) = roll_until("1d6", 4, true, &mut roller).unwrap();
+        assert_eq!(totals, [2, 5]);
+        assert!(found);
+    }
+
+    #[test]
+    fn rolling_until_gives_up() {
+        let mut roller = Iter::new(std::iter::repeat_n(1, MAX_ATTEMPTS + 1));
+        let (totals, found) = roll_until("1d6", 6, false, &mut roller).unwrap();
+        assert_eq!(totals.len(), MAX_ATTEMPTS);
+        assert!(!found);
+    }
+
+    #[test]
+    fn explosions_are_capped() {
+        let rolls = std::iter::repeat_n(6, MAX_DICE_ROLLED).chain([1]);
+        let mut roller = Iter::new(rolls);
+        assert!(evaluate("1d6x", &mut roller).is_err());
+    }
+
+    #[test]
+    fn secure_rolls_stay_in_range() {
+        let mut roller = SecureRoller::default();
+        for sides in [1, 6, 20, 100, 255] {
+            for _ in 0..500 {
+                let roll = roller.roll_die(sides).val;
+                assert!((1..=sides).contains(&roll));
+            }
+        }
+    }
+
+    #[test]
+    fn fudge_dice_have_three_faces() {
+        let dice = roll_fate(&mut Iter::new([1, 2, 3, 3]));
+        assert_eq!(dice, [-1, 0, 1, 1]);
+        assert_eq!(dice.map(fate_face), ["-", "0", "+", "+"]);
+    }
+
+    #[test]
+    fn ladder_clamps_at_both_ends() {
+        assert_eq!(fate_ladder(2), "Fair");
+        assert_eq!(fate_ladder(0), "Mediocre");
+        assert_eq!(fate_ladder(12), "Legendary");
+        assert_eq!(fate_ladder(-4), "Terrible");
+    }
+
+    #[test]
+    fn percentile_tiers_follow_thresholds() {
+        assert_eq!(PercentileTier::of(1, 10), PercentileTier::Critical);
+        assert_eq!(PercentileTier::of(12, 60), PercentileTier::Extreme);
+        assert_eq!(PercentileTier::of(13, 60), PercentileTier::Hard);
+        assert_eq!(PercentileTier::of(30, 60), PercentileTier::Hard);
+        assert_eq!(PercentileTier::of(31, 60), PercentileTier::Regular);
+        assert_eq!(PercentileTier::of(60, 60), PercentileTier::Regular);
+        assert_eq!(PercentileTier::of(61, 60), PercentileTier::Failure);
+        assert_eq!(PercentileTier::of(96, 60), PercentileTier::Failure);
+        assert_eq!(PercentileTier::of(96, 40), PercentileTier::Fumble);
+        assert_eq!(PercentileTier::of(100, 100), PercentileTier::Fumble);
+    }
+
+    #[test]
+    fn exports_list_every_die() {
+        let mut roller = Iter::new([3, 6, 1, 4, 5]);
+        let (total, _, dice) = evaluate_detailed("4d6kh3 - 1d8 + 2", &mut roller).unwrap();
+        let export = RollExport {
+            expression: "4d6kh3 - 1d8 + 2",
+            total,
+            mitigated: None,
+            dice,
+            seed: Some(7),
+            replayed: true,
+        };
+        let json = serde_json::to_value(&export).unwrap();
+        assert_eq!(json["total"], 10);
+        assert_eq!(json["seed"], 7);
+        assert_eq!(json["replayed"], true);
+        assert!(json.get("mitigated").is_none());
+        assert_eq!(json["dice"][0]["dice"], "4d6kh3");
+        assert_eq!(json["dice"][0]["rolls"][2]["value"], 1);
+        assert_eq!(json["dice"][0]["rolls"][2]["dropped"], true);
+        assert_eq!(json["dice"][1]["rolls"][0]["value"], 5);
+
+        let reply = export_reply(&export).unwrap();
+        assert!(reply.content.unwrap().starts_with("```json\n{"));
+    }
+}
@@ -0,0 +1,66 @@
+use super::errors::Error;
+use crate::serenity;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Set once Discord refuses the bot's token. The token won't start working again
+/// without a restart, so after that we stop sending requests that are bound to fail.
+static TOKEN_REJECTED: AtomicBool = AtomicBool::new(false);
+
+/// The error for a request we didn't send because Discord has already rejected the token
+#[derive(Debug)]
+pub(crate) struct TokenRejected;
+
+impl std::fmt::Display for TokenRejected {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Discord has rejected the bot's token, so nothing can be sent"
+        )
+    }
+}
+
+impl std::error::Error for TokenRejected {}
+
+/// Whether Discord has rejected the bot's token since it started
+pub(crate) fn token_rejected() -> bool {
+    TOKEN_REJECTED.load(Ordering::Relaxed)
+}
+
+/// Fail fast if Discord has already rejected the bot's token
+pub(crate) fn check_token() -> Result<(), Error> {
+    if token_rejected() {
+        return Err(TokenRejected.into());
+    }
+    Ok(())
+}
+
+/// Whether an error means Discord refused the bot's credentials, rather than
+/// something that might work if we tried again, like a timeout or a closed DM.
+/// Discord answers a bad or revoked token with 401 Unauthorized on every request.
+pub(crate) fn is_auth_failure(error: &Error) -> bool {
+    if error.is::<TokenRejected>() {
+        return true;
+    }
+    let Some(serenity::Error::Http(error)) = error.downcast_ref::<serenity::Error>() else {
+        return false;
+    };
+    error
+        .status_code()
+        .is_some_and(|status| status.as_u16() == 401)
+}
+
+/// Check an error for an auth failure, and stop any more requests to Discord if it is one.
+/// This is printed rather than sent to the log channel, because posting there needs the same token.
+/// Returns whether the error was an auth failure.
+pub(crate) fn note_failure(error: &Error) -> bool {
+    if !is_auth_failure(error) {
+        return false;
+    }
+    if !TOKEN_REJECTED.swap(true, Ordering::Relaxed) {
+        println!(
+            "FATAL: Discord rejected the bot's token ({error}). \
+            Reminders will stay queued until the bot is restarted with a valid token."
+        );
+    }
+    true
+}
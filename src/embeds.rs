@@ -0,0 +1,216 @@
+use super::errors::Error;
+use super::Context;
+use crate::serenity;
+use std::future::Future;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+/// Maximum number of characters Discord allows in a plain message
+pub(crate) const MAX_MESSAGE_LENGTH: usize = 2000;
+/// Maximum number of fields Discord allows in a single embed
+pub(crate) const MAX_FIELDS: usize = 25;
+/// Maximum number of characters Discord allows across all embeds in a message
+pub(crate) const MAX_CHARACTERS: usize = 6000;
+/// Maximum number of characters in an embed title
+pub(crate) const MAX_TITLE: usize = 256;
+/// Maximum number of characters in an embed description
+pub(crate) const MAX_DESCRIPTION: usize = 4096;
+/// Maximum number of characters in a field name
+const MAX_FIELD_NAME: usize = 256;
+/// Maximum number of characters in a field value
+pub(crate) const MAX_FIELD_VALUE: usize = 1024;
+/// Characters set aside on each page for the "Page x of y" footer
+const FOOTER_ALLOWANCE: usize = 32;
+/// How long Discord accepts responses to an interaction, after which its token expires
+const INTERACTION_LIFETIME: Duration = Duration::from_mins(15);
+/// Stop using the interaction a little early, so a slow request doesn't straddle the expiry
+const EXPIRY_MARGIN: Duration = Duration::from_secs(30);
+
+/// How the bot's embeds look when nothing more specific applies
+#[derive(Debug, Clone)]
+pub(crate) struct Theme {
+    /// Accent colour, replaced by colours that mean something like quake intensity
+    pub(crate) colour: serenity::Colour,
+    /// Footer text, replaced by footers that carry information like page numbers
+    pub(crate) footer: Option<String>,
+    /// Url of an icon shown beside the footer text
+    pub(crate) footer_icon: Option<String>,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            colour: serenity::Colour::BLURPLE,
+            footer: None,
+            footer_icon: None,
+        }
+    }
+}
+
+/// Theme set from the config at startup, see [`set_theme`]
+static THEME: OnceLock<Theme> = OnceLock::new();
+
+/// Set the theme used by [`branded_embed`]. Only the first call has any effect.
+pub(crate) fn set_theme(theme: Theme) {
+    let _ = THEME.set(theme);
+}
+
+/// The configured theme, or the default one if none has been set
+fn theme() -> &'static Theme {
+    THEME.get_or_init(Theme::default)
+}
+
+/// An empty embed in the bot's colour and with its footer.
+/// Every embed should start from this. Setting a colour or footer afterwards replaces the themed one.
+pub(crate) fn branded_embed() -> serenity::CreateEmbed {
+    let theme = theme();
+    let mut embed = serenity::CreateEmbed::default().colour(theme.colour);
+    if let Some(text) = &theme.footer {
+        let mut footer = serenity::CreateEmbedFooter::new(text);
+        if let Some(icon) = &theme.footer_icon {
+            footer = footer.icon_url(icon);
+        }
+        embed = embed.footer(footer);
+    }
+    embed
+}
+
+/// A field destined for an embed, stored as `(name, value, inline)`
+/// to match [`serenity::CreateEmbed::fields`]
+pub(crate) type Field = (String, String, bool);
+
+/// Cut a string down to at most `limit` characters, marking the cut with an ellipsis
+pub(crate) fn truncate(text: String, limit: usize) -> String {
+    if text.chars().count() <= limit {
+        return text;
+    }
+    let mut cut: String = text.chars().take(limit.saturating_sub(1)).collect();
+    cut.push('…');
+    cut
+}
+
+/// Split a list of fields into pages that each fit in a single embed.
+/// `header_length` is the number of characters the rest of the embed
+/// (title, description, etc) uses, which counts towards the character limit.
+/// Oversized field names and values are truncated.
+pub(crate) fn chunk_fields(fields: Vec<Field>, header_length: usize) -> Vec<Vec<Field>> {
+    // single pages keep the themed footer, so leave room for whichever is longer
+    let footer_length = theme()
+        .footer
+        .as_ref()
+        .map_or(0, |f| f.chars().count())
+        .max(FOOTER_ALLOWANCE);
+    let budget = MAX_CHARACTERS.saturating_sub(header_length + footer_length);
+    let mut pages = Vec::new();
+    let mut page: Vec<Field> = Vec::new();
+    let mut page_length = 0;
+
+    for (name, value, inline) in fields {
+        let name = truncate(name, MAX_FIELD_NAME);
+        let value = truncate(value, MAX_FIELD_VALUE);
+        let length = name.chars().count() + value.chars().count();
+
+        // start a new page if this field won't fit on the current one
+        if !page.is_empty() && (page.len() == MAX_FIELDS || page_length + length > budget) {
+            pages.push(std::mem::take(&mut page));
+            page_length = 0;
+        }
+        page_length += length;
+        page.push((name, value, inline));
+    }
+
+    if !page.is_empty() {
+        pages.push(page);
+    }
+    pages
+}
+
+/// Send a list of fields as one or more embeds, each based on `template`.
+/// Every embed goes in its own message because Discord's character limit
+/// applies to the message as a whole, not each embed.
+/// `header_length` is as for [`chunk_fields`].
+pub(crate) async fn send_paginated(
+    ctx: Context<'_>,
+    template: &serenity::CreateEmbed,
+    header_length: usize,
+    fields: Vec<Field>,
+) -> Result<(), Error> {
+    let pages = chunk_fields(fields, header_length);
+    let page_count = pages.len();
+
+    for (index, page) in pages.into_iter().enumerate() {
+        let mut embed = template.clone().fields(page);
+        if page_count > 1 {
+            embed = embed.footer(serenity::CreateEmbedFooter::new(format!(
+                "Page {} of {page_count}",
+                index + 1
+            )));
+        }
+        // the first send responds to the interaction and the rest become follow ups
+        respond(ctx, poise::CreateReply::default().embed(embed)).await?;
+    }
+    Ok(())
+}
+
+/// Whether an interaction is too old to respond to, or will be very soon
+fn interaction_expired(ctx: Context<'_>) -> bool {
+    let age = serenity::Timestamp::now().unix_timestamp() - ctx.created_at().unix_timestamp();
+    u64::try_from(age)
+        .is_ok_and(|age| age + EXPIRY_MARGIN.as_secs() >= INTERACTION_LIFETIME.as_secs())
+}
+
+/// Reply to a command, whether or not its interaction has expired.
+/// Usually this is just [`Context::send`], which responds to the interaction or follows
+/// up on it. Interactions can only be responded to for 15 minutes though, so a command
+/// that waited on the user for longer posts in the channel instead. Ephemeral replies
+/// go to the user's DMs then, so they stay private.
+pub(crate) async fn respond(ctx: Context<'_>, reply: poise::CreateReply) -> Result<(), Error> {
+    if !interaction_expired(ctx) {
+        ctx.send(reply).await?;
+        return Ok(());
+    }
+
+    let mut message = serenity::CreateMessage::default().embeds(reply.embeds);
+    if let Some(content) = reply.content {
+        message = message.content(content);
+    }
+    if let Some(components) = reply.components {
+        message = message.components(components);
+    }
+    if reply.ephemeral.unwrap_or(ctx.command().ephemeral) {
+        ctx.author().direct_message(ctx, message).await?;
+    } else {
+        ctx.channel_id().send_message(ctx, message).await?;
+    }
+    Ok(())
+}
+
+/// Show `placeholder` while `work` runs, then replace it with the reply `work` produces.
+/// Commands are already deferred, but Discord's "is thinking..." doesn't say what we're
+/// waiting on. If `work` fails the placeholder is deleted, leaving the error handler's
+/// message on its own.
+pub(crate) async fn with_placeholder(
+    ctx: Context<'_>,
+    placeholder: &str,
+    work: impl Future<Output = Result<poise::CreateReply, Error>>,
+) -> Result<(), Error> {
+    let handle = ctx
+        .send(poise::CreateReply::default().content(placeholder))
+        .await?;
+    match work.await {
+        Ok(mut reply) => {
+            // edits keep anything left out, so clear the placeholder text explicitly
+            if reply.content.is_none() {
+                reply = reply.content("");
+            }
+            handle.edit(ctx, reply).await?;
+            Ok(())
+        }
+        Err(e) => {
+            if let Err(delete_error) = handle.delete(ctx).await {
+                println!("Unable to delete placeholder `{placeholder}`: {delete_error:?}");
+            }
+            Err(e)
+        }
+    }
+}
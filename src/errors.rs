@@ -1,6 +1,9 @@
+use super::analytics;
+use super::database::from_db_id;
+use super::embeds;
 use super::Data;
 use crate::{serenity, Context};
-use poise::FrameworkError;
+use poise::{BoxFuture, FrameworkError};
 
 /// Standard type for errors
 pub(crate) type Error = Box<dyn std::error::Error + Send + Sync>;
@@ -9,22 +12,75 @@ pub(crate) type Error = Box<dyn std::error::Error + Send + Sync>;
 /// We implement custom handling for some errors and forward the rest onto the default handler.
 /// Currently we have custom handling for [`FrameworkError::Setup`] and [`FrameworkError::Command`].
 pub(crate) async fn on_error(error: FrameworkError<'_, Data, Error>) {
-    match error {
-        FrameworkError::Setup { error, .. } => panic!("Failed to start bot: {error:?}"),
-        FrameworkError::Command { error, ctx, .. } => send_error_message(ctx, error).await,
-        error => delegate_to_default_handler(error).await,
+    // any error tied to a command means that invocation failed
+    if let Some(ctx) = error.ctx() {
+        ctx.data().watchdog.finish(ctx);
+        analytics::record_invocation(ctx, false);
+    }
+
+    let failure = match error {
+        FrameworkError::Setup { error, .. } => Failure::Setup(error),
+        FrameworkError::Command { error, ctx, .. } => Failure::Command(ctx, error),
+        error => Failure::Other(error),
+    };
+    handle(failure, &Discord).await;
+}
+
+/// The errors [`on_error`] tells apart, with everything it doesn't care about stripped out.
+/// `C` is the context of a failed command and `E` is any other error.
+/// These are generic so tests can make them without a running bot.
+enum Failure<C, E> {
+    /// The bot couldn't start
+    Setup(Error),
+    /// A command returned an error
+    Command(C, Error),
+    /// Anything else, which poise knows how to deal with
+    Other(E),
+}
+
+/// Where [`handle`] sends errors.
+/// These return boxed futures because the compiler can't prove `async fn`s here
+/// are `Send` for every lifetime poise hands us.
+trait ErrorSink<'a, C, E> {
+    /// Tell the user who ran a command that it failed
+    fn command_failed(&self, ctx: C, error: Error) -> BoxFuture<'a, ()>;
+    /// Pass an error on to poise's default handler
+    fn delegate(&self, error: E) -> BoxFuture<'a, ()>;
+}
+
+/// Route an error to the right place.
+/// We can't do anything useful if setup fails, so we bring the whole bot down.
+async fn handle<'a, C, E>(failure: Failure<C, E>, sink: &impl ErrorSink<'a, C, E>) {
+    match failure {
+        Failure::Setup(error) => panic!("Failed to start bot: {error:?}"),
+        Failure::Command(ctx, error) => sink.command_failed(ctx, error).await,
+        Failure::Other(error) => sink.delegate(error).await,
+    }
+}
+
+/// The real [`ErrorSink`], which talks to Discord
+struct Discord;
+
+impl<'a> ErrorSink<'a, Context<'a>, FrameworkError<'a, Data, Error>> for Discord {
+    fn command_failed(&self, ctx: Context<'a>, error: Error) -> BoxFuture<'a, ()> {
+        Box::pin(send_error_message(ctx, error))
+    }
+
+    fn delegate(&self, error: FrameworkError<'a, Data, Error>) -> BoxFuture<'a, ()> {
+        Box::pin(delegate_to_default_handler(error))
     }
 }
 
 /// Send an error message to Discord in response to a com,amd
 async fn send_error_message(ctx: Context<'_>, error: Error) {
     // log the error locally first
-    println!("Error in command `{}`: {:?}", ctx.command().name, error,);
+    println!("Error in command `{}`: {:?}", ctx.command().name, error);
+    analytics::record_error(ctx, &error);
     // then try to tell the user about it
     if let Err(e) = ctx
         .send(
             poise::CreateReply::default().embed(
-                serenity::CreateEmbed::default()
+                embeds::branded_embed()
                     .colour(serenity::Colour::RED)
                     .title("Error")
                     .description(error.to_string()),
@@ -34,8 +90,15 @@ async fn send_error_message(ctx: Context<'_>, error: Error) {
     {
         // if we've gotten here something has gone wrong telling the user
         // log that too so the admin might notice one day
-        // (probably not though)
-        println!("Error while reporting error: {e}");
+        // (more likely now there's a log channel)
+        ctx.data()
+            .log_channel
+            .log(
+                ctx.http(),
+                "Error while reporting error",
+                &format!("Command `{}`: {e}", ctx.command().name),
+            )
+            .await;
     }
 }
 
@@ -45,3 +108,93 @@ async fn delegate_to_default_handler(error: FrameworkError<'_, Data, Error>) {
         println!("Error while handling error: {e}");
     }
 }
+
+/// List the most recent command errors
+#[poise::command(slash_command, owners_only, ephemeral)]
+pub(crate) async fn errors(
+    ctx: Context<'_>,
+    #[description = "Number of errors to show, defaults to 10"]
+    #[min = 1]
+    #[max = 100]
+    count: Option<i64>,
+) -> Result<(), Error> {
+    let errors = ctx
+        .data()
+        .analytics
+        .as_ref()
+        .ok_or("Errors aren't stored because the bot has no database")?
+        .recent_errors(count.unwrap_or(10))
+        .await?;
+    if errors.is_empty() {
+        ctx.say("No command errors recorded").await?;
+        return Ok(());
+    }
+
+    let fields = errors
+        .into_iter()
+        .map(|e| {
+            (
+                format!("/{}", e.command),
+                format!(
+                    "<t:{}:R> by <@{}>\n{}",
+                    e.occurred_at.timestamp(),
+                    from_db_id(e.user_id),
+                    e.error
+                ),
+                false,
+            )
+        })
+        .collect();
+    let title = "Recent command errors";
+    let template = embeds::branded_embed()
+        .title(title)
+        .colour(serenity::Colour::RED);
+    embeds::send_paginated(ctx, &template, title.len(), fields).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{handle, BoxFuture, Error, ErrorSink, Failure};
+    use std::sync::Mutex;
+
+    /// Records where errors were sent instead of sending them
+    #[derive(Default)]
+    struct Recorder {
+        sent: Mutex<Vec<String>>,
+    }
+
+    impl<'a> ErrorSink<'a, &'a str, &'a str> for Recorder {
+        fn command_failed(&self, ctx: &str, error: Error) -> BoxFuture<'a, ()> {
+            self.sent
+                .lock()
+                .unwrap()
+                .push(format!("user {ctx}: {error}"));
+            Box::pin(async {})
+        }
+
+        fn delegate(&self, error: &str) -> BoxFuture<'a, ()> {
+            self.sent.lock().unwrap().push(format!("default: {error}"));
+            Box::pin(async {})
+        }
+    }
+
+    #[tokio::test]
+    async fn command_errors_go_to_the_user() {
+        let recorder = Recorder::default();
+        handle(Failure::Command("roll", "bad dice".into()), &recorder).await;
+        assert_eq!(*recorder.sent.lock().unwrap(), ["user roll: bad dice"]);
+    }
+
+    #[tokio::test]
+    async fn other_errors_go_to_poise() {
+        let recorder = Recorder::default();
+        handle(Failure::Other("cooldown"), &recorder).await;
+        assert_eq!(*recorder.sent.lock().unwrap(), ["default: cooldown"]);
+    }
+
+    #[tokio::test]
+    #[should_panic(expected = "Failed to start bot")]
+    async fn setup_errors_stop_the_bot() {
+        handle::<&str, &str>(Failure::Setup("no token".into()), &Recorder::default()).await;
+    }
+}
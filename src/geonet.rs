@@ -0,0 +1,221 @@
+use super::circuit_breaker::CircuitBreaker;
+use super::errors::Error;
+use super::quake::{validate_quake_id, Quake, SearchedQuake, Station};
+use super::volcano::Volcano;
+use chrono::NaiveDate;
+use reqwest::Url;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::cmp::Reverse;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Base url for the geonet api, without a trailing slash
+const GEONET_API: &str = "https://api.geonet.org.nz";
+/// Every part of the api we use speaks version 2 of geonet's geojson format
+const GEOJSON: &str = "application/vnd.geo+json;version=2";
+/// Where geonet publishes the shakemap image for a quake, given its public id
+const SHAKEMAP_URL: &str = "https://static.geonet.org.nz/shakemap";
+
+/// Geonet's web feature service, which unlike the api can search the whole quake catalogue
+const QUAKE_SEARCH: &str = "https://wfs.geonet.org.nz/geonet/ows";
+/// Most quakes a single search returns
+pub(crate) const MAX_SEARCH_RESULTS: usize = 100;
+
+/// Url for the list of quakes at or above the given MMI
+fn quake_url(mmi: i8) -> String {
+    format!("{GEONET_API}/quake?MMI={mmi}")
+}
+
+/// Geonet wraps everything in a geojson feature collection.
+/// The only information we care about is the list in the `features` key.
+#[derive(Debug, Clone, Deserialize)]
+struct FeatureList<T> {
+    features: Vec<T>,
+}
+
+/// Talks to the geonet api. Every request shares one connection pool and goes
+/// through one circuit breaker, so together they back off when geonet is down.
+/// Cheap to clone, clones share the pool and breaker.
+#[derive(Clone)]
+pub(crate) struct GeoNetClient {
+    http: reqwest::Client,
+    breaker: Arc<CircuitBreaker>,
+}
+
+impl GeoNetClient {
+    /// Create a client that sends requests through `breaker`
+    pub(crate) fn new(http: reqwest::Client, breaker: Arc<CircuitBreaker>) -> Self {
+        GeoNetClient { http, breaker }
+    }
+
+    /// Fetch a feature collection from the api, given a url.
+    /// Returns `None` if geonet 404s, which it does for things it has no data on.
+    /// That isn't a sign geonet is down, so it doesn't count against the breaker.
+    async fn features<T: DeserializeOwned>(&self, url: String) -> Result<Option<Vec<T>>, Error> {
+        self.send_features(self.http.get(url).header("Accept", GEOJSON))
+            .await
+    }
+
+    /// Send a request for a feature collection, through the breaker.
+    /// A 404 gives `None`, as for [`Self::features`].
+    async fn send_features<T: DeserializeOwned>(
+        &self,
+        request: reqwest::RequestBuilder,
+    ) -> Result<Option<Vec<T>>, Error> {
+        let request = async {
+            let response = request.send().await?;
+            if response.status() == reqwest::StatusCode::NOT_FOUND {
+                return Ok(None);
+            }
+            let list = response
+                .error_for_status()?
+                .json::<FeatureList<T>>()
+                .await?;
+            Ok(Some(list.features))
+        };
+        self.breaker.call(request).await
+    }
+
+    /// All quakes at or above the given MMI, sorted from most to least recent
+    pub(crate) async fn quakes(&self, mmi: i8) -> Result<Vec<Quake>, Error> {
+        let mut quakes = self
+            .features::<Quake>(quake_url(mmi))
+            .await?
+            .ok_or("GeoNet couldn't find its quake list")?;
+        quakes.sort_by_key(|a| Reverse(a.properties.time));
+        Ok(quakes)
+    }
+
+    /// Look up a single quake by its geonet public id
+    pub(crate) async fn quake(&self, public_id: &str) -> Result<Quake, Error> {
+        validate_quake_id(public_id)?;
+        self.features(format!("{GEONET_API}/quake/{public_id}"))
+            .await?
+            .and_then(|quakes| quakes.into_iter().next())
+            .ok_or_else(|| format!("No quake found with ID `{public_id}`").into())
+    }
+
+    /// The strong motion readings for a quake, sorted from strongest to weakest
+    /// peak ground acceleration. Quakes without any strong motion data return an empty list.
+    pub(crate) async fn strong_motion(&self, public_id: &str) -> Result<Vec<Station>, Error> {
+        validate_quake_id(public_id)?;
+        let url = format!("{GEONET_API}/intensity/strong/processed/{public_id}");
+        let mut stations: Vec<Station> = self.features(url).await?.unwrap_or_default();
+        stations.sort_by(|a, b| {
+            b.properties
+                .peak_acceleration()
+                .total_cmp(&a.properties.peak_acceleration())
+        });
+        Ok(stations)
+    }
+
+    /// Find the shakemap image for a quake, if geonet has made one.
+    /// Only larger quakes get shakemaps, so a missing image is expected and any
+    /// failure just means the quake is shown without one. This deliberately skips
+    /// the circuit breaker, the image is optional and shouldn't trip it for the quake api.
+    pub(crate) async fn shakemap(&self, public_id: &str) -> Option<String> {
+        // public ids are alphanumeric, anything else shouldn't end up in a url
+        if !public_id.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return None;
+        }
+        let url = format!("{SHAKEMAP_URL}/{public_id}/intensity.jpg");
+
+        let response = self
+            .http
+            .head(&url)
+            .timeout(Duration::from_secs(5))
+            .send()
+            .await
+            .ok()?;
+        let is_image = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.starts_with("image/"));
+        (response.status().is_success() && is_image).then_some(url)
+    }
+
+    /// Search the quake catalogue for quakes between two dates, both inclusive and in UTC,
+    /// optionally only those whose public id starts with `prefix`.
+    /// Returns the [`MAX_SEARCH_RESULTS`] most recent matching quakes at most, sorted from most to least recent.
+    pub(crate) async fn search_quakes(
+        &self,
+        from: NaiveDate,
+        to: NaiveDate,
+        prefix: Option<&str>,
+    ) -> Result<Vec<SearchedQuake>, Error> {
+        let request = self.http.get(search_url(from, to, prefix)?);
+        let mut quakes = self
+            .send_features::<SearchedQuake>(request)
+            .await?
+            .ok_or("GeoNet couldn't find its quake catalogue")?;
+        quakes.sort_by_key(|q| Reverse(q.properties.time));
+        Ok(quakes)
+    }
+
+    /// The current volcanic alert level of every volcano geonet monitors
+    pub(crate) async fn volcano_alerts(&self) -> Result<Vec<Volcano>, Error> {
+        self.features(format!("{GEONET_API}/volcano/val"))
+            .await?
+            .ok_or_else(|| "GeoNet couldn't find its volcano alert levels".into())
+    }
+}
+
+/// Url for a quake catalogue search, see [`GeoNetClient::search_quakes`]
+fn search_url(from: NaiveDate, to: NaiveDate, prefix: Option<&str>) -> Result<Url, Error> {
+    let end = to
+        .succ_opt()
+        .ok_or("That end date is too far in the future")?;
+    // the filter is a little query language, so only let known safe text into it
+    let mut filter = format!("origintime>='{from}' AND origintime<'{end}'");
+    if let Some(prefix) = prefix {
+        validate_quake_id(prefix)?;
+        filter = format!("{filter} AND publicid LIKE '{prefix}%'");
+    }
+    let url = Url::parse_with_params(
+        QUAKE_SEARCH,
+        [
+            ("service", "WFS"),
+            ("version", "1.0.0"),
+            ("request", "GetFeature"),
+            ("typeName", "geonet:quake_search_v1"),
+            ("outputFormat", "json"),
+            ("maxFeatures", &MAX_SEARCH_RESULTS.to_string()),
+            // newest first, so a search with too many results is cut short at the oldest end
+            ("sortBy", "origintime D"),
+            ("cql_filter", &filter),
+        ],
+    )?;
+    Ok(url)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{quake_url, search_url};
+    use chrono::NaiveDate;
+
+    #[test]
+    fn quake_url_is_well_formed() {
+        assert_eq!(quake_url(3), "https://api.geonet.org.nz/quake?MMI=3");
+
+        let url = reqwest::Url::parse(&quake_url(-1)).unwrap();
+        assert_eq!(url.host_str(), Some("api.geonet.org.nz"));
+        assert_eq!(url.path(), "/quake");
+        assert_eq!(url.query(), Some("MMI=-1"));
+        assert!(!url.as_str()["https://".len()..].contains("//"));
+    }
+
+    #[test]
+    fn search_filters_by_date_and_prefix() {
+        let day = |d| NaiveDate::from_ymd_opt(2024, 1, d).unwrap();
+        let url = search_url(day(1), day(7), Some("2024p")).unwrap();
+        let (_, filter) = url.query_pairs().find(|(k, _)| k == "cql_filter").unwrap();
+        assert_eq!(
+            filter,
+            "origintime>='2024-01-01' AND origintime<'2024-01-08' AND publicid LIKE '2024p%'"
+        );
+        assert!(url.query().unwrap().contains("sortBy=origintime+D"));
+        assert!(search_url(day(1), day(1), Some("2024p' OR '1'='1")).is_err());
+    }
+}
@@ -0,0 +1,275 @@
+use super::database::{to_db_id, StatementCache};
+use super::errors::Error;
+use super::Context;
+use poise::serenity_prelude::GuildId;
+use poise::ChoiceParameter;
+use std::sync::Arc;
+use tokio_postgres::{types::Type, Client, Statement};
+
+/// Longest wording a server may give a string
+const MAX_OVERRIDE_LENGTH: usize = 32;
+
+/// Output strings a server can replace with its own wording.
+/// Only what the bot says can be changed, command names stay the same everywhere.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, poise::ChoiceParameter)]
+pub(crate) enum GuildString {
+    /// What reminders are called in replies, e.g. the `Reminder` in `Reminder #5 created`
+    #[name = "reminder name"]
+    ReminderName,
+}
+
+impl GuildString {
+    /// Every string that can be changed
+    const ALL: [GuildString; 1] = [GuildString::ReminderName];
+
+    /// The wording used when a server hasn't picked its own
+    pub(crate) fn default_text(self) -> &'static str {
+        match self {
+            GuildString::ReminderName => "Reminder",
+        }
+    }
+
+    /// Convert to the number stored in the database
+    fn to_db(self) -> i16 {
+        match self {
+            GuildString::ReminderName => 0,
+        }
+    }
+
+    /// Convert from the number stored in the database.
+    /// Strings this build doesn't know about are `None`, so they're ignored rather than misused.
+    fn from_db(value: i16) -> Option<Self> {
+        Self::ALL.into_iter().find(|s| s.to_db() == value)
+    }
+}
+
+/// Check a server's wording for a string, returning it tidied up.
+/// Wording is kept short and can't mention anyone, since it's shown to everyone in the server.
+fn validate_override(text: &str) -> Result<String, Error> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Err("The new wording can't be empty, use reset to go back to the default".into());
+    }
+    if text.chars().count() > MAX_OVERRIDE_LENGTH {
+        return Err(
+            format!("The new wording can be at most {MAX_OVERRIDE_LENGTH} characters").into(),
+        );
+    }
+    if text.contains(['@', '<', '>', '\n']) {
+        return Err("The new wording can't contain mentions or line breaks".into());
+    }
+    Ok(text.to_string())
+}
+
+/// Helper struct for storing each server's wording for output strings.
+/// Use the methods on this struct rather than directly acessing the fields.
+pub(crate) struct GuildStringsDatabase {
+    /// The database client used to interact with postgres
+    client: Arc<Client>,
+    /// A prepared database statement that fetches a server's wording for a string
+    get: Statement,
+    /// A prepared database statement that lists all of a server's wording
+    list: Statement,
+    /// A prepared database statement that sets a server's wording for a string
+    set: Statement,
+    /// A prepared database statement that goes back to the default wording for a string
+    clear: Statement,
+}
+
+impl GuildStringsDatabase {
+    /// Set up the strings table and statements on the connection the cache is for
+    pub(crate) async fn new(statements: &StatementCache) -> Result<Self, Error> {
+        let client = statements.client();
+        // Init table if required
+        client
+            .execute(
+                "CREATE TABLE IF NOT EXISTS guild_strings (
+                            guild_id BIGINT NOT NULL,
+                            key SMALLINT NOT NULL,
+                            value TEXT NOT NULL,
+                            PRIMARY KEY (guild_id, key)
+                        )",
+                &[],
+            )
+            .await?;
+
+        let get = statements
+            .prepare_typed(
+                "SELECT value FROM guild_strings WHERE guild_id = $1 AND key = $2",
+                &[Type::INT8, Type::INT2],
+            )
+            .await?;
+        let list = statements
+            .prepare_typed(
+                "SELECT key, value FROM guild_strings WHERE guild_id = $1 ORDER BY key",
+                &[Type::INT8],
+            )
+            .await?;
+        let set = statements
+            .prepare_typed(
+                "INSERT INTO guild_strings (guild_id, key, value) values ($1, $2, $3)
+                    ON CONFLICT (guild_id, key) DO UPDATE SET value = EXCLUDED.value",
+                &[Type::INT8, Type::INT2, Type::TEXT],
+            )
+            .await?;
+        let clear = statements
+            .prepare_typed(
+                "DELETE FROM guild_strings WHERE guild_id = $1 AND key = $2",
+                &[Type::INT8, Type::INT2],
+            )
+            .await?;
+
+        Ok(GuildStringsDatabase {
+            client,
+            get,
+            list,
+            set,
+            clear,
+        })
+    }
+
+    /// Get a server's wording for a string, or `None` if it uses the default
+    async fn get(&self, guild_id: GuildId, key: GuildString) -> Result<Option<String>, Error> {
+        let row = self
+            .client
+            .query_opt(&self.get, &[&to_db_id(guild_id), &key.to_db()])
+            .await?;
+        Ok(row.map(|row| row.get(0)))
+    }
+
+    /// List the strings a server has changed, with their wording
+    async fn list(&self, guild_id: GuildId) -> Result<Vec<(GuildString, String)>, Error> {
+        let rows = self
+            .client
+            .query(&self.list, &[&to_db_id(guild_id)])
+            .await?;
+        Ok(rows
+            .iter()
+            .filter_map(|row| Some((GuildString::from_db(row.get(0))?, row.get(1))))
+            .collect())
+    }
+
+    /// Set a server's wording for a string
+    async fn set(&self, guild_id: GuildId, key: GuildString, value: &str) -> Result<(), Error> {
+        self.client
+            .execute(&self.set, &[&to_db_id(guild_id), &key.to_db(), &value])
+            .await?;
+        Ok(())
+    }
+
+    /// Go back to the default wording for a string in a server
+    async fn clear(&self, guild_id: GuildId, key: GuildString) -> Result<(), Error> {
+        self.client
+            .execute(&self.clear, &[&to_db_id(guild_id), &key.to_db()])
+            .await?;
+        Ok(())
+    }
+}
+
+/// The wording to use for a string where a command was used.
+/// Outside servers, without a database, or if the lookup fails, this is the default wording,
+/// since a custom name isn't worth failing a command over.
+pub(crate) async fn guild_string(ctx: Context<'_>, key: GuildString) -> String {
+    let (Some(database), Some(guild_id)) = (&ctx.data().guild_strings, ctx.guild_id()) else {
+        return key.default_text().to_string();
+    };
+    match database.get(guild_id, key).await {
+        Ok(Some(text)) => text,
+        Ok(None) => key.default_text().to_string(),
+        Err(e) => {
+            println!("Unable to get {} wording for {guild_id}: {e:?}", key.name());
+            key.default_text().to_string()
+        }
+    }
+}
+
+/// Change the bot's wording for some of its replies in this server
+#[poise::command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    default_member_permissions = "MANAGE_GUILD"
+)]
+pub(crate) async fn serverstrings(
+    ctx: Context<'_>,
+    #[description = "The wording to change, leave out to see what's been changed"] string: Option<
+        GuildString,
+    >,
+    #[description = "The new wording"] text: Option<String>,
+    #[description = "Go back to the default wording"] reset: Option<bool>,
+) -> Result<(), Error> {
+    let database = ctx
+        .data()
+        .guild_strings
+        .as_ref()
+        .ok_or("Server strings are disabled because the bot has no database")?;
+    let guild_id = ctx
+        .guild_id()
+        .ok_or("Server strings only work in servers")?;
+
+    let Some(string) = string else {
+        let changed = database.list(guild_id).await?;
+        if changed.is_empty() {
+            ctx.say("This server uses the default wording for everything")
+                .await?;
+            return Ok(());
+        }
+        let lines: Vec<String> = changed
+            .iter()
+            .map(|(string, text)| {
+                format!(
+                    "{}: {text} (default {})",
+                    string.name(),
+                    string.default_text()
+                )
+            })
+            .collect();
+        ctx.say(format!("This server's wording\n{}", lines.join("\n")))
+            .await?;
+        return Ok(());
+    };
+
+    match (text, reset.unwrap_or(false)) {
+        (Some(_), true) => Err("Give new wording or reset it, not both".into()),
+        (None, false) => Err("Give the new wording, or reset to go back to the default".into()),
+        (None, true) => {
+            database.clear(guild_id, string).await?;
+            ctx.say(format!(
+                "The {} is back to \"{}\"",
+                string.name(),
+                string.default_text()
+            ))
+            .await?;
+            Ok(())
+        }
+        (Some(text), false) => {
+            let text = validate_override(&text)?;
+            database.set(guild_id, string, &text).await?;
+            ctx.say(format!("The {} is now \"{text}\"", string.name()))
+                .await?;
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{validate_override, GuildString};
+
+    #[test]
+    fn strings_round_trip_through_the_database() {
+        for string in GuildString::ALL {
+            assert_eq!(GuildString::from_db(string.to_db()), Some(string));
+        }
+        assert_eq!(GuildString::from_db(-1), None);
+    }
+
+    #[test]
+    fn overrides_are_short_and_harmless() {
+        assert_eq!(validate_override("  Nudge ").unwrap(), "Nudge");
+        assert!(validate_override(" ").is_err());
+        assert!(validate_override(&"a".repeat(33)).is_err());
+        assert!(validate_override("@everyone").is_err());
+        assert!(validate_override("<@1234>").is_err());
+    }
+}
@@ -0,0 +1,290 @@
+use super::errors::Error;
+use super::reminders::PriorityChoice;
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use serde::Deserialize;
+
+/// Most reminders that can be imported from one file
+pub(crate) const MAX_IMPORT: usize = 100;
+
+/// A reminder read from an imported file, ready to be added to the database
+#[derive(Debug, PartialEq)]
+pub(crate) struct ImportedReminder {
+    pub(crate) due_at: DateTime<Utc>,
+    pub(crate) message: String,
+    pub(crate) priority: PriorityChoice,
+}
+
+/// A reminder in a JSON import, before validation
+#[derive(Debug, Deserialize)]
+struct JsonReminder {
+    /// RFC 3339 timestamp, e.g. `2025-01-01T09:00:00+13:00`
+    due_at: String,
+    message: String,
+    /// `low`, `normal`, `high` or `critical`
+    #[serde(default)]
+    priority: Option<String>,
+}
+
+/// Parse an uploaded file into reminders, picking the format from the file extension.
+/// Times without an offset are read in `offset`. Each entry is validated separately,
+/// so a bad entry gives an error for that entry without spoiling the rest of the file.
+/// Only a file that can't be read at all is an error overall.
+pub(crate) fn parse_file(
+    filename: &str,
+    contents: &str,
+    offset: FixedOffset,
+    now: DateTime<Utc>,
+) -> Result<Vec<Result<ImportedReminder, String>>, Error> {
+    let extension = filename
+        .rsplit_once('.')
+        .map(|(_, ext)| ext.to_ascii_lowercase());
+    let entries = match extension.as_deref() {
+        Some("json") => parse_json(contents)?,
+        Some("ics") => parse_ics(contents, offset),
+        _ => return Err("Reminders can only be imported from .json or .ics files".into()),
+    };
+    if entries.len() > MAX_IMPORT {
+        return Err(format!("Files can contain at most {MAX_IMPORT} reminders").into());
+    }
+
+    // number entries from one to match how people count
+    Ok(entries
+        .into_iter()
+        .enumerate()
+        .map(|(i, entry)| {
+            entry
+                .and_then(|r| validate(r, now))
+                .map_err(|e| format!("Entry {}: {e}", i + 1))
+        })
+        .collect())
+}
+
+/// Check a reminder is something we're willing to schedule
+fn validate(reminder: ImportedReminder, now: DateTime<Utc>) -> Result<ImportedReminder, String> {
+    if reminder.message.trim().is_empty() {
+        return Err("the message is empty".to_string());
+    }
+    if reminder.due_at <= now {
+        return Err(format!(
+            "<t:{}> is in the past",
+            reminder.due_at.timestamp()
+        ));
+    }
+    Ok(reminder)
+}
+
+/// Read a JSON array of reminders
+fn parse_json(contents: &str) -> Result<Vec<Result<ImportedReminder, String>>, Error> {
+    let values: Vec<serde_json::Value> = serde_json::from_str(contents)
+        .map_err(|e| format!("The file isn't a JSON array of reminders: {e}"))?;
+
+    Ok(values
+        .into_iter()
+        .map(|value| {
+            let entry: JsonReminder = serde_json::from_value(value).map_err(|e| e.to_string())?;
+            let due_at = DateTime::parse_from_rfc3339(&entry.due_at)
+                .map_err(|e| format!("`{}` isn't a valid time: {e}", entry.due_at))?
+                .to_utc();
+            let priority = match entry.priority.as_deref() {
+                None | Some("normal") => PriorityChoice::Normal,
+                Some("low") => PriorityChoice::Low,
+                Some("high") => PriorityChoice::High,
+                Some("critical") => PriorityChoice::Critical,
+                Some(other) => return Err(format!("`{other}` isn't a priority")),
+            };
+            Ok(ImportedReminder {
+                due_at,
+                message: entry.message,
+                priority,
+            })
+        })
+        .collect())
+}
+
+/// The parts of an iCalendar event we care about, while it is being read
+#[derive(Default)]
+struct IcsEvent {
+    start: Option<Result<DateTime<Utc>, String>>,
+    summary: Option<String>,
+    priority: PriorityChoice,
+}
+
+/// Read the events in an iCalendar file as reminders, due at the start of each event.
+/// Only the parts of the format we need are handled: `DTSTART`, `SUMMARY` and `PRIORITY`.
+/// Named time zones (`TZID`) aren't supported because we only know UTC offsets.
+fn parse_ics(contents: &str, offset: FixedOffset) -> Vec<Result<ImportedReminder, String>> {
+    // long lines are folded by starting the continuation with whitespace
+    let mut lines: Vec<String> = Vec::new();
+    for line in contents.lines() {
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(rest), Some(last)) => last.push_str(rest),
+            _ => lines.push(line.to_string()),
+        }
+    }
+
+    let mut entries = Vec::new();
+    let mut event: Option<IcsEvent> = None;
+    for line in lines {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let (name, params) = key.split_once(';').unwrap_or((key, ""));
+        match (name.to_ascii_uppercase().as_str(), event.as_mut()) {
+            ("BEGIN", _) if value.eq_ignore_ascii_case("VEVENT") => {
+                event = Some(IcsEvent::default());
+            }
+            ("END", Some(_)) if value.eq_ignore_ascii_case("VEVENT") => {
+                let Some(IcsEvent {
+                    start,
+                    summary,
+                    priority,
+                }) = event.take()
+                else {
+                    continue;
+                };
+                entries.push(match (start, summary) {
+                    (Some(Ok(due_at)), Some(message)) => Ok(ImportedReminder {
+                        due_at,
+                        message,
+                        priority,
+                    }),
+                    (Some(Err(e)), _) => Err(e),
+                    (None, _) => Err("the event has no start time".to_string()),
+                    (_, None) => Err("the event has no summary".to_string()),
+                });
+            }
+            ("DTSTART", Some(event)) => event.start = Some(parse_ics_time(params, value, offset)),
+            ("SUMMARY", Some(event)) => event.summary = Some(unescape_ics(value)),
+            ("PRIORITY", Some(event)) => {
+                // 1 is the most important and 9 the least, 0 means undefined
+                event.priority = match value.trim().parse::<u8>() {
+                    Ok(1..=4) => PriorityChoice::High,
+                    Ok(6..=9) => PriorityChoice::Low,
+                    _ => PriorityChoice::Normal,
+                };
+            }
+            _ => (),
+        }
+    }
+    entries
+}
+
+/// Parse an iCalendar `DTSTART` value. Floating times and all day events use `offset`.
+fn parse_ics_time(params: &str, value: &str, offset: FixedOffset) -> Result<DateTime<Utc>, String> {
+    if params.to_ascii_uppercase().contains("TZID=") {
+        return Err("named time zones aren't supported, use UTC times".to_string());
+    }
+    let value = value.trim();
+    let time = if let Some(utc) = value.strip_suffix('Z') {
+        NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S")
+            .ok()
+            .map(|t| t.and_utc())
+    } else {
+        // a bare date is an all day event, which we treat as starting at midnight
+        let local = if value.len() == 8 {
+            NaiveDate::parse_from_str(value, "%Y%m%d")
+                .ok()
+                .and_then(|d| d.and_hms_opt(0, 0, 0))
+        } else {
+            NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok()
+        };
+        local
+            .and_then(|t| offset.from_local_datetime(&t).single())
+            .map(|t| t.to_utc())
+    };
+    time.ok_or_else(|| format!("`{value}` isn't a valid time"))
+}
+
+/// Undo iCalendar's text escaping
+fn unescape_ics(value: &str) -> String {
+    let mut text = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            text.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n' | 'N') => text.push('\n'),
+            Some(other) => text.push(other),
+            None => text.push('\\'),
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_file, ImportedReminder, PriorityChoice};
+    use chrono::{DateTime, FixedOffset, TimeZone, Utc};
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap()
+    }
+
+    fn nzdt() -> FixedOffset {
+        FixedOffset::east_opt(13 * 3600).unwrap()
+    }
+
+    #[test]
+    fn json_entries_are_checked_separately() {
+        let json = r#"[
+            {"due_at": "2025-06-01T09:00:00+12:00", "message": "dentist", "priority": "high"},
+            {"due_at": "2024-06-01T09:00:00Z", "message": "too late"},
+            {"due_at": "tomorrow", "message": "vague"},
+            {"message": "no time"}
+        ]"#;
+        let entries = parse_file("reminders.JSON", json, nzdt(), now()).unwrap();
+        assert_eq!(
+            entries[0],
+            Ok(ImportedReminder {
+                due_at: Utc.with_ymd_and_hms(2025, 5, 31, 21, 0, 0).unwrap(),
+                message: "dentist".to_string(),
+                priority: PriorityChoice::High,
+            })
+        );
+        assert!(entries[1].as_ref().unwrap_err().contains("in the past"));
+        assert!(entries[2].as_ref().unwrap_err().starts_with("Entry 3:"));
+        assert!(entries[3].is_err());
+    }
+
+    #[test]
+    fn ics_events_become_reminders() {
+        let ics = "BEGIN:VCALENDAR\r\n\
+            BEGIN:VEVENT\r\n\
+            DTSTART:20250301T020000Z\r\n\
+            SUMMARY:Call mum\\, then\r\n  dad\r\n\
+            PRIORITY:9\r\n\
+            END:VEVENT\r\n\
+            BEGIN:VEVENT\r\n\
+            DTSTART;VALUE=DATE:20250302\r\n\
+            SUMMARY:Bin day\r\n\
+            END:VEVENT\r\n\
+            BEGIN:VEVENT\r\n\
+            DTSTART;TZID=Pacific/Auckland:20250303T090000\r\n\
+            SUMMARY:Meeting\r\n\
+            END:VEVENT\r\n\
+            END:VCALENDAR\r\n";
+        let entries = parse_file("calendar.ics", ics, nzdt(), now()).unwrap();
+        assert_eq!(
+            entries[0],
+            Ok(ImportedReminder {
+                due_at: Utc.with_ymd_and_hms(2025, 3, 1, 2, 0, 0).unwrap(),
+                message: "Call mum, then dad".to_string(),
+                priority: PriorityChoice::Low,
+            })
+        );
+        // all day events are due at midnight in the user's time zone
+        assert_eq!(
+            entries[1].as_ref().unwrap().due_at,
+            Utc.with_ymd_and_hms(2025, 3, 1, 11, 0, 0).unwrap()
+        );
+        assert!(entries[2].is_err());
+    }
+
+    #[test]
+    fn unknown_formats_are_rejected() {
+        assert!(parse_file("reminders.txt", "", nzdt(), now()).is_err());
+        assert!(parse_file("reminders.json", "{}", nzdt(), now()).is_err());
+    }
+}
@@ -0,0 +1,44 @@
+use super::discord_auth;
+use super::embeds;
+use crate::serenity;
+use poise::serenity_prelude::{ChannelId, Colour, CreateMessage};
+
+/// A Discord channel where important events are posted, so operators
+/// notice problems without having to read the logs.
+/// This is optional, in which case events are only printed.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct LogChannel(Option<ChannelId>);
+
+impl LogChannel {
+    /// Post events to the given channel, or nowhere if it is `None`
+    pub(crate) fn new(channel: Option<ChannelId>) -> Self {
+        LogChannel(channel)
+    }
+
+    /// Print an important event, then post it to the log channel if there is one.
+    /// Long descriptions are cut short in the channel but printed in full.
+    /// Nothing is posted once Discord has rejected the bot's token.
+    /// Failing to post is printed but otherwise ignored, because there's nowhere left to report it.
+    pub(crate) async fn log(self, http: &serenity::Http, title: &str, description: &str) {
+        println!("{title}: {description}");
+
+        // posting needs the same token, so don't bother once Discord has refused it
+        let Some(channel) = self.0.filter(|_| !discord_auth::token_rejected()) else {
+            return;
+        };
+        let embed = embeds::branded_embed()
+            .title(title)
+            .description(embeds::truncate(
+                description.to_string(),
+                embeds::MAX_DESCRIPTION,
+            ))
+            .colour(Colour::ORANGE)
+            .timestamp(serenity::Timestamp::now());
+        if let Err(e) = channel
+            .send_message(http, CreateMessage::default().embed(embed))
+            .await
+        {
+            println!("Unable to post to log channel: {e}");
+        }
+    }
+}
@@ -1,16 +1,61 @@
-use crate::dice::roll;
+use crate::config::Feature;
+use crate::dice::{
+    abilities, fate, initiative, percentile, roll, rollagainst, rollpool, rolluntil,
+};
 use crate::quake::quake;
 use poise::serenity_prelude::{self as serenity};
 use std::sync::Arc;
+use std::time::Duration;
 
+mod admin;
+mod analytics;
+mod character_sheet;
+mod circuit_breaker;
+mod config;
+mod database;
 mod dice;
+mod discord_auth;
+mod embeds;
 mod errors;
+mod geonet;
+mod guild_strings;
+mod import;
+mod log_channel;
+mod postgres_tls;
 mod quake;
+mod quake_subscriptions;
 mod reminders;
+mod roll_history;
+mod roll_settings;
+mod supervisor;
+mod volcano;
+mod watchdog;
+mod webhook;
 
 /// User data, which is stored and accessible in all command invocations
 struct Data {
-    database: Arc<reminders::ReminderDatabase>,
+    /// Stores reminders, if we have a database
+    database: Option<Arc<reminders::ReminderDatabase>>,
+    /// Records command usage, if we have a database
+    analytics: Option<Arc<analytics::AnalyticsDatabase>>,
+    /// Stores each user's `/roll` defaults, if we have a database
+    roll_settings: Option<Arc<roll_settings::RollSettingsDatabase>>,
+    /// Stores each user's character sheet attributes for `/roll`, if we have a database
+    character_sheets: Option<Arc<character_sheet::CharacterSheetDatabase>>,
+    /// Stores natural 20s rolled in servers for leaderboards, if we have a database
+    roll_history: Option<Arc<roll_history::RollHistoryDatabase>>,
+    /// Stores each server's wording for output strings, if we have a database
+    guild_strings: Option<Arc<guild_strings::GuildStringsDatabase>>,
+    /// Stores who wants a DM about new quakes, if we have a database
+    quake_subscriptions: Option<Arc<quake_subscriptions::QuakeSubscriptionsDatabase>>,
+    /// Talks to geonet for quake and volcano data
+    geonet: geonet::GeoNetClient,
+    /// Seeds for dice rolls, drawn from a generator per guild
+    dice_seeds: dice::SeedBank,
+    /// Where to post important events for operators
+    log_channel: log_channel::LogChannel,
+    /// Warns users when a command is taking too long
+    watchdog: watchdog::Watchdog,
 }
 
 /// Helper type copied from the poise demo
@@ -21,52 +66,268 @@ type Context<'a> = poise::Context<'a, Data, errors::Error>;
 /// Entry point. Setup and launch the bot.
 #[tokio::main]
 async fn main() {
-    // Load the discord token. If it doesn't exist then panic.
-    let token = std::env::var("DISCORD_TOKEN").expect("missing DISCORD_TOKEN");
-
-    // Load the database URL. If it doesn't exist return an empty string
-    // which should mean we use the tokio-postgres defaults.
-    let data_path = std::env::var("DATABASE_URL").unwrap_or_default();
-    let database = Arc::new(
-        reminders::ReminderDatabase::connect(data_path)
-            .await
-            .unwrap(),
-    );
+    // Load the config from the environment. This panics if anything is missing or malformed.
+    let config = config::Config::from_env();
+    embeds::set_theme(config.theme.clone());
+
+    // The database is optional, dice and quakes work fine without it
+    let databases = if let Some(url) = &config.database_url {
+        match connect_databases(url, &config).await {
+            Ok(databases) => databases,
+            // carrying on would break the newer bot's data, so this one is fatal
+            Err(e) if e.is::<database::SchemaMismatch>() => panic!("{e}"),
+            Err(e) => {
+                println!("Unable to connect to the database, reminders are disabled: {e:?}");
+                Databases::default()
+            }
+        }
+    } else {
+        println!("DATABASE_URL is not set, reminders are disabled");
+        Databases::default()
+    };
+    let Databases {
+        reminders: database,
+        analytics,
+        roll_settings,
+        character_sheets,
+        roll_history,
+        guild_strings,
+        quake_subscriptions,
+    } = databases;
     // make a clone of the database for use in the closure below
     // this needs to happen here because rust spots errors if we try to `database.clone` in the framework setup
     // well there'll be an actual reason, but i'm just trusting the compiler
     let db = database.clone();
+    let log_channel = log_channel::LogChannel::new(config.log_channel);
+    let command_timeout = config.command_timeout;
+    // the quake alert poller shares this with commands, so it needs its own handle
+    let geonet = geonet::GeoNetClient::new(
+        reqwest::Client::new(),
+        Arc::new(circuit_breaker::CircuitBreaker::new(
+            "GeoNet",
+            3,
+            Duration::from_secs(30),
+            Duration::from_mins(10),
+        )),
+    );
+    let poller_geonet = geonet.clone();
+    let poller_subscriptions = quake_subscriptions.clone();
+    let self_check_user = config.self_check_user;
 
     // prepare the bot frameowrk
     let framework = poise::Framework::builder()
         .options(poise::FrameworkOptions {
-            // REGISTER COMMANDS HERE
-            commands: vec![quake(), reminders::remindme(), roll()],
+            commands: commands(&config),
             // register our custom error handler too
             on_error: |error| Box::pin(errors::on_error(error)),
+            // and a handler for events that aren't commands, like button presses
+            event_handler: |ctx, event, _framework, data| Box::pin(event_handler(ctx, event, data)),
+            // defer every command and watch for ones that hang
+            pre_command: |ctx| Box::pin(before_command(ctx)),
+            // record successful commands, failures are recorded by the error handler
+            post_command: |ctx| {
+                Box::pin(async move {
+                    ctx.data().watchdog.finish(ctx);
+                    analytics::record_invocation(ctx, true);
+                })
+            },
             // and fall back to the default for everything else
             ..Default::default()
         })
-        .setup(|ctx, _ready, framework| {
+        .setup(move |ctx, _ready, framework| {
             Box::pin(async move {
                 poise::builtins::register_globally(ctx, &framework.options().commands).await?;
+                // make sure DMs work now, rather than finding out when a reminder is due
+                if let Some(user) = self_check_user {
+                    tokio::spawn(reminders::dm_self_check(
+                        ctx.http.clone(),
+                        user,
+                        log_channel,
+                    ));
+                }
                 // Setup the user data struct
-                Ok(Data { database: db })
+                Ok(Data {
+                    database: db,
+                    analytics,
+                    roll_settings,
+                    character_sheets,
+                    roll_history,
+                    guild_strings,
+                    quake_subscriptions,
+                    geonet,
+                    dice_seeds: dice::SeedBank::default(),
+                    log_channel,
+                    watchdog: watchdog::Watchdog::new(command_timeout),
+                })
             })
         })
         .build();
 
     // create the bot client
     let intents = serenity::GatewayIntents::non_privileged();
-    let mut client = serenity::ClientBuilder::new(token, intents)
+    let mut client = serenity::ClientBuilder::new(&config.discord_token, intents)
         .framework(framework)
         .await
         .unwrap();
 
     // Spawn the reminder tasks
     // We do it now so we can pass it the bot and reuse its cache
-    reminders::spawn_reminder_tasks(database.clone(), client.http.clone()).await;
+    if let Some(database) = database {
+        reminders::spawn_reminder_tasks(database, client.http.clone(), log_channel).await;
+    }
+
+    // Post new quakes as they happen, if there's a channel or anyone who could subscribe
+    spawn_quake_alerts(
+        &config,
+        client.http.clone(),
+        poller_geonet,
+        poller_subscriptions,
+        log_channel,
+    );
 
     // Start the client
     client.start().await.unwrap();
 }
+
+/// Start posting new quakes, if there's an alert channel or a database for subscriptions
+fn spawn_quake_alerts(
+    config: &config::Config,
+    bot: Arc<serenity::Http>,
+    geonet: geonet::GeoNetClient,
+    subscriptions: Option<Arc<quake_subscriptions::QuakeSubscriptionsDatabase>>,
+    log_channel: log_channel::LogChannel,
+) {
+    // subscriptions are managed with /quakes, so without it there's nothing to deliver to
+    // and the DMs would point people at a command that isn't there
+    let subscriptions = subscriptions.filter(|_| config.enabled(Feature::Quakes));
+    if !config.enabled(Feature::QuakeAlerts)
+        || (config.quake_alert_channel.is_none() && subscriptions.is_none())
+    {
+        return;
+    }
+    let (min_interval, max_interval) = config.quake_poll_bounds;
+    let alerts = quake::QuakeAlerts {
+        channel: config.quake_alert_channel,
+        mmi: config.quake_alert_mmi,
+        subscriptions,
+        min_interval,
+        max_interval,
+    };
+    alerts.spawn(bot, geonet, log_channel);
+}
+
+/// The commands to register, leaving out any the operator has disabled.
+/// Admin commands are always available.
+fn commands(config: &config::Config) -> Vec<poise::Command<Data, errors::Error>> {
+    // REGISTER COMMANDS HERE
+    let mut commands = vec![admin::admin()];
+    if config.enabled(Feature::Dice) {
+        commands.extend([
+            abilities(),
+            character_sheet::sheet(),
+            fate(),
+            percentile(),
+            initiative(),
+            roll(),
+            roll_history::rollleaderboard(),
+            roll_settings::rollsettings(),
+            rolluntil(),
+            rollagainst(),
+            rollpool(),
+        ]);
+    }
+    if config.enabled(Feature::Quakes) {
+        commands.extend([quake(), quake::quakes(), quake::strongmotion()]);
+    }
+    if config.enabled(Feature::Reminders) {
+        commands.extend([
+            reminders::remind_about_message(),
+            reminders::remindme(),
+            reminders::timestamp(),
+            guild_strings::serverstrings(),
+        ]);
+    }
+    if config.enabled(Feature::Volcanoes) {
+        commands.push(volcano::volcano());
+    }
+    commands
+}
+
+/// Get a command going before it runs.
+/// Deferring tells Discord we're working on it, so slow commands don't time out.
+/// Commands marked `ephemeral` defer ephemerally so only the user sees the reply.
+async fn before_command(ctx: Context<'_>) {
+    ctx.data().watchdog.start(ctx);
+    let deferred = if ctx.command().ephemeral {
+        ctx.defer_ephemeral().await
+    } else {
+        ctx.defer().await
+    };
+    if let Err(e) = deferred {
+        println!(
+            "Unable to defer command `{}`: {e:?}",
+            ctx.command().qualified_name
+        );
+    }
+}
+
+/// Handle gateway events that aren't commands.
+/// Currently that's presses of the button acknowledging a critical reminder,
+/// and choices from the timezone menu offered to new users.
+async fn event_handler(
+    ctx: &serenity::Context,
+    event: &serenity::FullEvent,
+    data: &Data,
+) -> Result<(), errors::Error> {
+    if let serenity::FullEvent::InteractionCreate {
+        interaction: serenity::Interaction::Component(press),
+    } = event
+    {
+        reminders::acknowledge(ctx, data.database.as_deref(), press).await?;
+        reminders::choose_timezone(ctx, data.database.as_deref(), press).await?;
+    }
+    Ok(())
+}
+
+/// The helpers for each part of the bot that uses the database, which are all `None` without one
+#[derive(Default)]
+struct Databases {
+    reminders: Option<Arc<reminders::ReminderDatabase>>,
+    analytics: Option<Arc<analytics::AnalyticsDatabase>>,
+    roll_settings: Option<Arc<roll_settings::RollSettingsDatabase>>,
+    character_sheets: Option<Arc<character_sheet::CharacterSheetDatabase>>,
+    roll_history: Option<Arc<roll_history::RollHistoryDatabase>>,
+    guild_strings: Option<Arc<guild_strings::GuildStringsDatabase>>,
+    quake_subscriptions: Option<Arc<quake_subscriptions::QuakeSubscriptionsDatabase>>,
+}
+
+/// Connect to the database and set up the helpers for each part of the bot that uses it
+async fn connect_databases(url: &str, config: &config::Config) -> Result<Databases, errors::Error> {
+    let client = database::connect(url).await?;
+    database::check_schema_version(&client).await?;
+    let statements = database::StatementCache::new(client.clone());
+    let reminders = reminders::ReminderDatabase::new(
+        &statements,
+        config.max_reminders,
+        config.max_user_reminders,
+        config.max_delivery_attempts,
+    )
+    .await?;
+    let analytics = analytics::AnalyticsDatabase::new(&statements).await?;
+    let roll_settings = roll_settings::RollSettingsDatabase::new(&statements).await?;
+    let character_sheets = character_sheet::CharacterSheetDatabase::new(&statements).await?;
+    let roll_history = roll_history::RollHistoryDatabase::new(&statements).await?;
+    let guild_strings = guild_strings::GuildStringsDatabase::new(&statements).await?;
+    let quake_subscriptions =
+        quake_subscriptions::QuakeSubscriptionsDatabase::new(&statements).await?;
+    database::record_schema_version(&client).await?;
+    Ok(Databases {
+        reminders: Some(Arc::new(reminders)),
+        analytics: Some(Arc::new(analytics)),
+        roll_settings: Some(Arc::new(roll_settings)),
+        character_sheets: Some(Arc::new(character_sheets)),
+        roll_history: Some(Arc::new(roll_history)),
+        guild_strings: Some(Arc::new(guild_strings)),
+        quake_subscriptions: Some(Arc::new(quake_subscriptions)),
+    })
+}
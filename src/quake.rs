@@ -1,46 +1,159 @@
+use super::embeds;
 use super::errors::Error;
-use super::Context;
+use super::geonet::{GeoNetClient, MAX_SEARCH_RESULTS};
+use super::log_channel::LogChannel;
+use super::quake_subscriptions::{
+    subscribe, subscriptions, QuakeSubscriptionsDatabase, MIN_SUBSCRIPTION_MMI,
+};
+use super::supervisor::supervise;
+use super::{Context, Data};
 use crate::serenity;
+use chrono::NaiveDate;
 use iso8601_timestamp::Timestamp;
-use poise::serenity_prelude::Colour;
+use poise::serenity_prelude::{futures::future, ChannelId, Colour};
 use serde::Deserialize;
+use std::sync::Arc;
+use std::time::Duration;
 
 /// This structure corresponds to the `properties` compound in
 /// the data structure for a quake in the geonet api.
-/// Used to deserialize JSON quake data with Serde
+/// Used to deserialize JSON quake data with Serde.
+/// Only the id and time are required. Geonet could drop or null out the rest,
+/// and one missing detail shouldn't stop the whole quake being shown.
 #[derive(Debug, Clone, Deserialize)]
-struct QuakeProperties {
+pub(crate) struct QuakeProperties {
     #[serde(rename = "publicID")] // rename to match rust style conventions
     pub(crate) public_id: String,
     pub(crate) time: Timestamp,
-    pub(crate) depth: f64,
-    pub(crate) locality: String,
-    pub(crate) magnitude: f64,
-    pub(crate) mmi: i8,
-    pub(crate) quality: String,
+    #[serde(default)]
+    pub(crate) depth: Option<f64>,
+    #[serde(default)]
+    pub(crate) locality: Option<String>,
+    #[serde(default)]
+    pub(crate) magnitude: Option<f64>,
+    #[serde(default)]
+    pub(crate) mmi: Option<i8>,
+    #[serde(default)]
+    pub(crate) quality: Option<String>,
+}
+
+/// What we show in place of a detail geonet left out
+const UNKNOWN: &str = "unknown";
+
+/// Show a detail geonet may have left out, or [`UNKNOWN`] if it did
+fn or_unknown<T>(value: Option<T>, show: impl FnOnce(T) -> String) -> String {
+    value.map_or_else(|| UNKNOWN.to_string(), show)
+}
+
+/// Show a magnitude briefly, e.g. `M4.2`, or `M?` if it's missing
+fn short_magnitude(magnitude: Option<f64>) -> String {
+    magnitude.map_or_else(|| "M?".to_string(), |m| format!("M{m:.1}"))
+}
+
+/// Units for displaying quake depth
+#[derive(Debug, Default, Clone, Copy, poise::ChoiceParameter)]
+enum DepthUnit {
+    #[default]
+    #[name = "km"]
+    Kilometres,
+    #[name = "miles"]
+    Miles,
+}
+
+/// Format a depth given in km in the requested units, to the given number of decimal places
+fn format_depth(depth_km: f64, unit: DepthUnit, precision: usize) -> String {
+    match unit {
+        DepthUnit::Kilometres => format!("{depth_km:.precision$} km"),
+        DepthUnit::Miles => format!("{:.precision$} mi", depth_km * 0.621_371),
+    }
+}
+
+/// Describe a depth for lists, e.g. `12.3 km deep`, allowing for it to be missing
+fn describe_depth(depth_km: Option<f64>, unit: DepthUnit) -> String {
+    depth_km.map_or_else(
+        || "unknown depth".to_string(),
+        |d| format!("{} deep", format_depth(d, unit, 1)),
+    )
+}
+
+/// Convert a timestamp to whole seconds since the unix epoch, for Discord's `<t:...>` markup.
+/// Sub-second precision is rounded down rather than towards zero, so times before
+/// the epoch land on the second they fall within instead of the one after.
+fn unix_seconds(time: Timestamp) -> i64 {
+    let since_epoch = time.duration_since(Timestamp::UNIX_EPOCH);
+    let seconds = since_epoch.whole_seconds();
+    if since_epoch.subsec_nanoseconds() < 0 {
+        seconds - 1
+    } else {
+        seconds
+    }
+}
+
+/// The `geometry` compound of a quake in the geonet api, a point in longitude and latitude
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct QuakeGeometry {
+    /// Longitude then latitude, in degrees
+    pub(crate) coordinates: [f64; 2],
 }
 
 /// A quake, as repesented by geonet
 #[derive(Debug, Clone, Deserialize)]
-struct Quake {
-    // We ignore the geometry compound and only keep the `properties`
+pub(crate) struct Quake {
+    /// Where the quake was, if geonet told us
+    #[serde(default)]
+    pub(crate) geometry: Option<QuakeGeometry>,
     pub(crate) properties: QuakeProperties,
 }
 
+/// Link to a point on OpenStreetMap, given in degrees
+fn map_url(latitude: f64, longitude: f64) -> String {
+    format!(
+        "https://www.openstreetmap.org/?mlat={latitude:.4}&mlon={longitude:.4}#map=9/{latitude:.4}/{longitude:.4}"
+    )
+}
+
+/// Mean radius of the Earth in kilometres
+const EARTH_RADIUS_KM: f64 = 6371.0;
+
+/// Great-circle distance in kilometres between two points given as (latitude, longitude)
+/// in degrees, using the haversine formula
+fn haversine_km((lat_a, lon_a): (f64, f64), (lat_b, lon_b): (f64, f64)) -> f64 {
+    let (lat_a, lat_b) = (lat_a.to_radians(), lat_b.to_radians());
+    let d_lat = lat_b - lat_a;
+    let d_lon = (lon_b - lon_a).to_radians();
+    let h = (d_lat / 2.0).sin().powi(2) + lat_a.cos() * lat_b.cos() * (d_lon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_KM * h.sqrt().asin()
+}
+
+/// Find the quake closest to a point, given as (latitude, longitude) in degrees,
+/// along with its distance in kilometres. Quakes without coordinates are skipped.
+fn nearest_quake(quakes: &[Quake], point: (f64, f64)) -> Option<(&Quake, f64)> {
+    quakes
+        .iter()
+        .filter_map(|q| {
+            let [lon, lat] = q.geometry.as_ref()?.coordinates;
+            Some((q, haversine_km(point, (lat, lon))))
+        })
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+}
+
 // Define some methods for the Quake struct
 impl Quake {
     /// Convert a [`Quake`] to a [`serenity::CreateEmbed`],
-    /// a builder for an embed in a Discord message
-    fn create_embed(&self, mmi: i8) -> serenity::CreateEmbed {
+    /// a builder for an embed in a Discord message.
+    /// The shakemap, if there is one, is shown as the embed's image.
+    fn create_embed(
+        &self,
+        mmi: i8,
+        depth_unit: DepthUnit,
+        shakemap: Option<String>,
+    ) -> serenity::CreateEmbed {
         // Prepare some data
         let properties = &self.properties;
-        let timestamp = properties
-            .time
-            .duration_since(Timestamp::UNIX_EPOCH)
-            .whole_seconds();
+        let timestamp = unix_seconds(properties.time);
 
         // Create the embed
-        serenity::CreateEmbed::default()
+        let embed = embeds::branded_embed()
             .url(format!(
                 "https://www.geonet.org.nz/earthquake/{}",
                 properties.public_id
@@ -51,12 +164,39 @@ impl Quake {
                 // Special handling for a very bad day
                 8..=i8::MAX => format!("Well, fuck. Most recent quake with MMI >= {mmi}"),
             })
-            .field("Magnitude", format!("{:.3}", properties.magnitude), true)
-            .field("MMI", properties.mmi.to_string(), true)
-            .field("Depth", format!("{:.3} km", properties.depth), true)
+            .field(
+                "Magnitude",
+                or_unknown(properties.magnitude, |m| format!("{m:.3}")),
+                true,
+            )
+            .field("MMI", or_unknown(properties.mmi, |m| m.to_string()), true)
+            .field(
+                "Depth",
+                or_unknown(properties.depth, |d| format_depth(d, depth_unit, 3)),
+                true,
+            )
             .field("Time", format!("<t:{timestamp}:R>"), true)
-            .field("Quality", properties.quality.clone(), true)
-            .field("Location", &properties.locality, true)
+            .field(
+                "Quality",
+                or_unknown(properties.quality.as_deref(), str::to_string),
+                true,
+            )
+            .field(
+                "Location",
+                or_unknown(properties.locality.as_deref(), str::to_string),
+                true,
+            )
+            .fields(self.geometry.as_ref().map(|g| {
+                let [longitude, latitude] = g.coordinates;
+                (
+                    "Coordinates",
+                    format!(
+                        "[{latitude:.3}, {longitude:.3}]({})",
+                        map_url(latitude, longitude)
+                    ),
+                    true,
+                )
+            }))
             // Colour code the embed to match the severity
             .color(match mmi {
                 i8::MIN..=0 => Colour::LIGHT_GREY,
@@ -68,38 +208,235 @@ impl Quake {
                 6 => Colour::from_rgb(244, 124, 104),
                 7 => Colour::from_rgb(213, 98, 79),
                 8..=i8::MAX => Colour::from_rgb(153, 45, 34),
-            })
+            });
+        match shakemap {
+            Some(url) => embed.image(url),
+            None => embed,
+        }
+    }
+
+    /// Summarise a [`Quake`] as a single embed field, for use in lists
+    fn create_field(&self, depth_unit: DepthUnit) -> embeds::Field {
+        let properties = &self.properties;
+        let timestamp = unix_seconds(properties.time);
+
+        (
+            format!(
+                "{} {}",
+                short_magnitude(properties.magnitude),
+                properties.locality.as_deref().unwrap_or("unknown location")
+            ),
+            format!(
+                "MMI {}, {}, <t:{timestamp}:R>\n[{id}](https://www.geonet.org.nz/earthquake/{id})",
+                or_unknown(properties.mmi, |m| m.to_string()),
+                describe_depth(properties.depth, depth_unit),
+                id = properties.public_id
+            ),
+            false,
+        )
+    }
+
+    /// Summarise a [`Quake`] in one line, for channels that don't want an embed,
+    /// e.g. `M4.2 quake 10 km east of Seddon, 15.0 km deep, MMI 4, 3 minutes ago`
+    fn create_summary(&self, depth_unit: DepthUnit) -> String {
+        let properties = &self.properties;
+        format!(
+            "{} quake {}, {}, MMI {}, <t:{}:R>",
+            short_magnitude(properties.magnitude),
+            properties
+                .locality
+                .as_deref()
+                .unwrap_or("at an unknown location"),
+            describe_depth(properties.depth, depth_unit),
+            or_unknown(properties.mmi, |m| m.to_string()),
+            unix_seconds(properties.time)
+        )
     }
 }
 
-/// A structure for deserializing geonet's quake list
+/// The `properties` compound of a quake in geonet's quake catalogue search.
+/// The catalogue has fewer details than the api, notably no locality or intensity.
 #[derive(Debug, Clone, Deserialize)]
-struct QuakeList {
-    // The only information we care about is the list of quakes in the `feature` key
-    pub(crate) features: Vec<Quake>,
+pub(crate) struct SearchedQuakeProperties {
+    #[serde(rename = "publicid")]
+    pub(crate) public_id: String,
+    #[serde(rename = "origintime")]
+    pub(crate) time: Timestamp,
+    /// Depth in km, if it has been worked out
+    pub(crate) depth: Option<f64>,
+    /// Magnitude, if it has been worked out
+    pub(crate) magnitude: Option<f64>,
+}
+
+/// A quake from geonet's quake catalogue search
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct SearchedQuake {
+    #[serde(default)]
+    pub(crate) geometry: Option<QuakeGeometry>,
+    pub(crate) properties: SearchedQuakeProperties,
+}
+
+impl SearchedQuake {
+    /// Summarise a search result as a single embed field, like [`Quake::create_field`]
+    fn create_field(&self, depth_unit: DepthUnit) -> embeds::Field {
+        let properties = &self.properties;
+        let magnitude = short_magnitude(properties.magnitude);
+        let place = self.geometry.as_ref().map_or_else(
+            || "unknown location".to_string(),
+            |g| {
+                let [lon, lat] = g.coordinates;
+                format!("[{lat:.2}, {lon:.2}]({})", map_url(lat, lon))
+            },
+        );
+        let depth = describe_depth(properties.depth, depth_unit);
+
+        (
+            format!("{magnitude} {}", properties.public_id),
+            format!(
+                "{place}, {depth}, <t:{}:f>\n[{id}](https://www.geonet.org.nz/earthquake/{id})",
+                unix_seconds(properties.time),
+                id = properties.public_id
+            ),
+            false,
+        )
+    }
+}
+
+/// Parse a date given as `YYYY-MM-DD`
+fn parse_date(input: &str) -> Result<NaiveDate, Error> {
+    input
+        .trim()
+        .parse()
+        .map_err(|_| format!("`{input}` isn't a date, use the form 2024-01-31").into())
+}
+
+/// Keep the quakes at or after `cutoff`, given in unix seconds.
+/// The quakes must be sorted from most to least recent, as [`GeoNetClient::quakes`] returns them.
+fn quakes_since(quakes: Vec<Quake>, cutoff: i64) -> Vec<Quake> {
+    quakes
+        .into_iter()
+        .take_while(|q| unix_seconds(q.properties.time) >= cutoff)
+        .collect()
+}
+
+/// The quake with the largest magnitude, if there are any.
+/// Quakes without a magnitude only win if none have one.
+fn strongest(quakes: &[Quake]) -> Option<&Quake> {
+    let magnitude = |q: &Quake| q.properties.magnitude.unwrap_or(f64::NEG_INFINITY);
+    quakes
+        .iter()
+        .max_by(|a, b| magnitude(a).total_cmp(&magnitude(b)))
 }
 
 /// Poll geonet for all quakes at or above the given API and return the
 /// most recent. If no such quake exists then return an error.
-async fn get_quake(mmi: i8) -> Result<Quake, Error> {
-    let url = format!("https://api.geonet.org.nz/quake?MMI={mmi}");
-    let client = reqwest::Client::new();
-
-    let mut quakes = client
-        .get(url)
-        .header("Accept", "application/vnd.geo+json;version=2")
-        .send()
-        .await?
-        .json::<QuakeList>()
+async fn get_quake(data: &Data, mmi: i8) -> Result<Quake, Error> {
+    data.geonet
+        .quakes(mmi)
         .await?
-        .features;
-
-    quakes.sort_by(|a, b| a.properties.time.cmp(&b.properties.time));
-    quakes
-        .pop()
+        .into_iter()
+        .next()
         .ok_or("No quakes found with the required intensity".into())
 }
 
+/// Lowest and highest MMI the quake commands accept, matching their option bounds.
+/// Geonet goes down to -1, but quakes that weak aren't felt.
+const MMI_RANGE: std::ops::RangeInclusive<i8> = 0..=8;
+/// MMI used when a command isn't given one, around where quakes start to be widely felt
+const DEFAULT_MMI: i8 = 3;
+
+/// Minimum intensity in words, for people who don't know MMI numbers.
+/// Each is the MMI geonet gives that description.
+#[derive(Debug, Clone, Copy, poise::ChoiceParameter)]
+enum IntensityChoice {
+    #[name = "weak"]
+    Weak,
+    #[name = "moderate"]
+    Moderate,
+    #[name = "strong"]
+    Strong,
+    #[name = "severe"]
+    Severe,
+}
+
+impl IntensityChoice {
+    /// The MMI this description starts at
+    fn mmi(self) -> i8 {
+        match self {
+            IntensityChoice::Weak => 3,
+            IntensityChoice::Moderate => 5,
+            IntensityChoice::Strong => 6,
+            IntensityChoice::Severe => 7,
+        }
+    }
+}
+
+/// Check a minimum MMI given to a command, either as a number or in words,
+/// filling in the default if it was left out.
+/// Discord enforces the same bounds, but we don't rely on it to.
+fn check_minimum_mmi(mmi: Option<i8>, intensity: Option<IntensityChoice>) -> Result<i8, Error> {
+    let mmi = match (mmi, intensity) {
+        (Some(_), Some(_)) => {
+            return Err("Give a minimum intensity as a number or in words, not both".into())
+        }
+        (_, Some(intensity)) => intensity.mmi(),
+        (mmi, None) => mmi.unwrap_or(DEFAULT_MMI),
+    };
+    if !MMI_RANGE.contains(&mmi) {
+        return Err(format!(
+            "Minimum intensity must be from {} to {}, not {mmi}",
+            MMI_RANGE.start(),
+            MMI_RANGE.end()
+        )
+        .into());
+    }
+    Ok(mmi)
+}
+
+/// Quakes `/quakes list` shows when it isn't given a count
+const DEFAULT_LISTED_QUAKES: usize = 10;
+/// Most quakes `/quakes list` can be asked for, matching its option bounds
+const MAX_REQUESTED_QUAKES: usize = 100;
+/// Most quakes `/quakes list` actually shows, however many are asked for.
+/// Past this the reply is several pages of embeds, which is more spam than answer.
+const MAX_LISTED_QUAKES: usize = 25;
+
+/// Check how many quakes `/quakes list` was asked for, filling in the default if it was left out.
+/// Returns how many of the `available` quakes to show, and whether that's fewer than asked
+/// for because of [`MAX_LISTED_QUAKES`].
+fn listed_count(requested: Option<usize>, available: usize) -> Result<(usize, bool), Error> {
+    let requested = requested.unwrap_or(DEFAULT_LISTED_QUAKES);
+    if !(1..=MAX_REQUESTED_QUAKES).contains(&requested) {
+        return Err(format!(
+            "Number of quakes must be from 1 to {MAX_REQUESTED_QUAKES}, not {requested}"
+        )
+        .into());
+    }
+    let shown = requested.min(MAX_LISTED_QUAKES).min(available);
+    Ok((shown, shown < requested.min(available)))
+}
+
+/// Check a quake id is safe to put in a url path, so it can't wander off somewhere else
+pub(crate) fn validate_quake_id(public_id: &str) -> Result<(), Error> {
+    if public_id.is_empty() || !public_id.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(format!("`{public_id}` is not a valid quake ID").into());
+    }
+    Ok(())
+}
+
+/// Search, compare and subscribe to quakes from geonet
+///
+/// These are kept apart from `/quake`, because Discord doesn't allow running a command
+/// that has subcommands and `/quake` needs to keep working on its own.
+#[poise::command(
+    slash_command,
+    subcommands("list", "find", "nearest", "compare", "subscribe", "subscriptions")
+)]
+pub(crate) async fn quakes(ctx: Context<'_>) -> Result<(), Error> {
+    ctx.say("Please use a subcommand").await?;
+    Ok(())
+}
+
 /// Displays the most recent quake >= specified intensity (MMI)
 #[poise::command(slash_command)]
 pub(crate) async fn quake(
@@ -109,16 +446,817 @@ pub(crate) async fn quake(
     #[min = 0]
     #[max = 8]
     minimum_mmi: Option<i8>,
+    #[description = "Or the minimum intensity in words"] intensity: Option<IntensityChoice>,
+    #[description = "Units for depth, defaults to km"] depth_unit: Option<DepthUnit>,
+    #[description = "Reply with one line instead of an embed"] compact: Option<bool>,
 ) -> Result<(), Error> {
-    // let the server know we're thinking about it
-    ctx.defer().await?;
+    let mmi = check_minimum_mmi(minimum_mmi, intensity)?;
+    embeds::with_placeholder(ctx, "Fetching the latest quake from GeoNet…", async {
+        // fetch the quake from the api
+        let quake = get_quake(ctx.data(), mmi).await?;
+        if compact.unwrap_or(false) {
+            let summary = quake.create_summary(depth_unit.unwrap_or_default());
+            return Ok(poise::CreateReply::default().content(summary));
+        }
+        let shakemap = ctx
+            .data()
+            .geonet
+            .shakemap(&quake.properties.public_id)
+            .await;
 
-    // fetch the quake from the api
-    let mmi = minimum_mmi.unwrap_or(3);
-    let quake = get_quake(mmi).await?;
+        // return the response
+        let embed = quake.create_embed(mmi, depth_unit.unwrap_or_default(), shakemap);
+        Ok(poise::CreateReply::default().embed(embed))
+    })
+    .await
+}
 
-    // return the response
-    let embed = quake.create_embed(mmi);
-    ctx.send(poise::CreateReply::default().embed(embed)).await?;
-    Ok(())
+/// Finds the recent quake >= specified intensity (MMI) closest to a location
+#[poise::command(slash_command)]
+pub(crate) async fn nearest(
+    ctx: Context<'_>,
+    #[description = "Your latitude in degrees, e.g. -41.29 for Wellington"] latitude: f64,
+    #[description = "Your longitude in degrees, e.g. 174.78 for Wellington"] longitude: f64,
+    #[description = "Minimum intensity: 0-8"]
+    #[min = 0]
+    #[max = 8]
+    minimum_mmi: Option<i8>,
+    #[description = "Or the minimum intensity in words"] intensity: Option<IntensityChoice>,
+    #[description = "Units for depth, defaults to km"] depth_unit: Option<DepthUnit>,
+) -> Result<(), Error> {
+    if !(-90.0..=90.0).contains(&latitude) || !(-180.0..=180.0).contains(&longitude) {
+        return Err(
+            "Latitude must be between -90 and 90, and longitude between -180 and 180".into(),
+        );
+    }
+
+    let mmi = check_minimum_mmi(minimum_mmi, intensity)?;
+    embeds::with_placeholder(ctx, "Fetching recent quakes from GeoNet…", async {
+        let quakes = ctx.data().geonet.quakes(mmi).await?;
+        let (quake, distance) = nearest_quake(&quakes, (latitude, longitude))
+            .ok_or("No quakes found with the required intensity")?;
+        let shakemap = ctx
+            .data()
+            .geonet
+            .shakemap(&quake.properties.public_id)
+            .await;
+
+        let embed = quake
+            .create_embed(mmi, depth_unit.unwrap_or_default(), shakemap)
+            .description(format!(
+                "Nearest recent quake with MMI >= {mmi}, {distance:.0} km away"
+            ));
+        Ok(poise::CreateReply::default().embed(embed))
+    })
+    .await
+}
+
+/// Lists recent quakes >= specified intensity (MMI)
+#[poise::command(slash_command)]
+pub(crate) async fn list(
+    ctx: Context<'_>,
+    #[description = "Minimum intensity: 0-8"]
+    #[min = 0]
+    #[max = 8]
+    minimum_mmi: Option<i8>,
+    #[description = "Or the minimum intensity in words"] intensity: Option<IntensityChoice>,
+    #[description = "Number of quakes to show: 1-100, at most 25 are listed"]
+    #[min = 1]
+    #[max = 100]
+    count: Option<usize>,
+    #[description = "Units for depth, defaults to km"] depth_unit: Option<DepthUnit>,
+    #[description = "Only include quakes from the last this many hours"]
+    #[min = 1]
+    #[max = 720]
+    since: Option<i64>,
+) -> Result<(), Error> {
+    let mmi = check_minimum_mmi(minimum_mmi, intensity)?;
+    // check the count before bothering geonet
+    listed_count(count, 0)?;
+    let mut quakes = ctx.data().geonet.quakes(mmi).await?;
+    let mut title = format!("Recent quakes with MMI >= {mmi}");
+    let mut summary = None;
+    if let Some(hours) = since {
+        let cutoff = ctx.created_at().unix_timestamp() - hours * 3600;
+        quakes = quakes_since(quakes, cutoff);
+        let Some(top) = strongest(&quakes) else {
+            ctx.say(format!(
+                "No quakes with MMI >= {mmi} in the last {hours} hours"
+            ))
+            .await?;
+            return Ok(());
+        };
+        title = format!("Quakes with MMI >= {mmi} in the last {hours} hours");
+        summary = Some(format!(
+            "{} quakes, the strongest was {} {} <t:{}:R>",
+            quakes.len(),
+            short_magnitude(top.properties.magnitude),
+            top.properties
+                .locality
+                .as_deref()
+                .unwrap_or("in an unknown location"),
+            unix_seconds(top.properties.time)
+        ));
+    }
+    if quakes.is_empty() {
+        return Err("No quakes found with the required intensity".into());
+    }
+    let (shown, capped) = listed_count(count, quakes.len())?;
+    quakes.truncate(shown);
+    if capped {
+        let note = format!("Only the {shown} most recent are shown");
+        summary = Some(match summary {
+            Some(summary) => format!("{summary}\n{note}"),
+            None => note,
+        });
+    }
+
+    // one field per quake, spread over as many embeds as discord needs
+    let depth_unit = depth_unit.unwrap_or_default();
+    let fields = quakes.iter().map(|q| q.create_field(depth_unit)).collect();
+    let header_length = title.chars().count() + summary.as_ref().map_or(0, |s| s.chars().count());
+    let mut template = embeds::branded_embed().title(title);
+    if let Some(summary) = summary {
+        template = template.description(summary);
+    }
+    embeds::send_paginated(ctx, &template, header_length, fields).await
+}
+
+/// Searches geonet's full quake catalogue by date, and optionally public ID prefix
+#[poise::command(slash_command)]
+pub(crate) async fn find(
+    ctx: Context<'_>,
+    #[description = "First day to search, in UTC, e.g. 2016-11-13"] from: String,
+    #[description = "Last day to search, in UTC, defaults to the first"] to: Option<String>,
+    #[description = "Only quakes whose public ID starts with this, e.g. 2016p"] prefix: Option<
+        String,
+    >,
+    #[description = "Units for depth, defaults to km"] depth_unit: Option<DepthUnit>,
+) -> Result<(), Error> {
+    let from = parse_date(&from)?;
+    let to = to.as_deref().map(parse_date).transpose()?.unwrap_or(from);
+    if to < from {
+        return Err("The last day can't be before the first".into());
+    }
+    let quakes = ctx
+        .data()
+        .geonet
+        .search_quakes(from, to, prefix.as_deref())
+        .await?;
+    if quakes.is_empty() {
+        ctx.say(format!("No quakes found from {from} to {to}"))
+            .await?;
+        return Ok(());
+    }
+
+    let title = if from == to {
+        format!("Quakes on {from}")
+    } else {
+        format!("Quakes from {from} to {to}")
+    };
+    let mut summary = format!("{} quakes, most recent first", quakes.len());
+    if quakes.len() == MAX_SEARCH_RESULTS {
+        summary = format!(
+            "Only the {MAX_SEARCH_RESULTS} most recent quakes found, narrow the search to see the rest"
+        );
+    }
+    let depth_unit = depth_unit.unwrap_or_default();
+    let fields = quakes.iter().map(|q| q.create_field(depth_unit)).collect();
+    let header_length = title.chars().count() + summary.chars().count();
+    let template = embeds::branded_embed().title(title).description(summary);
+    embeds::send_paginated(ctx, &template, header_length, fields).await
+}
+
+/// Describe a gap between two times in seconds using its two largest units, e.g. "3 days 4 hours"
+fn describe_gap(seconds: u64) -> String {
+    const UNITS: [(&str, u64); 4] = [
+        ("day", 86_400),
+        ("hour", 3_600),
+        ("minute", 60),
+        ("second", 1),
+    ];
+
+    let mut remaining = seconds;
+    let mut parts = Vec::new();
+    for (name, size) in UNITS {
+        let count = remaining / size;
+        remaining %= size;
+        if count > 0 {
+            let plural = if count == 1 { "" } else { "s" };
+            parts.push(format!("{count} {name}{plural}"));
+        }
+        if parts.len() == 2 {
+            break;
+        }
+    }
+    if parts.is_empty() {
+        "0 seconds".to_string()
+    } else {
+        parts.join(" ")
+    }
+}
+
+/// Put two quakes side by side, with a third column showing how the second differs from the first
+fn create_comparison_embed(
+    first: &Quake,
+    second: &Quake,
+    depth_unit: DepthUnit,
+) -> serenity::CreateEmbed {
+    let describe = |quake: &Quake| {
+        let p = &quake.properties;
+        format!(
+            "Magnitude {}\nMMI {}\nDepth {}\n<t:{}>\n{}",
+            or_unknown(p.magnitude, |m| format!("{m:.2}")),
+            or_unknown(p.mmi, |m| m.to_string()),
+            or_unknown(p.depth, |d| format_depth(d, depth_unit, 1)),
+            unix_seconds(p.time),
+            or_unknown(p.locality.as_deref(), str::to_string)
+        )
+    };
+    let (a, b) = (&first.properties, &second.properties);
+    let gap = unix_seconds(b.time) - unix_seconds(a.time);
+    // differences need the detail from both quakes
+    let depth_change = or_unknown(a.depth.zip(b.depth), |(a, b)| {
+        let sign = if b < a { "-" } else { "+" };
+        format!("{sign}{}", format_depth((b - a).abs(), depth_unit, 1))
+    });
+    let magnitude_change = or_unknown(a.magnitude.zip(b.magnitude), |(a, b)| {
+        format!("{:+.2}", b - a)
+    });
+    let mmi_change = or_unknown(a.mmi.zip(b.mmi), |(a, b)| {
+        format!("{:+}", i16::from(b) - i16::from(a))
+    });
+    let difference = format!(
+        "Magnitude {magnitude_change}\nMMI {mmi_change}\nDepth {depth_change}\n{} {}",
+        describe_gap(gap.unsigned_abs()),
+        if gap < 0 { "earlier" } else { "later" },
+    );
+
+    embeds::branded_embed()
+        .title(format!("Quake {} vs {}", a.public_id, b.public_id))
+        .field(a.public_id.clone(), describe(first), true)
+        .field(b.public_id.clone(), describe(second), true)
+        .field("Difference", difference, true)
+}
+
+/// Compares two quakes side by side
+#[poise::command(slash_command)]
+pub(crate) async fn compare(
+    ctx: Context<'_>,
+    #[description = "GeoNet quake ID, e.g. 2016p858000"] first: String,
+    #[description = "GeoNet quake ID to compare against the first"] second: String,
+    #[description = "Units for depth, defaults to km"] depth_unit: Option<DepthUnit>,
+) -> Result<(), Error> {
+    embeds::with_placeholder(ctx, "Fetching both quakes from GeoNet…", async {
+        // fetch both at once, and report every id that failed rather than just the first
+        let (first, second) = future::join(
+            ctx.data().geonet.quake(first.trim()),
+            ctx.data().geonet.quake(second.trim()),
+        )
+        .await;
+        let (first, second) = match (first, second) {
+            (Ok(first), Ok(second)) => (first, second),
+            (Err(e), Ok(_)) | (Ok(_), Err(e)) => return Err(e),
+            (Err(first), Err(second)) => return Err(format!("{first}\n{second}").into()),
+        };
+
+        let embed = create_comparison_embed(&first, &second, depth_unit.unwrap_or_default());
+        Ok(poise::CreateReply::default().embed(embed))
+    })
+    .await
+}
+
+/// This structure corresponds to the `properties` compound of a single
+/// station's reading in geonet's strong motion data for a quake.
+/// Peak ground accelerations are given in %g.
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct StationProperties {
+    pub(crate) network: String,
+    pub(crate) station: String,
+    /// Distance from the quake, in km
+    pub(crate) distance: f64,
+    /// Peak horizontal ground acceleration
+    pub(crate) pga_h: f64,
+    /// Peak vertical ground acceleration
+    pub(crate) pga_v: f64,
+}
+
+impl StationProperties {
+    /// The larger of the horizontal and vertical peak ground acceleration
+    pub(crate) fn peak_acceleration(&self) -> f64 {
+        self.pga_h.max(self.pga_v)
+    }
+}
+
+/// A strong motion station reading, as represented by geonet
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct Station {
+    // Like with quakes we only care about the `properties`
+    pub(crate) properties: StationProperties,
+}
+
+/// Build an embed summarising the strongest readings for a quake
+fn create_strong_motion_embed(public_id: &str, stations: &[Station]) -> serenity::CreateEmbed {
+    let embed = embeds::branded_embed()
+        .url(format!("https://www.geonet.org.nz/earthquake/{public_id}"))
+        .title(format!("Strong motion for quake ID {public_id}"))
+        .description("Stations with the highest peak ground acceleration (PGA)");
+
+    stations.iter().fold(embed, |embed, station| {
+        let properties = &station.properties;
+        embed.field(
+            format!("{}.{}", properties.network, properties.station),
+            format!(
+                "PGA {:.3} %g (h) / {:.3} %g (v)\n{:.1} km away",
+                properties.pga_h, properties.pga_v, properties.distance
+            ),
+            true,
+        )
+    })
+}
+
+/// Displays the peak ground acceleration recorded by strong motion stations for a quake
+#[poise::command(slash_command)]
+pub(crate) async fn strongmotion(
+    ctx: Context<'_>,
+    #[description = "GeoNet quake ID, e.g. 2016p858000"] quake_id: String,
+    #[description = "Number of stations to show: 1-25"]
+    #[min = 1]
+    #[max = 25]
+    stations: Option<usize>,
+) -> Result<(), Error> {
+    let quake_id = quake_id.trim();
+    embeds::with_placeholder(ctx, "Fetching strong motion readings from GeoNet…", async {
+        let mut readings = ctx.data().geonet.strong_motion(quake_id).await?;
+        if readings.is_empty() {
+            return Err(format!("No strong motion data found for quake {quake_id}").into());
+        }
+
+        // embeds can't hold more than 25 fields, so respect that even if discord doesn't enforce the bounds
+        readings.truncate(stations.unwrap_or(10).min(25));
+        let embed = create_strong_motion_embed(quake_id, &readings);
+        Ok(poise::CreateReply::default().embed(embed))
+    })
+    .await
+}
+
+/// Posts new quakes to a channel, and DMs them to anyone subscribed, as geonet reports them
+#[derive(Clone)]
+pub(crate) struct QuakeAlerts {
+    /// Where alerts are posted, if anywhere
+    pub(crate) channel: Option<ChannelId>,
+    /// Only quakes at or above this intensity are posted to the channel
+    pub(crate) mmi: i8,
+    /// Who wants a DM about which quakes, if we have a database
+    pub(crate) subscriptions: Option<Arc<QuakeSubscriptionsDatabase>>,
+    /// Shortest wait between polls, used while there is a lot going on
+    pub(crate) min_interval: Duration,
+    /// Longest wait between polls, used while it is quiet
+    pub(crate) max_interval: Duration,
+}
+
+/// How long to wait before polling geonet again, given the quakes it last reported.
+/// Each quake counts for ten times more per step of magnitude above 4, and less the
+/// longer ago it was, roughly following how aftershocks die away. Quakes without a
+/// magnitude don't count. A busy period brings the interval down towards `min`,
+/// while a quiet one lets it drift back up to `max`.
+fn poll_interval(quakes: &[Quake], now: Timestamp, min: Duration, max: Duration) -> Duration {
+    let activity = quakes
+        .iter()
+        .filter_map(|q| {
+            let age_hours = now.duration_since(q.properties.time).as_seconds_f64() / 3600.0;
+            Some(10f64.powf(q.properties.magnitude? - 4.0) / (1.0 + age_hours.max(0.0)))
+        })
+        .fold(0.0, f64::max);
+    Duration::from_secs_f64(max.as_secs_f64() / (1.0 + activity)).clamp(min, max)
+}
+
+impl QuakeAlerts {
+    /// Start polling in the background, restarting the poller if it panics
+    pub(crate) fn spawn(
+        self,
+        bot: Arc<serenity::Http>,
+        geonet: GeoNetClient,
+        log_channel: LogChannel,
+    ) {
+        let http = bot.clone();
+        supervise("quake alerts", bot, log_channel, move || {
+            self.clone().run(http.clone(), geonet.clone())
+        });
+    }
+
+    /// Poll geonet forever, posting each new quake to the alert channel and its subscribers.
+    /// Quakes from before the first poll aren't posted, so a restart doesn't repeat old alerts.
+    /// Shares the geonet client with the commands so together they back off when it's down.
+    async fn run(self, bot: Arc<serenity::Http>, geonet: GeoNetClient) {
+        // subscriptions can ask for weaker quakes than the channel gets
+        let mmi = if self.subscriptions.is_some() {
+            self.mmi.min(MIN_SUBSCRIPTION_MMI)
+        } else {
+            self.mmi
+        };
+        let mut last_seen: Option<Timestamp> = None;
+        loop {
+            if !self.anyone_listening().await {
+                // start afresh once someone subscribes, rather than sending them quakes from before
+                last_seen = None;
+                tokio::time::sleep(self.max_interval).await;
+                continue;
+            }
+            let wait = match geonet.quakes(mmi).await {
+                Ok(quakes) => {
+                    if let Some(seen) = last_seen {
+                        // oldest first, so they land in the channel in order
+                        let new = quakes.iter().take_while(|q| q.properties.time > seen);
+                        for quake in new.collect::<Vec<_>>().into_iter().rev() {
+                            self.post(&bot, quake).await;
+                        }
+                    }
+                    last_seen = quakes.first().map(|q| q.properties.time).or(last_seen);
+                    poll_interval(
+                        &quakes,
+                        Timestamp::now_utc(),
+                        self.min_interval,
+                        self.max_interval,
+                    )
+                }
+                Err(e) => {
+                    println!("Unable to poll geonet for quake alerts: {e:?}");
+                    self.max_interval
+                }
+            };
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Whether there's anywhere to send alerts. Without an alert channel
+    /// there's no point polling geonet until someone subscribes.
+    async fn anyone_listening(&self) -> bool {
+        if self.channel.is_some() {
+            return true;
+        }
+        let Some(subscriptions) = &self.subscriptions else {
+            return false;
+        };
+        subscriptions.any().await.unwrap_or_else(|e| {
+            // polling anyway is harmless, while missing a quake someone asked about isn't
+            println!("Unable to check for quake subscriptions: {e:?}");
+            true
+        })
+    }
+
+    /// Post a single quake to the alert channel, if it's strong enough, and to its subscribers
+    async fn post(&self, bot: &serenity::Http, quake: &Quake) {
+        let mmi = quake.properties.mmi.unwrap_or(i8::MIN);
+        if let Some(channel) = self.channel.filter(|_| mmi >= self.mmi) {
+            let embed = quake
+                .create_embed(self.mmi, DepthUnit::default(), None)
+                .description(format!("New quake with MMI >= {}", self.mmi));
+            if let Err(e) = channel
+                .send_message(bot, serenity::CreateMessage::default().embed(embed))
+                .await
+            {
+                println!(
+                    "Unable to post alert for quake {}: {e:?}",
+                    quake.properties.public_id
+                );
+            }
+        }
+        if let Some(subscriptions) = &self.subscriptions {
+            self.notify_subscribers(bot, subscriptions, quake, mmi)
+                .await;
+        }
+    }
+
+    /// DM a quake to everyone with a subscription it matches, once each
+    async fn notify_subscribers(
+        &self,
+        bot: &serenity::Http,
+        subscriptions: &QuakeSubscriptionsDatabase,
+        quake: &Quake,
+        mmi: i8,
+    ) {
+        let matching = match subscriptions.down_to(mmi).await {
+            Ok(matching) => matching,
+            Err(e) => {
+                println!("Unable to get quake subscriptions: {e:?}");
+                return;
+            }
+        };
+        let mut users: Vec<_> = matching
+            .into_iter()
+            .filter(|(_, subscription)| subscription.matches(quake))
+            .map(|(user, _)| user)
+            .collect();
+        users.sort_unstable();
+        users.dedup();
+        let summary = format!(
+            "{}\nRemove this alert with `/quakes subscriptions`",
+            quake.create_summary(DepthUnit::default())
+        );
+        for user in users {
+            let sent = match user.create_dm_channel(bot).await {
+                Ok(dm) => {
+                    dm.send_message(bot, serenity::CreateMessage::default().content(&summary))
+                        .await
+                }
+                Err(e) => Err(e),
+            };
+            if let Err(e) = sent {
+                println!(
+                    "Unable to DM quake {} to {user}: {e:?}",
+                    quake.properties.public_id
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        check_minimum_mmi, create_comparison_embed, describe_gap, format_depth, haversine_km,
+        listed_count, map_url, nearest_quake, parse_date, poll_interval, quakes_since, strongest,
+        unix_seconds, DepthUnit, IntensityChoice, Quake, QuakeGeometry, SearchedQuake,
+    };
+    use iso8601_timestamp::Timestamp;
+    use std::time::Duration;
+
+    #[test]
+    fn minimum_mmi_is_bounded() {
+        assert_eq!(check_minimum_mmi(None, None).unwrap(), 3);
+        assert_eq!(check_minimum_mmi(Some(0), None).unwrap(), 0);
+        assert_eq!(check_minimum_mmi(Some(8), None).unwrap(), 8);
+        assert_eq!(
+            check_minimum_mmi(Some(-1), None).unwrap_err().to_string(),
+            "Minimum intensity must be from 0 to 8, not -1"
+        );
+        assert!(check_minimum_mmi(Some(9), None).is_err());
+    }
+
+    #[test]
+    fn intensity_words_map_to_mmi() {
+        let word = |intensity| check_minimum_mmi(None, Some(intensity)).unwrap();
+        assert_eq!(word(IntensityChoice::Weak), 3);
+        assert_eq!(word(IntensityChoice::Moderate), 5);
+        assert_eq!(word(IntensityChoice::Strong), 6);
+        assert_eq!(word(IntensityChoice::Severe), 7);
+        assert!(check_minimum_mmi(Some(4), Some(IntensityChoice::Weak)).is_err());
+    }
+
+    #[test]
+    fn lists_are_capped() {
+        assert_eq!(listed_count(None, 100).unwrap(), (10, false));
+        assert_eq!(listed_count(Some(25), 100).unwrap(), (25, false));
+        assert_eq!(listed_count(Some(26), 100).unwrap(), (25, true));
+        assert_eq!(listed_count(Some(100), 100).unwrap(), (25, true));
+        // fewer quakes than the cap aren't truncated
+        assert_eq!(listed_count(Some(100), 12).unwrap(), (12, false));
+        assert_eq!(listed_count(Some(30), 25).unwrap(), (25, false));
+        assert_eq!(listed_count(Some(30), 26).unwrap(), (25, true));
+        assert_eq!(
+            listed_count(Some(0), 100).unwrap_err().to_string(),
+            "Number of quakes must be from 1 to 100, not 0"
+        );
+        assert!(listed_count(Some(101), 100).is_err());
+    }
+
+    /// Parse a timestamp and convert it to unix seconds
+    fn seconds(time: &str) -> i64 {
+        unix_seconds(Timestamp::parse(time).unwrap())
+    }
+
+    #[test]
+    fn depth_in_kilometres() {
+        assert_eq!(format_depth(12.3456, DepthUnit::Kilometres, 3), "12.346 km");
+        assert_eq!(format_depth(5.0, DepthUnit::Kilometres, 1), "5.0 km");
+    }
+
+    #[test]
+    fn depth_in_miles() {
+        assert_eq!(format_depth(10.0, DepthUnit::Miles, 3), "6.214 mi");
+        assert_eq!(format_depth(100.0, DepthUnit::Miles, 1), "62.1 mi");
+        assert_eq!(format_depth(0.0, DepthUnit::Miles, 1), "0.0 mi");
+    }
+
+    #[test]
+    fn whole_second_timestamps() {
+        assert_eq!(seconds("1970-01-01T00:00:00Z"), 0);
+        assert_eq!(seconds("2024-01-01T00:00:00.000Z"), 1_704_067_200);
+    }
+
+    #[test]
+    fn geonet_style_timestamps() {
+        // the 2016 Kaikōura earthquake, as geonet reports it
+        assert_eq!(seconds("2016-11-13T11:02:56.346Z"), 1_479_034_976);
+        assert_eq!(seconds("2016-11-13T11:02:56.999Z"), 1_479_034_976);
+    }
+
+    #[test]
+    fn offsets_are_applied() {
+        assert_eq!(seconds("2024-01-01T13:00:00+13:00"), 1_704_067_200);
+    }
+
+    #[test]
+    fn pre_epoch_rounds_down() {
+        assert_eq!(seconds("1969-12-31T23:59:59.500Z"), -1);
+        assert_eq!(seconds("1969-12-31T23:59:59Z"), -1);
+        assert_eq!(seconds("1960-01-01T00:00:00.250Z"), -315_619_200);
+    }
+
+    #[test]
+    fn gaps_use_two_largest_units() {
+        assert_eq!(describe_gap(0), "0 seconds");
+        assert_eq!(describe_gap(61), "1 minute 1 second");
+        assert_eq!(describe_gap(3 * 86_400 + 4 * 3_600 + 59), "3 days 4 hours");
+        assert_eq!(describe_gap(86_400 + 30), "1 day 30 seconds");
+    }
+
+    /// A quake with just enough detail for filtering
+    fn quake(id: &str, time: &str, magnitude: f64) -> Quake {
+        serde_json::from_value(serde_json::json!({
+            "properties": {
+                "publicID": id,
+                "time": time,
+                "depth": 10.0,
+                "locality": "Somewhere",
+                "magnitude": magnitude,
+                "mmi": 3,
+                "quality": "best",
+            }
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn summaries_fit_on_one_line() {
+        let mut quake = quake("2024p000001", "2024-01-01T00:00:00Z", 4.25);
+        assert_eq!(
+            quake.create_summary(DepthUnit::Kilometres),
+            "M4.2 quake Somewhere, 10.0 km deep, MMI 3, <t:1704067200:R>"
+        );
+        quake.properties.locality = None;
+        quake.properties.depth = None;
+        quake.properties.mmi = None;
+        assert_eq!(
+            quake.create_summary(DepthUnit::Miles),
+            "M4.2 quake at an unknown location, unknown depth, MMI unknown, <t:1704067200:R>"
+        );
+    }
+
+    #[test]
+    fn window_keeps_recent_quakes() {
+        let quakes = vec![
+            quake("c", "2024-01-02T00:00:00Z", 3.1),
+            quake("b", "2024-01-01T12:00:00Z", 4.5),
+            quake("a", "2023-12-31T00:00:00Z", 6.0),
+        ];
+        let cutoff = seconds("2024-01-01T12:00:00Z");
+        let recent = quakes_since(quakes, cutoff);
+        let ids: Vec<_> = recent
+            .iter()
+            .map(|q| q.properties.public_id.as_str())
+            .collect();
+        assert_eq!(ids, ["c", "b"]);
+        assert_eq!(strongest(&recent).unwrap().properties.public_id, "b");
+    }
+
+    #[test]
+    fn empty_window_has_no_strongest() {
+        let quakes = vec![quake("a", "2023-12-31T00:00:00Z", 6.0)];
+        let recent = quakes_since(quakes, seconds("2024-01-01T00:00:00Z"));
+        assert!(recent.is_empty());
+        assert!(strongest(&recent).is_none());
+    }
+
+    #[test]
+    fn map_url_points_at_the_quake() {
+        assert_eq!(
+            map_url(-41.29, 174.78),
+            "https://www.openstreetmap.org/?mlat=-41.2900&mlon=174.7800#map=9/-41.2900/174.7800"
+        );
+    }
+
+    #[test]
+    fn haversine_distances() {
+        let wellington = (-41.29, 174.78);
+        let auckland = (-36.85, 174.76);
+        assert!(haversine_km(wellington, wellington).abs() < 1e-9);
+        // about 494 km by great circle
+        assert!((haversine_km(wellington, auckland) - 494.0).abs() < 2.0);
+        assert!(
+            (haversine_km(wellington, auckland) - haversine_km(auckland, wellington)).abs() < 1e-9
+        );
+        // a quarter of the way around the equator
+        let quarter = haversine_km((0.0, 0.0), (0.0, 90.0));
+        assert!((quarter - std::f64::consts::FRAC_PI_2 * 6371.0).abs() < 1e-6);
+        // across the antimeridian is the short way
+        assert!(haversine_km((0.0, 179.5), (0.0, -179.5)) < 112.0);
+    }
+
+    #[test]
+    fn nearest_skips_quakes_without_coordinates() {
+        let mut far = quake("far", "2024-01-01T00:00:00Z", 5.0);
+        far.geometry = Some(QuakeGeometry {
+            coordinates: [176.0, -38.0],
+        });
+        let mut near = quake("near", "2024-01-01T00:00:00Z", 3.0);
+        near.geometry = Some(QuakeGeometry {
+            coordinates: [174.8, -41.3],
+        });
+        let nowhere = quake("nowhere", "2024-01-01T00:00:00Z", 4.0);
+
+        let quakes = [far, nowhere, near];
+        let (closest, distance) = nearest_quake(&quakes, (-41.29, 174.78)).unwrap();
+        assert_eq!(closest.properties.public_id, "near");
+        assert!(distance < 5.0);
+        assert!(nearest_quake(&quakes[1..2], (-41.29, 174.78)).is_none());
+    }
+
+    #[test]
+    fn polling_speeds_up_after_a_big_quake() {
+        let min = Duration::from_mins(1);
+        let max = Duration::from_mins(10);
+        let now = Timestamp::parse("2024-01-02T00:00:00Z").unwrap();
+
+        // nothing happening, so poll as slowly as allowed
+        assert_eq!(poll_interval(&[], now, min, max), max);
+        let quiet = [quake("a", "2024-01-01T00:00:00Z", 2.5)];
+        assert!(poll_interval(&quiet, now, min, max) > Duration::from_secs(590));
+
+        // a big quake an hour ago means aftershocks are likely
+        let big = [quake("b", "2024-01-01T23:00:00Z", 6.2)];
+        assert_eq!(poll_interval(&big, now, min, max), min);
+
+        // a moderate one sits in between, and matters less as it gets older
+        let fresh = [quake("c", "2024-01-01T23:00:00Z", 4.5)];
+        let stale = [quake("c", "2024-01-01T06:00:00Z", 4.5)];
+        let fresh_wait = poll_interval(&fresh, now, min, max);
+        assert!(min < fresh_wait && fresh_wait < max);
+        assert!(fresh_wait < poll_interval(&stale, now, min, max));
+    }
+
+    #[test]
+    fn catalogue_search_results_parse() {
+        let quake: SearchedQuake = serde_json::from_value(serde_json::json!({
+            "type": "Feature",
+            "geometry": {"type": "Point", "coordinates": [173.0, -42.7]},
+            "properties": {
+                "publicid": "2016p858000",
+                "eventtype": "earthquake",
+                "origintime": "2016-11-13T11:02:56.346Z",
+                "depth": 15.1,
+                "magnitude": 7.8,
+                "magnitudetype": "Mw"
+            }
+        }))
+        .unwrap();
+        assert_eq!(quake.properties.public_id, "2016p858000");
+        assert_eq!(unix_seconds(quake.properties.time), 1_479_034_976);
+        assert_eq!(quake.properties.magnitude, Some(7.8));
+
+        let (name, value, _) = quake.create_field(DepthUnit::Kilometres);
+        assert_eq!(name, "M7.8 2016p858000");
+        assert!(value.contains("15.1 km deep"));
+    }
+
+    #[test]
+    fn dates_parse_as_iso() {
+        assert_eq!(parse_date(" 2016-11-13").unwrap().to_string(), "2016-11-13");
+        assert!(parse_date("13/11/2016").is_err());
+        assert!(parse_date("2016-02-30").is_err());
+    }
+
+    #[test]
+    fn missing_details_show_as_unknown() {
+        let sparse: Quake = serde_json::from_value(serde_json::json!({
+            "properties": {
+                "publicID": "2024p000001",
+                "time": "2024-01-01T00:00:00Z",
+                "magnitude": null,
+                "mmi": 2,
+                "renamedLocality": "Somewhere"
+            }
+        }))
+        .unwrap();
+        assert_eq!(sparse.properties.mmi, Some(2));
+
+        let (name, value, _) = sparse.create_field(DepthUnit::Kilometres);
+        assert_eq!(name, "M? unknown location");
+        assert!(value.starts_with("MMI 2, unknown depth"));
+
+        // a quake without a magnitude doesn't beat one with
+        let full = quake("2024p000002", "2024-01-01T00:00:00Z", 1.5);
+        let quakes = [sparse, full];
+        assert_eq!(
+            strongest(&quakes).unwrap().properties.public_id,
+            "2024p000002"
+        );
+        let embed = create_comparison_embed(&quakes[0], &quakes[1], DepthUnit::Kilometres);
+        let json = serde_json::to_value(embed).unwrap();
+        assert!(json["fields"][2]["value"]
+            .as_str()
+            .unwrap()
+            .starts_with("Magnitude unknown\nMMI +1\nDepth unknown"));
+    }
 }
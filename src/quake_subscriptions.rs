@@ -0,0 +1,378 @@
+use super::database::{from_db_id, to_db_id, StatementCache};
+use super::errors::Error;
+use super::quake::Quake;
+use super::Context;
+use crate::serenity;
+use poise::serenity_prelude::UserId;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_postgres::{types::Type, Client, Statement};
+
+/// Most subscriptions one person can have, which also keeps them all in one select menu
+const MAX_SUBSCRIPTIONS: i64 = 10;
+/// Lowest intensity anyone can subscribe to, below this there'd be a DM every few hours
+pub(crate) const MIN_SUBSCRIPTION_MMI: i8 = 3;
+/// Longest place name a subscription can be narrowed to
+const MAX_PLACE_LENGTH: usize = 50;
+/// How long the removal menu waits for a choice
+const CHOOSE_WINDOW: Duration = Duration::from_mins(1);
+
+/// Someone's request to be sent a DM about new quakes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Subscription {
+    pub(crate) id: i64,
+    /// Only quakes at or above this intensity count
+    pub(crate) mmi: i8,
+    /// Only quakes whose locality mentions this count, if set
+    pub(crate) near: Option<String>,
+}
+
+impl Subscription {
+    /// Whether a quake is one this subscription asked about
+    pub(crate) fn matches(&self, quake: &Quake) -> bool {
+        let properties = &quake.properties;
+        properties.mmi.is_some_and(|mmi| mmi >= self.mmi)
+            && self.near.as_ref().is_none_or(|near| {
+                properties
+                    .locality
+                    .as_ref()
+                    .is_some_and(|l| l.to_lowercase().contains(&near.to_lowercase()))
+            })
+    }
+
+    /// Describe a subscription for lists and menus, e.g. `MMI 4+ near Seddon`
+    fn describe(&self) -> String {
+        match &self.near {
+            Some(near) => format!("MMI {}+ near {near}", self.mmi),
+            None => format!("MMI {}+ anywhere", self.mmi),
+        }
+    }
+}
+
+/// Check a place name is worth matching against, returning it tidied up
+fn validate_place(place: &str) -> Result<String, Error> {
+    let place = place.trim();
+    if place.is_empty() || place.chars().count() > MAX_PLACE_LENGTH {
+        return Err(format!("Place names must be 1 to {MAX_PLACE_LENGTH} characters").into());
+    }
+    Ok(place.to_string())
+}
+
+/// Helper struct for storing who wants to hear about new quakes.
+/// Use the methods on this struct rather than directly acessing the fields.
+pub(crate) struct QuakeSubscriptionsDatabase {
+    /// The database client used to interact with postgres
+    client: Arc<Client>,
+    /// A prepared database statement that lists a user's subscriptions
+    list: Statement,
+    /// A prepared database statement that fetches every subscription down to an intensity
+    down_to: Statement,
+    /// A prepared database statement that checks whether anyone has a subscription
+    any: Statement,
+    /// A prepared database statement that adds a subscription, unless the user has too many already
+    add: Statement,
+    /// A prepared database statement that deletes one of a user's subscriptions
+    remove: Statement,
+}
+
+impl QuakeSubscriptionsDatabase {
+    /// Set up the subscriptions table and statements on the connection the cache is for
+    pub(crate) async fn new(statements: &StatementCache) -> Result<Self, Error> {
+        let client = statements.client();
+        // Init table if required
+        client
+            .execute(
+                "CREATE TABLE IF NOT EXISTS quake_subscriptions (
+                            id BIGSERIAL PRIMARY KEY,
+                            user_id BIGINT NOT NULL,
+                            mmi SMALLINT NOT NULL,
+                            near TEXT
+                        )",
+                &[],
+            )
+            .await?;
+
+        let list = statements
+            .prepare_typed(
+                "SELECT id, mmi, near FROM quake_subscriptions WHERE user_id = $1 ORDER BY id",
+                &[Type::INT8],
+            )
+            .await?;
+        let down_to = statements
+            .prepare_typed(
+                "SELECT user_id, id, mmi, near FROM quake_subscriptions WHERE mmi <= $1",
+                &[Type::INT2],
+            )
+            .await?;
+        let any = statements
+            .prepare("SELECT EXISTS (SELECT 1 FROM quake_subscriptions)")
+            .await?;
+        let add = statements
+            .prepare_typed(
+                "INSERT INTO quake_subscriptions (user_id, mmi, near)
+                    SELECT $1, $2, $3
+                    WHERE (SELECT COUNT(*) FROM quake_subscriptions WHERE user_id = $1) < $4
+                    RETURNING id",
+                &[Type::INT8, Type::INT2, Type::TEXT, Type::INT8],
+            )
+            .await?;
+        let remove = statements
+            .prepare_typed(
+                "DELETE FROM quake_subscriptions WHERE user_id = $1 AND id = $2",
+                &[Type::INT8, Type::INT8],
+            )
+            .await?;
+
+        Ok(QuakeSubscriptionsDatabase {
+            client,
+            list,
+            down_to,
+            any,
+            add,
+            remove,
+        })
+    }
+
+    /// List a user's subscriptions, oldest first
+    async fn list(&self, user_id: UserId) -> Result<Vec<Subscription>, Error> {
+        let rows = self.client.query(&self.list, &[&to_db_id(user_id)]).await?;
+        Ok(rows
+            .iter()
+            .map(|row| Subscription {
+                id: row.get(0),
+                mmi: row.get::<_, i16>(1).try_into().unwrap_or(i8::MAX),
+                near: row.get(2),
+            })
+            .collect())
+    }
+
+    /// Get everyone's subscriptions that a quake of this intensity could match
+    pub(crate) async fn down_to(&self, mmi: i8) -> Result<Vec<(UserId, Subscription)>, Error> {
+        let rows = self.client.query(&self.down_to, &[&i16::from(mmi)]).await?;
+        Ok(rows
+            .iter()
+            .map(|row| {
+                (
+                    UserId::new(from_db_id(row.get(0))),
+                    Subscription {
+                        id: row.get(1),
+                        mmi: row.get::<_, i16>(2).try_into().unwrap_or(i8::MAX),
+                        near: row.get(3),
+                    },
+                )
+            })
+            .collect())
+    }
+
+    /// Whether anyone has a subscription at all
+    pub(crate) async fn any(&self) -> Result<bool, Error> {
+        Ok(self.client.query_one(&self.any, &[]).await?.get(0))
+    }
+
+    /// Add a subscription, returning its ID.
+    /// Fails if the user already has [`MAX_SUBSCRIPTIONS`].
+    async fn add(&self, user_id: UserId, mmi: i8, near: Option<&str>) -> Result<i64, Error> {
+        let row = self
+            .client
+            .query_opt(
+                &self.add,
+                &[&to_db_id(user_id), &i16::from(mmi), &near, &MAX_SUBSCRIPTIONS],
+            )
+            .await?
+            .ok_or(format!(
+                "You can have at most {MAX_SUBSCRIPTIONS} quake subscriptions, remove one to make room"
+            ))?;
+        Ok(row.get(0))
+    }
+
+    /// Remove one of a user's subscriptions, returning whether they had it
+    async fn remove(&self, user_id: UserId, id: i64) -> Result<bool, Error> {
+        let removed = self
+            .client
+            .execute(&self.remove, &[&to_db_id(user_id), &id])
+            .await?;
+        Ok(removed > 0)
+    }
+}
+
+/// Get the subscriptions database, or explain why there isn't one
+fn subscriptions_database(ctx: Context<'_>) -> Result<&QuakeSubscriptionsDatabase, Error> {
+    Ok(ctx
+        .data()
+        .quake_subscriptions
+        .as_deref()
+        .ok_or("Quake subscriptions are disabled because the bot has no database")?)
+}
+
+/// Get a DM whenever a new quake at or above an intensity happens
+#[poise::command(slash_command, ephemeral)]
+pub(crate) async fn subscribe(
+    ctx: Context<'_>,
+    #[description = "Minimum intensity: 3-8"]
+    #[min = 3]
+    #[max = 8]
+    minimum_mmi: i8,
+    #[description = "Only quakes near this place, e.g. Seddon"]
+    #[max_length = 50]
+    near: Option<String>,
+) -> Result<(), Error> {
+    let database = subscriptions_database(ctx)?;
+    if !(MIN_SUBSCRIPTION_MMI..=8).contains(&minimum_mmi) {
+        return Err(format!(
+            "Minimum intensity must be from {MIN_SUBSCRIPTION_MMI} to 8, not {minimum_mmi}"
+        )
+        .into());
+    }
+    let near = near.as_deref().map(validate_place).transpose()?;
+    database
+        .add(ctx.author().id, minimum_mmi, near.as_deref())
+        .await?;
+    let subscription = Subscription {
+        id: 0,
+        mmi: minimum_mmi,
+        near,
+    };
+    ctx.say(format!(
+        "Subscribed to quakes {}, I'll DM you when one happens. \
+        See or remove your subscriptions with `/quakes subscriptions`.",
+        subscription.describe()
+    ))
+    .await?;
+    Ok(())
+}
+
+/// List your quake subscriptions, and pick one to remove
+#[poise::command(slash_command, ephemeral)]
+pub(crate) async fn subscriptions(ctx: Context<'_>) -> Result<(), Error> {
+    let database = subscriptions_database(ctx)?;
+    let author = ctx.author().id;
+    let subscriptions = database.list(author).await?;
+    if subscriptions.is_empty() {
+        ctx.say("You don't have any quake subscriptions, add one with `/quakes subscribe`")
+            .await?;
+        return Ok(());
+    }
+
+    let lines: Vec<String> = subscriptions
+        .iter()
+        .map(|s| format!("- {}", s.describe()))
+        .collect();
+    let content = format!("Your quake subscriptions\n{}", lines.join("\n"));
+    let menu_id = format!("{}unsubscribe", ctx.id());
+    let options = subscriptions
+        .iter()
+        .map(|s| serenity::CreateSelectMenuOption::new(s.describe(), s.id.to_string()))
+        .collect();
+    let menu = serenity::CreateSelectMenu::new(
+        &menu_id,
+        serenity::CreateSelectMenuKind::String { options },
+    )
+    .placeholder("Remove subscription…");
+    let handle = ctx
+        .send(
+            poise::CreateReply::default()
+                .content(&content)
+                .components(vec![serenity::CreateActionRow::SelectMenu(menu)]),
+        )
+        .await?;
+    // waiting on the menu doesn't count as the command hanging
+    ctx.data().watchdog.finish(ctx);
+
+    let filter_id = menu_id.clone();
+    let press = serenity::ComponentInteractionCollector::new(ctx)
+        .author_id(author)
+        .channel_id(ctx.channel_id())
+        .timeout(CHOOSE_WINDOW)
+        .filter(move |press| press.data.custom_id == filter_id)
+        .await;
+    let Some(press) = press else {
+        // leave the list up, just without the menu
+        handle
+            .edit(
+                ctx,
+                poise::CreateReply::default()
+                    .content(content)
+                    .components(Vec::new()),
+            )
+            .await?;
+        return Ok(());
+    };
+    let id = match &press.data.kind {
+        serenity::ComponentInteractionDataKind::StringSelect { values } => {
+            values.first().and_then(|v| v.parse::<i64>().ok())
+        }
+        _ => None,
+    };
+    let subscription = subscriptions
+        .into_iter()
+        .find(|s| Some(s.id) == id)
+        .ok_or("That isn't one of the options")?;
+    // it may have been removed from another menu in the meantime
+    let reply = if database.remove(author, subscription.id).await? {
+        format!("Unsubscribed from quakes {}", subscription.describe())
+    } else {
+        "That subscription was already removed".to_string()
+    };
+    press
+        .create_response(
+            ctx,
+            serenity::CreateInteractionResponse::UpdateMessage(
+                serenity::CreateInteractionResponseMessage::new()
+                    .content(reply)
+                    .components(Vec::new()),
+            ),
+        )
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{validate_place, Subscription};
+    use crate::quake::Quake;
+
+    fn quake(mmi: Option<i8>, locality: Option<&str>) -> Quake {
+        serde_json::from_value(serde_json::json!({
+            "properties": {
+                "publicID": "2024p000001",
+                "time": "2024-01-01T00:00:00.000Z",
+                "depth": 10.0,
+                "magnitude": 4.5,
+                "mmi": mmi,
+                "locality": locality,
+                "quality": "preliminary"
+            }
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn subscriptions_match_intensity_and_place() {
+        let anywhere = Subscription {
+            id: 1,
+            mmi: 4,
+            near: None,
+        };
+        let seddon = Subscription {
+            id: 2,
+            mmi: 4,
+            near: Some("seddon".to_string()),
+        };
+        let nearby = quake(Some(5), Some("10 km east of Seddon"));
+        assert!(anywhere.matches(&nearby));
+        assert!(seddon.matches(&nearby));
+        let elsewhere = quake(Some(4), Some("5 km north of Taupō"));
+        assert!(anywhere.matches(&elsewhere));
+        assert!(!seddon.matches(&elsewhere));
+        assert!(!anywhere.matches(&quake(Some(3), Some("Seddon"))));
+        assert!(!anywhere.matches(&quake(None, None)));
+        assert!(!seddon.matches(&quake(Some(6), None)));
+    }
+
+    #[test]
+    fn places_are_tidied() {
+        assert_eq!(validate_place("  Seddon ").unwrap(), "Seddon");
+        assert!(validate_place(" ").is_err());
+        assert!(validate_place(&"a".repeat(51)).is_err());
+    }
+}
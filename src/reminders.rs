@@ -1,69 +1,266 @@
+use super::database::{self, from_db_id, to_db_id, StatementCache};
+use super::discord_auth;
+use super::embeds;
 use super::errors::Error;
+use super::guild_strings::{guild_string, GuildString};
+use super::import::{self, ImportedReminder};
+use super::log_channel::LogChannel;
+use super::webhook::{self, Webhook};
 use super::Context;
 use crate::serenity;
-use chrono::{DateTime, Duration, TimeDelta, Utc};
-use poise::serenity_prelude::UserId;
-use poise::serenity_prelude::{futures::future, CreateEmbed, CreateMessage};
+use chrono::{
+    DateTime, Datelike, Duration, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, Offset,
+    TimeDelta, TimeZone, Utc, Weekday,
+};
+use poise::serenity_prelude::{futures::future, Colour, CreateMessage, MessageFlags};
+use poise::serenity_prelude::{ChannelId, MessageId, UserId};
+use poise::ChoiceParameter;
+use std::collections::{BTreeMap, HashSet};
 use std::sync::Arc;
-use tokio_postgres::{connect, types::Type, Client, NoTls, Row, Statement};
+use tokio_postgres::{types::Type, Client, Row, Statement};
 
+#[derive(Clone)]
 struct Reminder {
     pub(crate) id: i64,
     pub(crate) user_id: UserId,
     pub(crate) due_at: DateTime<Utc>,
     pub(crate) message: String,
+    pub(crate) priority: PriorityChoice,
+    /// Channel or thread the reminder was made in, if it was made in a server.
+    /// Used to deliver the reminder if the user's DMs are closed.
+    pub(crate) channel_id: Option<ChannelId>,
+    /// Label the user chose to group reminders by, like "work"
+    pub(crate) category: Option<String>,
+    /// Number of times the reminder has already been sent without being acknowledged.
+    /// Only critical reminders are sent more than once.
+    pub(crate) deliveries: i16,
+    /// Link to the Discord message the reminder is about, if it was made from one
+    pub(crate) source_link: Option<String>,
+    /// Channel and message to deliver the reminder as a reply to, if the user asked for that.
+    /// If the message is gone the reminder goes to their DMs instead.
+    pub(crate) reply_to: Option<(ChannelId, MessageId)>,
+    /// Set while the user has paused the reminder. It is kept, but not sent until resumed.
+    pub(crate) paused: bool,
+    /// Bumped every time the reminder moves to a new time, so a task waiting on it can
+    /// tell it was moved even if it was later moved back
+    pub(crate) version: i32,
 }
 
 impl Reminder {
     /// Convert a database row into a [`Reminder`]
     fn from_row(x: &Row) -> Self {
         let id: i64 = x.get(0);
-        let user_id_int: i64 = x.get(1);
-
         // User ids are u64 but postgres doesn't support that so we store them as i64
-        // We undo this here before it gets to the user
-        #[allow(clippy::cast_sign_loss)]
-        let user_id = UserId::from(user_id_int as u64);
+        let user_id = UserId::new(from_db_id(x.get(1)));
 
         let due_at: DateTime<Utc> = x.get(2);
         let message: String = x.get(3);
+        let priority = PriorityChoice::from_db(x.get(4));
+        let channel_id = x
+            .get::<_, Option<i64>>(5)
+            .map(|id| ChannelId::new(from_db_id(id)));
+        let category: Option<String> = x.get(6);
+        let deliveries: i16 = x.get(7);
+        let source_link: Option<String> = x.get(8);
+        let reply_channel = x.get::<_, Option<i64>>(9);
+        let reply_message = x.get::<_, Option<i64>>(10);
+        let reply_to = reply_channel.zip(reply_message).map(|(channel, message)| {
+            (
+                ChannelId::new(from_db_id(channel)),
+                MessageId::new(from_db_id(message)),
+            )
+        });
+        let paused: bool = x.get(11);
+        let version: i32 = x.get(12);
 
         Reminder {
             id,
             user_id,
             due_at,
             message,
+            priority,
+            channel_id,
+            category,
+            deliveries,
+            source_link,
+            reply_to,
+            paused,
+            version,
         }
     }
 }
 
+/// A user's reminder usage, counted from when stats started being kept
+struct ReminderStats {
+    /// Reminders the user has made
+    created: i64,
+    /// Reminders sent to the user. Critical reminders only count the first time.
+    delivered: i64,
+    /// Total time between making each reminder and it being due, in seconds
+    lead_seconds: i64,
+    /// Reminders still waiting to be sent
+    pending: i64,
+}
+
+/// How long delivered reminders are kept for `/remindme list` to show
+const RECEIPT_RETENTION: TimeDelta = TimeDelta::days(1);
+
+/// How long destructive operations are kept in the audit log
+const AUDIT_RETENTION: TimeDelta = TimeDelta::days(90);
+
+/// First schema version whose audit log never holds the text of cancelled reminders
+const REDACTED_AUDIT_VERSION: i32 = 19;
+
+/// A reminder that has been delivered, kept briefly so users can check it went out
+struct Receipt {
+    id: i64,
+    message: String,
+    due_at: DateTime<Utc>,
+    delivered_at: DateTime<Utc>,
+}
+
+/// Everything stored about a reminder, for the bot's owners to debug with
+struct ReminderDetails {
+    reminder: Reminder,
+    created_at: DateTime<Utc>,
+    /// Failed delivery attempts since the reminder was last moved or delivered
+    failure_count: i32,
+}
+
+/// A destructive reminder operation, kept so mistakes can be looked into later
+struct AuditEntry {
+    user_id: UserId,
+    /// The command that was run, like `remindme shift`
+    command: String,
+    /// What the command was asked to do, like which reminder it cancelled
+    details: String,
+    /// Number of reminders changed or deleted
+    affected: i64,
+    happened_at: DateTime<Utc>,
+}
+
+/// When a recurring reminder comes back around: on some days of the week,
+/// at a time on the user's clock, until an optional last day
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Schedule {
+    /// Days to recur on, with Monday as the lowest bit, see [`weekday_bit`]
+    weekdays: u8,
+    /// Time of day on the user's clock
+    time: NaiveTime,
+    /// Last day to recur on, on the user's clock
+    until: Option<NaiveDate>,
+}
+
+/// Reminders with the same message, due within this long of each other and made within
+/// this long of each other, are treated as one reminder submitted twice
+const DUPLICATE_WINDOW: TimeDelta = TimeDelta::minutes(1);
+
 /// Helper struct for passing around a bunch of useful stuff for working with the database.
 /// Use the methods on this struct rather than directly acessing the fields.
 pub(crate) struct ReminderDatabase {
     /// The database client used to interact with postgres
-    client: Client,
-    /// A prepared database statement that adds a reminder to the database
+    client: Arc<Client>,
+    /// A prepared database statement that adds a reminder to the database, unless it duplicates a recent one
     add: Statement,
     /// A prepared database statement that removes a reminder from the database
     remove: Statement,
     /// A prepared database statement that fetches all reminders from the database
     select: Statement,
+    /// A prepared database statement that fetches a single reminder by id
+    select_one: Statement,
+    /// A prepared database statement that counts all reminders in the database
+    count: Statement,
+    /// A prepared database statement that counts one user's reminders
+    count_user: Statement,
+    /// A prepared database statement that marks reminders as being sent
+    claim: Statement,
+    /// A prepared database statement that marks a reminder as no longer being sent
+    release: Statement,
+    /// A prepared database statement that fetches the reminders marked as being sent
+    interrupted: Statement,
+    /// A prepared database statement that fetches a user's reminders with messages matching a pattern
+    search: Statement,
+    /// A prepared database statement that fetches a user's next upcoming reminder
+    select_next: Statement,
+    /// A prepared database statement that fetches a user's reminders, optionally in one category
+    list: Statement,
+    /// A prepared database statement that pushes back a reminder that is being sent again
+    snooze: Statement,
+    /// A prepared database statement that moves one of a user's upcoming reminders to a new time
+    postpone: Statement,
+    /// A prepared database statement that moves all of a user's upcoming reminders by some seconds
+    shift: Statement,
+    /// A prepared database statement that adds many reminders for a user at once
+    add_many: Statement,
+    /// A prepared database statement that counts a failed delivery attempt for a reminder
+    record_failure: Statement,
+    /// A prepared database statement that clears a reminder's failed delivery attempts
+    reset_failures: Statement,
+    /// A prepared database statement that deletes overdue reminders that keep failing to deliver
+    purge: Statement,
+    /// A prepared database statement that fetches a user's UTC offset
+    get_timezone: Statement,
+    /// A prepared database statement that sets a user's UTC offset
+    set_timezone: Statement,
+    /// A prepared database statement that fetches a user's webhook
+    get_webhook: Statement,
+    /// A prepared database statement that sets a user's webhook
+    set_webhook: Statement,
+    /// A prepared database statement that removes a user's webhook
+    clear_webhook: Statement,
+    /// A prepared database statement that fetches whether a user wants reminders about messages as replies
+    get_replies: Statement,
+    /// A prepared database statement that sets whether a user wants reminders about messages as replies
+    set_replies: Statement,
+    /// A prepared database statement that adds an earlier heads up for a reminder, deleted along with it
+    add_lead: Statement,
+    /// A prepared database statement that counts reminders a user has created
+    record_created: Statement,
+    /// A prepared database statement that counts a reminder delivered to a user
+    record_delivered: Statement,
+    /// A prepared database statement that fetches a user's reminder stats
+    get_stats: Statement,
+    /// A prepared database statement that keeps a delivered reminder for a while, clearing out old ones
+    record_receipt: Statement,
+    /// A prepared database statement that fetches a user's recently delivered reminders, optionally in one category
+    list_receipts: Statement,
+    /// A prepared database statement that fetches every column of a reminder by id, whoever owns it
+    inspect: Statement,
+    /// A prepared database statement that records a destructive operation in the audit log
+    record_audit: Statement,
+    /// A prepared database statement that fetches the most recent audit log entries
+    list_audit: Statement,
+    /// A prepared database statement that fetches a user's reminders due in a span of time,
+    /// other than a given reminder and its heads-up or main reminder
+    due_between: Statement,
+    /// A prepared database statement that makes a reminder recur on a schedule
+    add_schedule: Statement,
+    /// A prepared database statement that fetches a reminder's schedule
+    get_schedule: Statement,
+    /// A prepared database statement that moves a recurring reminder on to its next occurrence
+    recur: Statement,
+    /// A prepared database statement that pauses or resumes one or all of a user's reminders
+    set_paused: Statement,
+    /// A prepared database statement that removes heads ups that have come due for some reminders
+    drop_leads: Statement,
+    /// Bumped whenever reminders are resumed, waking the tasks of paused reminders that are due
+    resumes: tokio::sync::watch::Sender<u64>,
+    /// Reminders being sent in a digest, so their own tasks leave them be.
+    /// Only held briefly, and never across a database query.
+    digest_claims: tokio::sync::Mutex<HashSet<i64>>,
+    /// Maximum number of reminders allowed in the database, if there is one
+    max_reminders: Option<i64>,
+    /// Maximum number of reminders each user can have, if there is one
+    max_user_reminders: Option<i64>,
+    /// Number of failed deliveries after which a reminder is no longer attempted
+    max_delivery_attempts: i32,
 }
 
 impl ReminderDatabase {
-    /// Connect to the database specified by the given database string
-    /// The string format is specified in the documentation for [`tokio_postgres::Config`]
-    pub(crate) async fn connect(database: String) -> Result<Self, Error> {
-        let (client, connection) = connect(&database, NoTls).await?;
-
-        // The connection object performs the actual communication with the database.
-        // Spawn it off to run on its own so it isn't blocking the main thread forever.
-        tokio::spawn(async move {
-            if let Err(e) = connection.await {
-                eprintln!("connection error: {e}");
-            }
-        });
-
+    /// Create the reminder tables if required, and bring older tables up to date
+    // it is long, but only because there is one statement per table
+    #[allow(clippy::too_many_lines)]
+    async fn create_tables(client: &Client) -> Result<(), Error> {
         // Init table if required
         client
             .execute(
@@ -76,19 +273,429 @@ impl ReminderDatabase {
                 &[],
             )
             .await?;
+        // Columns added after the table was first created
+        client
+            .batch_execute(
+                "ALTER TABLE reminders ADD COLUMN IF NOT EXISTS priority SMALLINT NOT NULL DEFAULT 1;
+                ALTER TABLE reminders ADD COLUMN IF NOT EXISTS failure_count INTEGER NOT NULL DEFAULT 0;
+                ALTER TABLE reminders ADD COLUMN IF NOT EXISTS channel_id BIGINT;
+                ALTER TABLE reminders ADD COLUMN IF NOT EXISTS category TEXT;
+                ALTER TABLE reminders ADD COLUMN IF NOT EXISTS deliveries SMALLINT NOT NULL DEFAULT 0;
+                ALTER TABLE reminders ADD COLUMN IF NOT EXISTS created_at TIMESTAMPTZ NOT NULL DEFAULT now();
+                ALTER TABLE reminders ADD COLUMN IF NOT EXISTS source_link TEXT;
+                ALTER TABLE reminders ADD COLUMN IF NOT EXISTS reply_channel_id BIGINT;
+                ALTER TABLE reminders ADD COLUMN IF NOT EXISTS reply_message_id BIGINT;
+                ALTER TABLE reminders ADD COLUMN IF NOT EXISTS lead_for BIGINT REFERENCES reminders (id) ON DELETE CASCADE;
+                ALTER TABLE reminders ADD COLUMN IF NOT EXISTS paused BOOLEAN NOT NULL DEFAULT false;
+                ALTER TABLE reminders ADD COLUMN IF NOT EXISTS sending BOOLEAN NOT NULL DEFAULT false;
+                ALTER TABLE reminders ADD COLUMN IF NOT EXISTS version INTEGER NOT NULL DEFAULT 0;
+                CREATE INDEX IF NOT EXISTS reminders_user_category ON reminders (user_id, category);",
+            )
+            .await?;
+        client
+            .execute(
+                "CREATE TABLE IF NOT EXISTS timezones (
+                            user_id BIGINT PRIMARY KEY,
+                            utc_offset INTEGER NOT NULL
+                        )",
+                &[],
+            )
+            .await?;
+        client
+            .execute(
+                "CREATE TABLE IF NOT EXISTS webhooks (
+                            user_id BIGINT PRIMARY KEY,
+                            url TEXT NOT NULL,
+                            also_dm BOOLEAN NOT NULL
+                        )",
+                &[],
+            )
+            .await?;
+        client
+            .execute(
+                "CREATE TABLE IF NOT EXISTS reply_preferences (
+                            user_id BIGINT PRIMARY KEY,
+                            reply BOOLEAN NOT NULL
+                        )",
+                &[],
+            )
+            .await?;
+        // Running totals for `/remindme stats`, since reminders are deleted once sent
+        client
+            .execute(
+                "CREATE TABLE IF NOT EXISTS reminder_stats (
+                            user_id BIGINT PRIMARY KEY,
+                            created BIGINT NOT NULL DEFAULT 0,
+                            delivered BIGINT NOT NULL DEFAULT 0,
+                            lead_seconds BIGINT NOT NULL DEFAULT 0
+                        )",
+                &[],
+            )
+            .await?;
+        // Reminders are deleted once sent, so delivered ones are copied here for a short while
+        client
+            .execute(
+                "CREATE TABLE IF NOT EXISTS reminder_receipts (
+                            reminder_id BIGINT PRIMARY KEY,
+                            user_id BIGINT NOT NULL,
+                            message TEXT NOT NULL,
+                            category TEXT,
+                            due_at TIMESTAMPTZ NOT NULL,
+                            delivered_at TIMESTAMPTZ NOT NULL
+                        )",
+                &[],
+            )
+            .await?;
+        // Destructive operations, so mistakes can be investigated after the reminders are gone
+        client
+            .execute(
+                "CREATE TABLE IF NOT EXISTS reminder_audit (
+                            id BIGSERIAL PRIMARY KEY,
+                            user_id BIGINT NOT NULL,
+                            command TEXT NOT NULL,
+                            details TEXT NOT NULL,
+                            affected BIGINT NOT NULL,
+                            happened_at TIMESTAMPTZ NOT NULL DEFAULT now()
+                        )",
+                &[],
+            )
+            .await?;
+        // Cancellations used to record the reminder's text, which is private, so keep only its id
+        if database::stored_schema_version(client)
+            .await?
+            .is_some_and(|version| version < REDACTED_AUDIT_VERSION)
+        {
+            client
+                .execute(
+                    "UPDATE reminder_audit SET details = substring(details from '^#[0-9]+')
+                        WHERE details ~ '^#[0-9]+: '",
+                    &[],
+                )
+                .await?;
+        }
+        // When recurring reminders come back around, removed along with the reminder
+        client
+            .execute(
+                "CREATE TABLE IF NOT EXISTS reminder_schedules (
+                            reminder_id BIGINT PRIMARY KEY REFERENCES reminders (id) ON DELETE CASCADE,
+                            weekdays SMALLINT NOT NULL,
+                            local_time TIME NOT NULL,
+                            until DATE
+                        )",
+                &[],
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Set up the reminder tables and statements using the given database client.
+    /// If `max_reminders` is set then no more than that many reminders will be stored at once,
+    /// and if `max_user_reminders` is set no user can have more than that many.
+    /// Reminders that fail to deliver `max_delivery_attempts` times are given up on.
+    // it is long, but only because there is one statement per query
+    #[allow(clippy::too_many_lines)]
+    pub(crate) async fn new(
+        statements: &StatementCache,
+        max_reminders: Option<i64>,
+        max_user_reminders: Option<i64>,
+        max_delivery_attempts: i32,
+    ) -> Result<Self, Error> {
+        let client = statements.client();
+        Self::create_tables(&client).await?;
 
         // Prepare the statements we'll be using. The tokio-postgres docs warn
         // > Prepared statements should be use for any query which contains user-specified data,
         // > as they provided the functionality to safely embed that data in the request.
         // > Do not form statements via string concatenation and pass them to [other] methods!
         // I believe prepared statements may also have performance benefits?
-        let (add, remove, select) = future::try_join3(
-            client.prepare_typed(
-                "INSERT INTO reminders (user_id, due_at, message) values ($1, $2, $3) RETURNING id",
-                &[Type::INT8, Type::TIMESTAMPTZ, Type::TEXT],
+        let (add, remove, select, count) = future::try_join4(
+            // returns the new reminder's id and true, or a duplicate's id and false,
+            // or nothing if the user already has as many reminders as they're allowed
+            statements.prepare_typed(
+                "WITH duplicate AS (
+                        SELECT id FROM reminders
+                        WHERE user_id = $1 AND message = $3
+                            AND due_at BETWEEN $2 - $7 * interval '1 second' AND $2 + $7 * interval '1 second'
+                            AND created_at > now() - $7 * interval '1 second'
+                        LIMIT 1
+                    ), added AS (
+                        INSERT INTO reminders (user_id, due_at, message, priority, channel_id, category,
+                            source_link, reply_channel_id, reply_message_id)
+                        SELECT $1, $2, $3, $4, $5, $6, $8, $9, $10 WHERE NOT EXISTS (SELECT 1 FROM duplicate)
+                            AND ($11::bigint IS NULL OR (SELECT COUNT(*) FROM reminders WHERE user_id = $1) < $11)
+                        RETURNING id
+                    )
+                    SELECT id, true FROM added UNION ALL SELECT id, false FROM duplicate",
+                &[
+                    Type::INT8,
+                    Type::TIMESTAMPTZ,
+                    Type::TEXT,
+                    Type::INT2,
+                    Type::INT8,
+                    Type::TEXT,
+                    Type::INT8,
+                    Type::TEXT,
+                    Type::INT8,
+                    Type::INT8,
+                    Type::INT8,
+                ],
+            ),
+            statements.prepare_typed("DELETE FROM reminders WHERE id = $1", &[Type::INT8]),
+            statements.prepare_typed(
+                "SELECT id, user_id, due_at, message, priority, channel_id, category, deliveries, source_link, reply_channel_id, reply_message_id, paused, version FROM reminders
+                    WHERE failure_count < $1 AND NOT sending",
+                &[Type::INT4],
+            ),
+            statements.prepare("SELECT COUNT(*) FROM reminders"),
+        )
+        .await?;
+        let (select_one, search, get_timezone, set_timezone) = future::try_join4(
+            statements.prepare_typed(
+                "SELECT id, user_id, due_at, message, priority, channel_id, category, deliveries, source_link, reply_channel_id, reply_message_id, paused, version FROM reminders WHERE id = $1",
+                &[Type::INT8],
+            ),
+            statements.prepare_typed(
+                "SELECT id, user_id, due_at, message, priority, channel_id, category, deliveries, source_link, reply_channel_id, reply_message_id, paused, version FROM reminders
+                    WHERE user_id = $1 AND message ILIKE $2 ORDER BY due_at",
+                &[Type::INT8, Type::TEXT],
+            ),
+            statements.prepare_typed(
+                "SELECT utc_offset FROM timezones WHERE user_id = $1",
+                &[Type::INT8],
+            ),
+            statements.prepare_typed(
+                "INSERT INTO timezones (user_id, utc_offset) values ($1, $2)
+                    ON CONFLICT (user_id) DO UPDATE SET utc_offset = EXCLUDED.utc_offset",
+                &[Type::INT8, Type::INT4],
+            ),
+        )
+        .await?;
+
+        let (add_many, record_failure, reset_failures, purge) = future::try_join4(
+            statements.prepare_typed(
+                "INSERT INTO reminders (user_id, channel_id, due_at, message, priority)
+                    SELECT $1, $2, * FROM UNNEST($3::timestamptz[], $4::text[], $5::smallint[])
+                    WHERE $6::bigint IS NULL
+                        OR (SELECT COUNT(*) FROM reminders WHERE user_id = $1) + cardinality($3::timestamptz[]) <= $6
+                    RETURNING id, user_id, due_at, message, priority, channel_id, category, deliveries, source_link, reply_channel_id, reply_message_id, paused, version",
+                &[
+                    Type::INT8,
+                    Type::INT8,
+                    Type::TIMESTAMPTZ_ARRAY,
+                    Type::TEXT_ARRAY,
+                    Type::INT2_ARRAY,
+                    Type::INT8,
+                ],
+            ),
+            statements.prepare_typed(
+                "UPDATE reminders SET failure_count = failure_count + 1, sending = false WHERE id = $1
+                    RETURNING failure_count",
+                &[Type::INT8],
+            ),
+            statements.prepare_typed(
+                "UPDATE reminders SET failure_count = 0 WHERE id = $1",
+                &[Type::INT8],
+            ),
+            statements.prepare_typed(
+                "DELETE FROM reminders WHERE due_at < $1 AND failure_count >= $2",
+                &[Type::TIMESTAMPTZ, Type::INT4],
+            ),
+        )
+        .await?;
+        let (select_next, list, snooze, shift) = future::try_join4(
+            statements.prepare_typed(
+                "SELECT id, user_id, due_at, message, priority, channel_id, category, deliveries, source_link, reply_channel_id, reply_message_id, paused, version FROM reminders
+                    WHERE user_id = $1 AND due_at > now() AND NOT paused ORDER BY due_at LIMIT 1",
+                &[Type::INT8],
+            ),
+            statements.prepare_typed(
+                "SELECT id, user_id, due_at, message, priority, channel_id, category, deliveries, source_link, reply_channel_id, reply_message_id, paused, version FROM reminders
+                    WHERE user_id = $1 AND ($2::text IS NULL OR category = $2) ORDER BY due_at",
+                &[Type::INT8, Type::TEXT],
+            ),
+            statements.prepare_typed(
+                "UPDATE reminders SET due_at = $2, deliveries = deliveries + 1, failure_count = 0, sending = false,
+                        version = version + 1
+                    WHERE id = $1
+                    RETURNING id, user_id, due_at, message, priority, channel_id, category, deliveries, source_link, reply_channel_id, reply_message_id, paused, version",
+                &[Type::INT8, Type::TIMESTAMPTZ],
+            ),
+            statements.prepare_typed(
+                "UPDATE reminders SET due_at = due_at + $2 * interval '1 second', version = version + 1
+                    WHERE user_id = $1 AND due_at > now()
+                    RETURNING id, user_id, due_at, message, priority, channel_id, category, deliveries, source_link, reply_channel_id, reply_message_id, paused, version",
+                &[Type::INT8, Type::INT8],
+            ),
+        )
+        .await?;
+
+        let (get_webhook, set_webhook, clear_webhook) = future::try_join3(
+            statements.prepare_typed(
+                "SELECT url, also_dm FROM webhooks WHERE user_id = $1",
+                &[Type::INT8],
+            ),
+            statements.prepare_typed(
+                "INSERT INTO webhooks (user_id, url, also_dm) values ($1, $2, $3)
+                    ON CONFLICT (user_id) DO UPDATE SET url = EXCLUDED.url, also_dm = EXCLUDED.also_dm",
+                &[Type::INT8, Type::TEXT, Type::BOOL],
+            ),
+            statements.prepare_typed("DELETE FROM webhooks WHERE user_id = $1", &[Type::INT8]),
+        )
+        .await?;
+        let (get_replies, set_replies, add_lead) = future::try_join3(
+            statements.prepare_typed(
+                "SELECT reply FROM reply_preferences WHERE user_id = $1",
+                &[Type::INT8],
+            ),
+            statements.prepare_typed(
+                "INSERT INTO reply_preferences (user_id, reply) values ($1, $2)
+                    ON CONFLICT (user_id) DO UPDATE SET reply = EXCLUDED.reply",
+                &[Type::INT8, Type::BOOL],
+            ),
+            // copies the main reminder, so the two can't drift apart as it is made
+            statements.prepare_typed(
+                "INSERT INTO reminders (user_id, due_at, message, priority, channel_id, category, lead_for)
+                    SELECT user_id, $2, '(upcoming) ' || message, priority, channel_id, category, id
+                    FROM reminders WHERE id = $1
+                    RETURNING id, user_id, due_at, message, priority, channel_id, category, deliveries, source_link, reply_channel_id, reply_message_id, paused, version",
+                &[Type::INT8, Type::TIMESTAMPTZ],
+            ),
+        )
+        .await?;
+
+        let (record_created, record_delivered, get_stats) = future::try_join3(
+            statements.prepare_typed(
+                "INSERT INTO reminder_stats (user_id, created, lead_seconds) values ($1, $2, $3)
+                    ON CONFLICT (user_id) DO UPDATE SET
+                        created = reminder_stats.created + EXCLUDED.created,
+                        lead_seconds = reminder_stats.lead_seconds + EXCLUDED.lead_seconds",
+                &[Type::INT8, Type::INT8, Type::INT8],
+            ),
+            statements.prepare_typed(
+                "INSERT INTO reminder_stats (user_id, delivered) values ($1, 1)
+                    ON CONFLICT (user_id) DO UPDATE SET delivered = reminder_stats.delivered + 1",
+                &[Type::INT8],
+            ),
+            statements.prepare_typed(
+                "SELECT COALESCE(s.created, 0), COALESCE(s.delivered, 0), COALESCE(s.lead_seconds, 0),
+                        (SELECT COUNT(*) FROM reminders WHERE user_id = $1)
+                    FROM (SELECT $1) AS u(user_id)
+                    LEFT JOIN reminder_stats s ON s.user_id = u.user_id",
+                &[Type::INT8],
+            ),
+        )
+        .await?;
+
+        let (record_receipt, list_receipts, postpone, inspect) = future::try_join4(
+            statements.prepare_typed(
+                "WITH expired AS (
+                        DELETE FROM reminder_receipts WHERE delivered_at < now() - $7 * interval '1 second'
+                    )
+                    INSERT INTO reminder_receipts (reminder_id, user_id, message, category, due_at, delivered_at)
+                    values ($1, $2, $3, $4, $5, $6) ON CONFLICT (reminder_id) DO NOTHING",
+                &[
+                    Type::INT8,
+                    Type::INT8,
+                    Type::TEXT,
+                    Type::TEXT,
+                    Type::TIMESTAMPTZ,
+                    Type::TIMESTAMPTZ,
+                    Type::INT8,
+                ],
+            ),
+            statements.prepare_typed(
+                "SELECT reminder_id, message, due_at, delivered_at FROM reminder_receipts
+                    WHERE user_id = $1 AND ($2::text IS NULL OR category = $2)
+                        AND delivered_at > now() - $3 * interval '1 second'
+                    ORDER BY delivered_at DESC",
+                &[Type::INT8, Type::TEXT, Type::INT8],
+            ),
+            statements.prepare_typed(
+                "UPDATE reminders SET due_at = $3, failure_count = 0, version = version + 1
+                    WHERE id = $1 AND user_id = $2 AND due_at > now()
+                    RETURNING id, user_id, due_at, message, priority, channel_id, category, deliveries, source_link, reply_channel_id, reply_message_id, paused, version",
+                &[Type::INT8, Type::INT8, Type::TIMESTAMPTZ],
+            ),
+            statements.prepare_typed(
+                "SELECT id, user_id, due_at, message, priority, channel_id, category, deliveries, source_link, reply_channel_id, reply_message_id, paused, version, created_at, failure_count
+                    FROM reminders WHERE id = $1",
+                &[Type::INT8],
+            ),
+        )
+        .await?;
+
+        let (record_audit, list_audit, due_between) = future::try_join3(
+            statements.prepare_typed(
+                "WITH expired AS (
+                        DELETE FROM reminder_audit WHERE happened_at < now() - $5 * interval '1 second'
+                    )
+                    INSERT INTO reminder_audit (user_id, command, details, affected) values ($1, $2, $3, $4)",
+                &[Type::INT8, Type::TEXT, Type::TEXT, Type::INT8, Type::INT8],
+            ),
+            statements.prepare_typed(
+                "SELECT user_id, command, details, affected, happened_at FROM reminder_audit
+                    ORDER BY happened_at DESC, id DESC LIMIT $1",
+                &[Type::INT8],
+            ),
+            statements.prepare_typed(
+                "SELECT id, user_id, due_at, message, priority, channel_id, category, deliveries, source_link, reply_channel_id, reply_message_id, paused, version
+                    FROM reminders WHERE user_id = $1 AND due_at BETWEEN $2 AND $3 AND failure_count < $4
+                        AND NOT paused AND id <> $5 AND lead_for IS DISTINCT FROM $5
+                        AND id IS DISTINCT FROM (SELECT lead_for FROM reminders WHERE id = $5)
+                    ORDER BY due_at",
+                &[Type::INT8, Type::TIMESTAMPTZ, Type::TIMESTAMPTZ, Type::INT4, Type::INT8],
+            ),
+        )
+        .await?;
+
+        let (add_schedule, get_schedule, recur, set_paused) = future::try_join4(
+            statements.prepare_typed(
+                "INSERT INTO reminder_schedules (reminder_id, weekdays, local_time, until)
+                    values ($1, $2, $3, $4)",
+                &[Type::INT8, Type::INT2, Type::TIME, Type::DATE],
+            ),
+            statements.prepare_typed(
+                "SELECT weekdays, local_time, until FROM reminder_schedules WHERE reminder_id = $1",
+                &[Type::INT8],
+            ),
+            statements.prepare_typed(
+                "UPDATE reminders SET due_at = $2, deliveries = 0, failure_count = 0, sending = false,
+                        version = version + 1
+                    WHERE id = $1
+                    RETURNING id, user_id, due_at, message, priority, channel_id, category, deliveries, source_link, reply_channel_id, reply_message_id, paused, version",
+                &[Type::INT8, Type::TIMESTAMPTZ],
+            ),
+            // a reminder's heads up is paused and resumed along with it
+            statements.prepare_typed(
+                "UPDATE reminders SET paused = $3
+                    WHERE user_id = $1 AND ($2::bigint IS NULL OR id = $2 OR lead_for = $2)
+                        AND paused <> $3
+                    RETURNING id, user_id, due_at, message, priority, channel_id, category, deliveries, source_link, reply_channel_id, reply_message_id, paused, version",
+                &[Type::INT8, Type::INT8, Type::BOOL],
+            ),
+        )
+        .await?;
+        let (count_user, claim, release, interrupted, drop_leads) = future::try_join5(
+            statements.prepare_typed(
+                "SELECT COUNT(*) FROM reminders WHERE user_id = $1",
+                &[Type::INT8],
+            ),
+            statements.prepare_typed(
+                "UPDATE reminders SET sending = true WHERE id = ANY($1) AND NOT sending RETURNING id",
+                &[Type::INT8_ARRAY],
+            ),
+            statements.prepare_typed(
+                "UPDATE reminders SET sending = false WHERE id = $1",
+                &[Type::INT8],
+            ),
+            statements.prepare(
+                "SELECT id, user_id, due_at, message, priority, channel_id, category, deliveries, source_link, reply_channel_id, reply_message_id, paused, version FROM reminders
+                    WHERE sending ORDER BY due_at",
+            ),
+            statements.prepare_typed(
+                "DELETE FROM reminders WHERE lead_for = ANY($1) AND due_at <= $2",
+                &[Type::INT8_ARRAY, Type::TIMESTAMPTZ],
             ),
-            client.prepare_typed("DELETE FROM reminders WHERE id = $1", &[Type::INT8]),
-            client.prepare("SELECT id, user_id, due_at, message FROM reminders"),
         )
         .await?;
 
@@ -98,220 +705,4383 @@ impl ReminderDatabase {
             add,
             remove,
             select,
+            select_one,
+            count,
+            count_user,
+            claim,
+            release,
+            interrupted,
+            search,
+            select_next,
+            list,
+            snooze,
+            postpone,
+            shift,
+            add_many,
+            record_failure,
+            reset_failures,
+            purge,
+            get_timezone,
+            set_timezone,
+            get_webhook,
+            set_webhook,
+            clear_webhook,
+            get_replies,
+            set_replies,
+            add_lead,
+            record_created,
+            record_delivered,
+            get_stats,
+            record_receipt,
+            list_receipts,
+            inspect,
+            record_audit,
+            list_audit,
+            due_between,
+            add_schedule,
+            get_schedule,
+            recur,
+            set_paused,
+            drop_leads,
+            resumes: tokio::sync::watch::Sender::new(0),
+            digest_claims: tokio::sync::Mutex::default(),
+            max_reminders,
+            max_user_reminders,
+            max_delivery_attempts,
         };
         Ok(db_helper)
     }
 
-    /// Add a reminder to the database
+    /// Add a reminder to the database.
+    /// Fails if the database or the user already holds the maximum number of reminders.
+    /// If the user made the same reminder moments ago, which usually means a retried submission,
+    /// nothing is added and the existing reminder's id is returned instead.
+    // each argument is a column of the new reminder
+    #[allow(clippy::too_many_arguments)]
     async fn add_reminder(
         &self,
         user_id: UserId,
+        channel_id: Option<ChannelId>,
         due_at: DateTime<Utc>,
         message: String,
-    ) -> Result<Reminder, Error> {
-        // Postgres doesn't have an unsigned int 64 so we cast it to an i64
-        #[allow(clippy::cast_possible_wrap)]
-        let author_id = user_id.get() as i64;
+        priority: PriorityChoice,
+        category: Option<String>,
+        source_link: Option<String>,
+        reply_to: Option<(ChannelId, MessageId)>,
+    ) -> Result<Added, Error> {
+        // Check the global cap before adding anything
+        // Concurrent inserts could overshoot it slightly but that's fine for a safety limit
+        if let Some(limit) = self.max_reminders {
+            let count: i64 = self.client.query_one(&self.count, &[]).await?.get(0);
+            if count >= limit {
+                println!("Reminder cap of {limit} reached, rejecting new reminder");
+                return Err("The bot is at capacity and can't take any more reminders".into());
+            }
+        }
 
-        let id: i64 = self
+        // the user's limit is checked in the same statement as the insert, so it can't be overshot
+        let row = self
             .client
-            .query_one(&self.add, &[&author_id, &due_at, &message])
-            .await?
-            .get(0);
+            .query_opt(
+                &self.add,
+                &[
+                    &to_db_id(user_id),
+                    &due_at,
+                    &message,
+                    &priority.to_db(),
+                    &channel_id.map(to_db_id),
+                    &category,
+                    &DUPLICATE_WINDOW.num_seconds(),
+                    &source_link,
+                    &reply_to.map(|(channel, _)| to_db_id(channel)),
+                    &reply_to.map(|(_, message)| to_db_id(message)),
+                    &self.max_user_reminders,
+                ],
+            )
+            .await?;
+        let Some(row) = row else {
+            let limit = self.max_user_reminders.unwrap_or_default();
+            return Err(format!(
+                "You can only have {limit} reminders at once, remove some to make room"
+            )
+            .into());
+        };
+        let id: i64 = row.get(0);
+        if !row.get::<_, bool>(1) {
+            return Ok(Added::Duplicate(id));
+        }
+        self.record_created(user_id, &[due_at]).await;
 
-        Ok(Reminder {
+        Ok(Added::New(Reminder {
             id,
             user_id,
             due_at,
             message,
-        })
-    }
-
-    /// Remove a reminder from the database
-    async fn remove_reminder(&self, reminder: Reminder) -> Result<(), Error> {
-        self.client.execute(&self.remove, &[&reminder.id]).await?;
-        Ok(())
+            priority,
+            channel_id,
+            category,
+            deliveries: 0,
+            source_link,
+            reply_to,
+            paused: false,
+            version: 0,
+        }))
     }
 
-    /// Get all reminders in the database.
-    /// Because we purge all past reminders this should just include future reminders.
-    /// However this is not guaranteed.
-    async fn get_reminders(&self) -> Result<Vec<Row>, Error> {
-        let rows = self.client.query(&self.select, &[]).await?;
-        Ok(rows)
+    /// Add a heads up for a reminder, due at `due_at`, with the same message marked as upcoming.
+    /// It is linked to the reminder, so removing the reminder removes it too.
+    async fn add_lead(
+        &self,
+        reminder: &Reminder,
+        due_at: DateTime<Utc>,
+    ) -> Result<Reminder, Error> {
+        let row = self
+            .client
+            .query_opt(&self.add_lead, &[&reminder.id, &due_at])
+            .await?
+            .ok_or("The reminder was removed before its heads up could be added")?;
+        Ok(Reminder::from_row(&row))
     }
-}
-
-/// Helper enum for the available time periods
-#[derive(Debug, poise::ChoiceParameter)]
-enum TimeUnitChoice {
-    #[name = "seconds"]
-    Seconds,
-    #[name = "minutes"]
-    Minutes,
-    #[name = "hours"]
-    Hours,
-    #[name = "days"]
-    Days,
-    #[name = "weeks"]
-    Weeks,
-    /// Actually 28 days
-    #[name = "months"]
-    Months,
-}
 
-/// Calculate when a reminder is due from the start time and duration.
-/// The quantity and unit of the duration are passed as seperate parameters.
-fn calculate_wait(
-    start: serenity::Timestamp,
-    duration: i64,
-    unit: &TimeUnitChoice,
-) -> DateTime<Utc> {
-    let start_time = start.to_utc();
+    /// Add a batch of reminders for a user in one statement.
+    /// Fails without adding anything if the batch would take the database past the maximum number of reminders,
+    /// or the user past their own maximum.
+    async fn add_reminders(
+        &self,
+        user_id: UserId,
+        channel_id: Option<ChannelId>,
+        reminders: Vec<ImportedReminder>,
+    ) -> Result<Vec<Reminder>, Error> {
+        if let Some(limit) = self.max_reminders {
+            let count: i64 = self.client.query_one(&self.count, &[]).await?.get(0);
+            let batch = i64::try_from(reminders.len())?;
+            if count + batch > limit {
+                println!("Reminder cap of {limit} would be exceeded, rejecting import");
+                return Err(format!(
+                    "The bot only has room for {} more reminders",
+                    (limit - count).max(0)
+                )
+                .into());
+            }
+        }
 
-    let wait_duration = match unit {
-        TimeUnitChoice::Seconds => Duration::seconds(duration),
-        TimeUnitChoice::Minutes => Duration::minutes(duration),
-        TimeUnitChoice::Hours => Duration::hours(duration),
-        TimeUnitChoice::Days => Duration::days(duration),
-        TimeUnitChoice::Weeks => Duration::weeks(duration),
-        TimeUnitChoice::Months => Duration::days(28 * duration),
-    };
+        let mut due_ats = Vec::with_capacity(reminders.len());
+        let mut messages = Vec::with_capacity(reminders.len());
+        let mut priorities = Vec::with_capacity(reminders.len());
+        for reminder in reminders {
+            due_ats.push(reminder.due_at);
+            messages.push(reminder.message);
+            priorities.push(reminder.priority.to_db());
+        }
+        // the whole batch counts towards the user's limit, checked in the same statement as the insert
+        let rows = self
+            .client
+            .query(
+                &self.add_many,
+                &[
+                    &to_db_id(user_id),
+                    &channel_id.map(to_db_id),
+                    &due_ats,
+                    &messages,
+                    &priorities,
+                    &self.max_user_reminders,
+                ],
+            )
+            .await?;
+        if rows.is_empty() && !due_ats.is_empty() {
+            let limit = self.max_user_reminders.unwrap_or_default();
+            let count: i64 = self
+                .client
+                .query_one(&self.count_user, &[&to_db_id(user_id)])
+                .await?
+                .get(0);
+            return Err(format!(
+                "You can only have {limit} reminders at once and already have {count}, \
+                so there's only room for {} more",
+                (limit - count).max(0)
+            )
+            .into());
+        }
+        let added: Vec<Reminder> = rows.iter().map(Reminder::from_row).collect();
+        let due_ats: Vec<DateTime<Utc>> = added.iter().map(|r| r.due_at).collect();
+        self.record_created(user_id, &due_ats).await;
+        Ok(added)
+    }
 
-    // Add the wait duration to the start time
-    start_time + wait_duration
-}
+    /// Count newly created reminders towards a user's stats.
+    /// Stats are a nicety, so failures are only logged.
+    async fn record_created(&self, user_id: UserId, due_ats: &[DateTime<Utc>]) {
+        let now = Utc::now();
+        let lead_seconds: i64 = due_ats
+            .iter()
+            .map(|due| (*due - now).num_seconds().max(0))
+            .sum();
+        let count = i64::try_from(due_ats.len()).unwrap_or(i64::MAX);
+        if let Err(e) = self
+            .client
+            .execute(
+                &self.record_created,
+                &[&to_db_id(user_id), &count, &lead_seconds],
+            )
+            .await
+        {
+            println!("Unable to record new reminders for {user_id} in stats: {e:?}");
+        }
+    }
 
-/// Deliver a reminder to a user in their direct messages
-async fn send_reminder(bot: Arc<serenity::Http>, reminder: &Reminder) -> Result<(), Error> {
-    // Get the user's DMs
-    let user = bot.get_user(reminder.user_id).await?;
-    let dm_channel = user.create_dm_channel(bot.clone()).await?;
+    /// Count a delivered reminder towards a user's stats.
+    /// Stats are a nicety, so failures are only logged.
+    async fn record_delivered(&self, user_id: UserId) {
+        if let Err(e) = self
+            .client
+            .execute(&self.record_delivered, &[&to_db_id(user_id)])
+            .await
+        {
+            println!("Unable to record delivered reminder for {user_id} in stats: {e:?}");
+        }
+    }
 
-    // Prepare and send the message
-    let message = CreateMessage::default().add_embed(
-        CreateEmbed::default()
-            .title("Reminder")
-            .description(reminder.message.clone())
-            .field(
-                "Scheduled For",
-                format!("<t:{}>", reminder.due_at.timestamp()),
-                false,
+    /// Keep a delivered reminder around for [`RECEIPT_RETENTION`], and forget older ones.
+    /// Receipts are a nicety, so failures are only logged.
+    async fn record_receipt(&self, reminder: &Reminder, delivered_at: DateTime<Utc>) {
+        if let Err(e) = self
+            .client
+            .execute(
+                &self.record_receipt,
+                &[
+                    &reminder.id,
+                    &to_db_id(reminder.user_id),
+                    &reminder.message,
+                    &reminder.category,
+                    &reminder.due_at,
+                    &delivered_at,
+                    &RECEIPT_RETENTION.num_seconds(),
+                ],
             )
-            .field(
-                "Delivery Accuracy",
-                format!(
-                    "{} seconds late",
-                    (Utc::now() - reminder.due_at).num_seconds()
-                ),
-                false,
-            ),
-    );
-    dm_channel.send_message(bot, message).await?;
-
-    Ok(())
-}
+            .await
+        {
+            println!(
+                "Unable to record receipt for reminder {}: {e:?}",
+                reminder.id
+            );
+        }
+    }
 
-/// Send a reminder to the user.
-/// If successful, remove it from the database.
-/// If not, log an error and leave the reminder in the database
-/// so it can be retired later.
-async fn send_and_remove_reminder(
-    database: Arc<ReminderDatabase>,
-    bot: Arc<serenity::Http>,
-    reminder: Reminder,
-) {
-    if let Err(e) = send_reminder(bot, &reminder).await {
-        println!("Unable to send reminder: {e:?}");
-        return;
+    /// Get a user's reminders delivered within [`RECEIPT_RETENTION`], most recent first,
+    /// optionally only those in one category
+    async fn list_receipts(
+        &self,
+        user_id: UserId,
+        category: Option<&str>,
+    ) -> Result<Vec<Receipt>, Error> {
+        let rows = self
+            .client
+            .query(
+                &self.list_receipts,
+                &[
+                    &to_db_id(user_id),
+                    &category,
+                    &RECEIPT_RETENTION.num_seconds(),
+                ],
+            )
+            .await?;
+        Ok(rows
+            .iter()
+            .map(|row| Receipt {
+                id: row.get(0),
+                message: row.get(1),
+                due_at: row.get(2),
+                delivered_at: row.get(3),
+            })
+            .collect())
     }
-    if let Err(e) = database.remove_reminder(reminder).await {
-        println!("Unable to remove reminder: {e:?}");
+
+    /// Get a user's reminder stats
+    async fn get_stats(&self, user_id: UserId) -> Result<ReminderStats, Error> {
+        let row = self
+            .client
+            .query_one(&self.get_stats, &[&to_db_id(user_id)])
+            .await?;
+        Ok(ReminderStats {
+            created: row.get(0),
+            delivered: row.get(1),
+            lead_seconds: row.get(2),
+            pending: row.get(3),
+        })
     }
-}
 
-/// Sleep until a reminder is due, then deliver it and remove it from the database
-async fn sleeping_reminder(
-    database: Arc<ReminderDatabase>,
-    bot: Arc<serenity::Http>,
-    reminder: Reminder,
-) {
-    let delta = reminder.due_at - Utc::now();
+    /// Remove a reminder from the database
+    async fn remove_reminder(&self, reminder: Reminder) -> Result<(), Error> {
+        self.client.execute(&self.remove, &[&reminder.id]).await?;
+        Ok(())
+    }
 
-    if delta <= TimeDelta::zero() {
-        send_and_remove_reminder(database, bot, reminder).await;
-        return;
+    /// Mark reminders as being sent, before anything is sent. If the bot stops partway through
+    /// sending, this is how it knows not to send them again, see [`finish_interrupted`].
+    /// Returns the ids that were marked, leaving out any already being sent or removed.
+    async fn claim_for_sending(&self, ids: &[i64]) -> Result<Vec<i64>, Error> {
+        let rows = self.client.query(&self.claim, &[&ids]).await?;
+        Ok(rows.iter().map(|row| row.get(0)).collect())
     }
 
-    let duration = match delta.to_std() {
-        Ok(v) => v,
-        Err(e) => {
-            println!("Unable to calculate reminder instant: {e}");
-            return;
+    /// Mark a reminder as no longer being sent, after a failed send that doesn't count
+    /// against it, so it is tried again on restart. Failures are only logged.
+    async fn release_sending(&self, id: i64) {
+        if let Err(e) = self.client.execute(&self.release, &[&id]).await {
+            println!("Unable to release reminder {id} for sending: {e:?}");
         }
-    };
+    }
 
-    tokio::time::sleep(duration).await;
-    send_and_remove_reminder(database, bot, reminder).await;
+    /// Get the reminders that were still marked as being sent when the bot stopped
+    async fn get_interrupted(&self) -> Result<Vec<Reminder>, Error> {
+        let rows = self.client.query(&self.interrupted, &[]).await?;
+        Ok(rows.iter().map(Reminder::from_row).collect())
+    }
+
+    /// Count a failed attempt to deliver a reminder, which is no longer being sent.
+    /// Returns true if the reminder has now failed too many times and should be given up on.
+    async fn record_failure(&self, id: i64) -> Result<bool, Error> {
+        let failures: Option<i32> = self
+            .client
+            .query_opt(&self.record_failure, &[&id])
+            .await?
+            .map(|row| row.get(0));
+        Ok(failures.is_some_and(|n| n >= self.max_delivery_attempts))
+    }
+
+    /// Count a delivery of a reminder that will be sent again, and move it to `due_at`.
+    /// Returns the updated reminder, or `None` if it has been removed in the meantime.
+    async fn snooze_reminder(
+        &self,
+        id: i64,
+        due_at: DateTime<Utc>,
+    ) -> Result<Option<Reminder>, Error> {
+        let row = self.client.query_opt(&self.snooze, &[&id, &due_at]).await?;
+        Ok(row.as_ref().map(Reminder::from_row))
+    }
+
+    /// Make a reminder recur on a schedule once it has been delivered
+    async fn add_schedule(&self, id: i64, schedule: &Schedule) -> Result<(), Error> {
+        self.client
+            .execute(
+                &self.add_schedule,
+                &[
+                    &id,
+                    &i16::from(schedule.weekdays),
+                    &schedule.time,
+                    &schedule.until,
+                ],
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Get a reminder's schedule, or `None` if it doesn't recur
+    async fn get_schedule(&self, id: i64) -> Result<Option<Schedule>, Error> {
+        let Some(row) = self.client.query_opt(&self.get_schedule, &[&id]).await? else {
+            return Ok(None);
+        };
+        Ok(Some(Schedule {
+            weekdays: u8::try_from(row.get::<_, i16>(0))?,
+            time: row.get(1),
+            until: row.get(2),
+        }))
+    }
+
+    /// Move a recurring reminder on to its next occurrence at `due_at`, as a fresh delivery.
+    /// Returns the updated reminder, or `None` if it has been removed in the meantime.
+    async fn recur_reminder(
+        &self,
+        id: i64,
+        due_at: DateTime<Utc>,
+    ) -> Result<Option<Reminder>, Error> {
+        let row = self.client.query_opt(&self.recur, &[&id, &due_at]).await?;
+        Ok(row.as_ref().map(Reminder::from_row))
+    }
+
+    /// Pause or resume one of a user's reminders, or all of them if `id` is `None`.
+    /// Returns the reminders that changed, which leaves out any that were already that way.
+    /// Resuming wakes the tasks of reminders that came due while paused, so they're sent.
+    async fn set_paused(
+        &self,
+        user_id: UserId,
+        id: Option<i64>,
+        paused: bool,
+    ) -> Result<Vec<Reminder>, Error> {
+        let rows = self
+            .client
+            .query(&self.set_paused, &[&to_db_id(user_id), &id, &paused])
+            .await?;
+        if !paused {
+            self.resumes.send_modify(|count| *count += 1);
+        }
+        Ok(rows.iter().map(Reminder::from_row).collect())
+    }
+
+    /// Remove the heads ups of some reminders that came due by `now`
+    async fn drop_due_leads(&self, ids: &[i64], now: DateTime<Utc>) -> Result<(), Error> {
+        self.client.execute(&self.drop_leads, &[&ids, &now]).await?;
+        Ok(())
+    }
+
+    /// Move one of a user's reminders to a new time, if it hasn't come due yet.
+    /// Returns the moved reminder, or `None` if the user has no such upcoming reminder.
+    async fn postpone_reminder(
+        &self,
+        id: i64,
+        user_id: UserId,
+        due_at: DateTime<Utc>,
+    ) -> Result<Option<Reminder>, Error> {
+        let row = self
+            .client
+            .query_opt(&self.postpone, &[&id, &to_db_id(user_id), &due_at])
+            .await?;
+        Ok(row.as_ref().map(Reminder::from_row))
+    }
+
+    /// Move all of a user's upcoming reminders by `offset`, which may be negative.
+    /// Returns the moved reminders.
+    async fn shift_reminders(
+        &self,
+        user_id: UserId,
+        offset: Duration,
+    ) -> Result<Vec<Reminder>, Error> {
+        let rows = self
+            .client
+            .query(&self.shift, &[&to_db_id(user_id), &offset.num_seconds()])
+            .await?;
+        Ok(rows.iter().map(Reminder::from_row).collect())
+    }
+
+    /// Clear the failed delivery attempts for a reminder
+    async fn reset_failures(&self, id: i64) -> Result<(), Error> {
+        self.client.execute(&self.reset_failures, &[&id]).await?;
+        Ok(())
+    }
+
+    /// Delete reminders that were due before `due_before` and have failed to
+    /// deliver at least `min_failures` times. Returns the number deleted.
+    async fn purge_failed(
+        &self,
+        due_before: DateTime<Utc>,
+        min_failures: i32,
+    ) -> Result<u64, Error> {
+        let purged = self
+            .client
+            .execute(&self.purge, &[&due_before, &min_failures])
+            .await?;
+        Ok(purged)
+    }
+
+    /// Get a single reminder by id, if it still exists
+    async fn get_reminder(&self, id: i64) -> Result<Option<Reminder>, Error> {
+        let row = self.client.query_opt(&self.select_one, &[&id]).await?;
+        Ok(row.as_ref().map(Reminder::from_row))
+    }
+
+    /// Get everything stored about a reminder, whoever it belongs to
+    async fn inspect_reminder(&self, id: i64) -> Result<Option<ReminderDetails>, Error> {
+        let row = self.client.query_opt(&self.inspect, &[&id]).await?;
+        Ok(row.map(|r| ReminderDetails {
+            reminder: Reminder::from_row(&r),
+            created_at: r.get(13),
+            failure_count: r.get(14),
+        }))
+    }
+
+    /// Get a user's reminders whose message contains the given text, ignoring case.
+    /// Results are sorted by when they are due.
+    async fn search_reminders(&self, user_id: UserId, text: &str) -> Result<Vec<Reminder>, Error> {
+        // Escape the LIKE wildcards so they match literally
+        let escaped = text
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_");
+        let pattern = format!("%{escaped}%");
+
+        let rows = self
+            .client
+            .query(&self.search, &[&to_db_id(user_id), &pattern])
+            .await?;
+        Ok(rows.iter().map(Reminder::from_row).collect())
+    }
+
+    /// Get all of a user's reminders, or only those in `category` if one is given.
+    /// Results are sorted by when they are due.
+    async fn list_reminders(
+        &self,
+        user_id: UserId,
+        category: Option<&str>,
+    ) -> Result<Vec<Reminder>, Error> {
+        let rows = self
+            .client
+            .query(&self.list, &[&to_db_id(user_id), &category])
+            .await?;
+        Ok(rows.iter().map(Reminder::from_row).collect())
+    }
+
+    /// Get a user's earliest reminder that isn't due yet, if they have one
+    async fn next_reminder(&self, user_id: UserId) -> Result<Option<Reminder>, Error> {
+        let row = self
+            .client
+            .query_opt(&self.select_next, &[&to_db_id(user_id)])
+            .await?;
+        Ok(row.as_ref().map(Reminder::from_row))
+    }
+
+    /// Get the UTC offset a user has set, if they have set one
+    async fn get_timezone(&self, user_id: UserId) -> Result<Option<FixedOffset>, Error> {
+        let row = self
+            .client
+            .query_opt(&self.get_timezone, &[&to_db_id(user_id)])
+            .await?;
+        Ok(row.and_then(|r| FixedOffset::east_opt(r.get(0))))
+    }
+
+    /// Set a user's UTC offset, replacing any existing one
+    async fn set_timezone(&self, user_id: UserId, offset: FixedOffset) -> Result<(), Error> {
+        self.client
+            .execute(
+                &self.set_timezone,
+                &[&to_db_id(user_id), &offset.local_minus_utc()],
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Get the webhook a user has set, if they have set one
+    async fn get_webhook(&self, user_id: UserId) -> Result<Option<Webhook>, Error> {
+        let row = self
+            .client
+            .query_opt(&self.get_webhook, &[&to_db_id(user_id)])
+            .await?;
+        Ok(row.map(|r| Webhook {
+            url: r.get(0),
+            also_dm: r.get(1),
+        }))
+    }
+
+    /// Set a user's webhook, replacing any existing one
+    async fn set_webhook(&self, user_id: UserId, webhook: &Webhook) -> Result<(), Error> {
+        self.client
+            .execute(
+                &self.set_webhook,
+                &[&to_db_id(user_id), &webhook.url, &webhook.also_dm],
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Remove a user's webhook. Returns false if they didn't have one.
+    async fn clear_webhook(&self, user_id: UserId) -> Result<bool, Error> {
+        let removed = self
+            .client
+            .execute(&self.clear_webhook, &[&to_db_id(user_id)])
+            .await?;
+        Ok(removed > 0)
+    }
+
+    /// Whether a user wants reminders about messages delivered as replies to the message
+    async fn get_replies(&self, user_id: UserId) -> Result<bool, Error> {
+        let row = self
+            .client
+            .query_opt(&self.get_replies, &[&to_db_id(user_id)])
+            .await?;
+        Ok(row.is_some_and(|r| r.get(0)))
+    }
+
+    /// Set whether a user wants reminders about messages delivered as replies to the message
+    async fn set_replies(&self, user_id: UserId, reply: bool) -> Result<(), Error> {
+        self.client
+            .execute(&self.set_replies, &[&to_db_id(user_id), &reply])
+            .await?;
+        Ok(())
+    }
+
+    /// Record a destructive operation in the audit log, and forget entries older than [`AUDIT_RETENTION`].
+    /// Details shouldn't include reminders' text, which is private, just ids and counts.
+    /// The operation has already happened, so failures are only logged.
+    async fn record_audit(&self, user_id: UserId, command: &str, details: &str, affected: u64) {
+        let affected = i64::try_from(affected).unwrap_or(i64::MAX);
+        if let Err(e) = self
+            .client
+            .execute(
+                &self.record_audit,
+                &[
+                    &to_db_id(user_id),
+                    &command,
+                    &details,
+                    &affected,
+                    &AUDIT_RETENTION.num_seconds(),
+                ],
+            )
+            .await
+        {
+            println!("Unable to record `{command}` by {user_id} in the audit log: {e:?}");
+        }
+    }
+
+    /// Get the `count` most recent audit log entries, newest first
+    async fn list_audit(&self, count: i64) -> Result<Vec<AuditEntry>, Error> {
+        let rows = self.client.query(&self.list_audit, &[&count]).await?;
+        Ok(rows
+            .iter()
+            .map(|row| AuditEntry {
+                user_id: UserId::new(from_db_id(row.get(0))),
+                command: row.get(1),
+                details: row.get(2),
+                affected: row.get(3),
+                happened_at: row.get(4),
+            })
+            .collect())
+    }
+
+    /// Get a user's reminders due between `from` and `to` that could share a digest with
+    /// `reminder`, leaving out any that have been given up on. A heads-up is never paired
+    /// with the reminder it's for, as it would arrive at the same time and be pointless.
+    /// Results are sorted by when they are due.
+    async fn due_between(
+        &self,
+        reminder: &Reminder,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<Reminder>, Error> {
+        let rows = self
+            .client
+            .query(
+                &self.due_between,
+                &[
+                    &to_db_id(reminder.user_id),
+                    &from,
+                    &to,
+                    &self.max_delivery_attempts,
+                    &reminder.id,
+                ],
+            )
+            .await?;
+        Ok(rows.iter().map(Reminder::from_row).collect())
+    }
+
+    /// Let reminders' own tasks deliver them again, once a digest is done with them
+    async fn release_claims(&self, ids: &[i64]) {
+        let mut claims = self.digest_claims.lock().await;
+        for id in ids {
+            claims.remove(id);
+        }
+    }
+
+    /// Get all reminders in the database that haven't been given up on.
+    /// Because we purge all past reminders this should just include future reminders.
+    /// However this is not guaranteed.
+    async fn get_reminders(&self) -> Result<Vec<Row>, Error> {
+        let rows = self
+            .client
+            .query(&self.select, &[&self.max_delivery_attempts])
+            .await?;
+        Ok(rows)
+    }
+}
+
+/// Get the reminder database, or an error explaining that reminders are
+/// disabled if the bot started without one
+fn reminder_database(ctx: Context<'_>) -> Result<Arc<ReminderDatabase>, Error> {
+    ctx.data()
+        .database
+        .clone()
+        .ok_or_else(|| "Reminders are disabled because the bot has no database".into())
+}
+
+/// Record the command being run in the audit log, see [`ReminderDatabase::record_audit`]
+async fn audit(ctx: Context<'_>, database: &ReminderDatabase, details: &str, affected: u64) {
+    database
+        .record_audit(
+            ctx.author().id,
+            &ctx.command().qualified_name,
+            details,
+            affected,
+        )
+        .await;
+}
+
+/// Check a user is someone we can deliver reminders to.
+/// Bots and Discord's system account can't be sent DMs, so their reminders would
+/// only ever fail, filling the database with retries.
+fn check_can_remind(user: &serenity::User) -> Result<(), Error> {
+    if user.bot || user.system {
+        return Err("Reminders can only be set by people, not bots or system accounts".into());
+    }
+    Ok(())
+}
+
+/// Tidy a category so "Work" and " work " file together.
+/// Blank categories are treated as no category.
+fn normalise_category(category: &str) -> Option<String> {
+    let category = category.trim().to_lowercase();
+    (!category.is_empty()).then_some(category)
+}
+
+/// The channel or thread a command was used in, if it was used in a server.
+/// Direct messages are left out because they're no use as a fallback for themselves.
+fn origin_channel(ctx: Context<'_>) -> Option<ChannelId> {
+    ctx.guild_id().map(|_| ctx.channel_id())
+}
+
+/// Helper enum for the available time periods
+#[derive(Debug, poise::ChoiceParameter)]
+enum TimeUnitChoice {
+    #[name = "seconds"]
+    Seconds,
+    #[name = "minutes"]
+    Minutes,
+    #[name = "hours"]
+    Hours,
+    #[name = "days"]
+    Days,
+    #[name = "weeks"]
+    Weeks,
+    /// Actually 28 days
+    #[name = "months"]
+    Months,
+}
+
+/// Boundaries a reminder's due time can be rounded up to
+#[derive(Debug, Clone, Copy, poise::ChoiceParameter)]
+enum RoundChoice {
+    #[name = "5 minutes"]
+    FiveMinutes,
+    #[name = "hour"]
+    Hour,
+}
+
+/// How insistently a reminder is delivered
+#[derive(Debug, Default, Clone, Copy, PartialEq, poise::ChoiceParameter)]
+pub(crate) enum PriorityChoice {
+    /// Delivered silently
+    #[name = "low"]
+    Low,
+    #[default]
+    #[name = "normal"]
+    Normal,
+    /// Delivered with a ping and a red embed
+    #[name = "high"]
+    High,
+    /// As for high, but sent again every [`NAG_INTERVAL`] until acknowledged,
+    /// up to [`MAX_DELIVERIES`] times
+    #[name = "critical"]
+    Critical,
+}
+
+impl PriorityChoice {
+    /// Convert to the number stored in the database
+    fn to_db(self) -> i16 {
+        match self {
+            PriorityChoice::Low => 0,
+            PriorityChoice::Normal => 1,
+            PriorityChoice::High => 2,
+            PriorityChoice::Critical => 3,
+        }
+    }
+
+    /// Convert from the number stored in the database.
+    /// Unknown values are treated as normal priority.
+    fn from_db(value: i16) -> Self {
+        match value {
+            0 => PriorityChoice::Low,
+            2 => PriorityChoice::High,
+            3 => PriorityChoice::Critical,
+            _ => PriorityChoice::Normal,
+        }
+    }
+}
+
+/// Parse a UTC offset like `+12`, `-05:30` or `UTC+13:45` into a [`FixedOffset`].
+/// Returns `None` if the input isn't a valid offset.
+fn parse_utc_offset(input: &str) -> Option<FixedOffset> {
+    let input = input.trim();
+    let input = input
+        .strip_prefix("UTC")
+        .or_else(|| input.strip_prefix("utc"))
+        .unwrap_or(input);
+    if input.is_empty() {
+        return FixedOffset::east_opt(0);
+    }
+
+    let (sign, rest) = if let Some(rest) = input.strip_prefix('+') {
+        (1, rest)
+    } else if let Some(rest) = input.strip_prefix('-') {
+        (-1, rest)
+    } else {
+        (1, input)
+    };
+    let (hours, minutes) = rest.split_once(':').unwrap_or((rest, "0"));
+    let hours: i32 = hours.parse().ok()?;
+    let minutes: i32 = minutes.parse().ok()?;
+
+    if !(0..=14).contains(&hours) || !(0..60).contains(&minutes) {
+        return None;
+    }
+    // Real world offsets run from UTC-12 to UTC+14
+    let seconds = sign * (hours * 3600 + minutes * 60);
+    if !(-12 * 3600..=14 * 3600).contains(&seconds) {
+        return None;
+    }
+    FixedOffset::east_opt(seconds)
+}
+
+/// Parse a time that says exactly when it is, wherever you are: a Discord timestamp tag
+/// like `<t:1706700000:R>`, unix seconds, or an RFC 3339 time with an offset.
+/// Returns `None` if the input isn't one of these.
+fn parse_absolute_time(input: &str) -> Option<DateTime<Utc>> {
+    let input = input.trim();
+    let seconds = match input.strip_prefix("<t:").and_then(|t| t.strip_suffix('>')) {
+        Some(tag) => match tag.split_once(':') {
+            // the style after the second colon doesn't change the time, but it must be a real one
+            Some((seconds, style))
+                if TIMESTAMP_STYLES
+                    .iter()
+                    .any(|(known, _)| style == known.to_string()) =>
+            {
+                seconds
+            }
+            Some(_) => return None,
+            None => tag,
+        },
+        None => input,
+    };
+    if let Ok(seconds) = seconds.parse() {
+        return DateTime::from_timestamp(seconds, 0);
+    }
+    DateTime::parse_from_rfc3339(input)
+        .ok()
+        .map(|time| time.to_utc())
+}
+
+/// Parse a date and time like `2024-01-31 14:30` as it reads on a clock at the given offset.
+/// Seconds are optional, and a date alone means midnight.
+/// Returns `None` if the input isn't a valid date and time.
+fn parse_local_time(input: &str, offset: FixedOffset) -> Option<DateTime<Utc>> {
+    const FORMATS: [&str; 4] = [
+        "%Y-%m-%d %H:%M",
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%dT%H:%M",
+        "%Y-%m-%dT%H:%M:%S",
+    ];
+
+    let input = input.trim();
+    let local = FORMATS
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(input, format).ok())
+        .or_else(|| {
+            NaiveDate::parse_from_str(input, "%Y-%m-%d")
+                .ok()
+                .map(|date| date.and_time(NaiveTime::MIN))
+        })?;
+    offset
+        .from_local_datetime(&local)
+        .single()
+        .map(|time| time.to_utc())
+}
+
+/// The bit for a day of the week in [`Schedule::weekdays`]
+fn weekday_bit(day: Weekday) -> u8 {
+    1 << day.num_days_from_monday()
+}
+
+/// Parse the days a reminder recurs on, like `tue, thu`, `Mondays and Fridays`,
+/// `weekdays`, `weekends` or `daily`, into a [`Schedule::weekdays`] mask
+fn parse_weekdays(input: &str) -> Result<u8, Error> {
+    const WEEKDAYS: u8 = 0b001_1111;
+    const WEEKENDS: u8 = 0b110_0000;
+
+    let mut mask = 0;
+    let words = input
+        .split([',', ' ', '&', '/'])
+        .map(str::trim)
+        .filter(|w| !w.is_empty() && !w.eq_ignore_ascii_case("and"));
+    for word in words {
+        let word = word.to_lowercase();
+        mask |= match word.as_str() {
+            "weekdays" => WEEKDAYS,
+            "weekends" => WEEKENDS,
+            "daily" | "everyday" => WEEKDAYS | WEEKENDS,
+            _ => {
+                // allow plurals like mondays
+                let day: Weekday = word
+                    .parse()
+                    .or_else(|_| word.trim_end_matches('s').parse())
+                    .map_err(|_| format!("`{word}` isn't a day of the week"))?;
+                weekday_bit(day)
+            }
+        };
+    }
+    if mask == 0 {
+        return Err("Give at least one day, e.g. `tue, thu` or `weekdays`".into());
+    }
+    Ok(mask)
+}
+
+/// Parse a time of day like `14:30`, `10am` or `2:30pm`
+fn parse_time_of_day(input: &str) -> Option<NaiveTime> {
+    let input = input.trim().to_lowercase();
+    let (clock, afternoon) = match (input.strip_suffix("am"), input.strip_suffix("pm")) {
+        (Some(clock), _) => (clock.trim(), Some(false)),
+        (_, Some(clock)) => (clock.trim(), Some(true)),
+        _ => (input.as_str(), None),
+    };
+    let (hour, minute) = clock.split_once(':').unwrap_or((clock, "0"));
+    let mut hour: u32 = hour.parse().ok()?;
+    let minute: u32 = minute.parse().ok()?;
+    if let Some(afternoon) = afternoon {
+        if !(1..=12).contains(&hour) {
+            return None;
+        }
+        hour = hour % 12 + if afternoon { 12 } else { 0 };
+    }
+    NaiveTime::from_hms_opt(hour, minute, 0)
+}
+
+/// The first time a schedule comes around after `after`, reading its time of day at `offset`.
+/// Returns `None` once the schedule has ended.
+fn next_occurrence(
+    schedule: &Schedule,
+    after: DateTime<Utc>,
+    offset: FixedOffset,
+) -> Option<DateTime<Utc>> {
+    let today = after.with_timezone(&offset).date_naive();
+    // a week and a day covers every weekday, even if today's time has passed
+    for date in today.iter_days().take(8) {
+        if schedule.until.is_some_and(|until| date > until) {
+            return None;
+        }
+        if schedule.weekdays & weekday_bit(date.weekday()) == 0 {
+            continue;
+        }
+        let due = offset
+            .from_local_datetime(&date.and_time(schedule.time))
+            .single()?
+            .to_utc();
+        if due > after {
+            return Some(due);
+        }
+    }
+    None
+}
+
+/// Describe a schedule, e.g. `every Tue, Thu at 10:00 until 2024-12-01`
+fn describe_schedule(schedule: &Schedule) -> String {
+    const DAYS: [Weekday; 7] = [
+        Weekday::Mon,
+        Weekday::Tue,
+        Weekday::Wed,
+        Weekday::Thu,
+        Weekday::Fri,
+        Weekday::Sat,
+        Weekday::Sun,
+    ];
+
+    let days: Vec<String> = DAYS
+        .into_iter()
+        .filter(|day| schedule.weekdays & weekday_bit(*day) != 0)
+        .map(|day| day.to_string())
+        .collect();
+    let days = if days.len() == 7 {
+        "day".to_string()
+    } else {
+        days.join(", ")
+    };
+    let mut description = format!("every {days} at {}", schedule.time.format("%H:%M"));
+    if let Some(until) = schedule.until {
+        description = format!("{description} until {until}");
+    }
+    description
+}
+
+/// Every style Discord can show a timestamp tag in, with what each looks like
+const TIMESTAMP_STYLES: [(char, &str); 7] = [
+    ('t', "short time"),
+    ('T', "long time"),
+    ('d', "short date"),
+    ('D', "long date"),
+    ('f', "short date and time"),
+    ('F', "long date and time"),
+    ('R', "relative"),
+];
+
+/// List a time as a tag in each of [`TIMESTAMP_STYLES`], with the tag
+/// in a code span to copy and then rendered to show what it looks like
+fn timestamp_styles(time: DateTime<Utc>) -> String {
+    let seconds = time.timestamp();
+    TIMESTAMP_STYLES
+        .iter()
+        .map(|(style, name)| format!("`<t:{seconds}:{style}>` {name}: <t:{seconds}:{style}>"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// What we say when a reminder would be due further out than we can represent
+const TOO_FAR: &str = "That's too far in the future to set a reminder for";
+
+/// Calculate when a reminder is due from the start time and duration.
+/// The quantity and unit of the duration are passed as seperate parameters.
+/// Fails rather than overflowing if the result is too far away to represent.
+fn calculate_wait(
+    start: serenity::Timestamp,
+    duration: i64,
+    unit: &TimeUnitChoice,
+) -> Result<DateTime<Utc>, Error> {
+    let start_time = start.to_utc();
+
+    // Add the wait duration to the start time
+    unit_duration(duration, unit)
+        .and_then(|wait| start_time.checked_add_signed(wait))
+        .ok_or_else(|| TOO_FAR.into())
+}
+
+/// Round a time up to the next boundary, measured in the given time zone so hours
+/// line up with the user's clock even in half hour zones.
+/// Times already on a boundary are left alone.
+fn round_up(time: DateTime<Utc>, round: RoundChoice, offset: FixedOffset) -> DateTime<Utc> {
+    let step = match round {
+        RoundChoice::FiveMinutes => 5 * 60,
+        RoundChoice::Hour => 60 * 60,
+    };
+    let past_boundary = (time.timestamp() + i64::from(offset.local_minus_utc())).rem_euclid(step);
+    if past_boundary == 0 && time.timestamp_subsec_nanos() == 0 {
+        return time;
+    }
+    let whole_seconds = time - Duration::nanoseconds(time.timestamp_subsec_nanos().into());
+    whole_seconds + Duration::seconds(step - past_boundary)
+}
+
+/// Number of seconds in one of a time unit
+fn unit_seconds(unit: &TimeUnitChoice) -> i64 {
+    match unit {
+        TimeUnitChoice::Seconds => 1,
+        TimeUnitChoice::Minutes => 60,
+        TimeUnitChoice::Hours => 60 * 60,
+        TimeUnitChoice::Days => 24 * 60 * 60,
+        TimeUnitChoice::Weeks => 7 * 24 * 60 * 60,
+        TimeUnitChoice::Months => 28 * 24 * 60 * 60,
+    }
+}
+
+/// Convert a quantity of some time unit into a [`Duration`].
+/// Returns `None` if it is too long for a [`Duration`] to hold.
+fn unit_duration(duration: i64, unit: &TimeUnitChoice) -> Option<Duration> {
+    duration
+        .checked_mul(unit_seconds(unit))
+        .and_then(Duration::try_seconds)
+}
+
+/// Every time unit, smallest first
+const TIME_UNITS: [TimeUnitChoice; 6] = [
+    TimeUnitChoice::Seconds,
+    TimeUnitChoice::Minutes,
+    TimeUnitChoice::Hours,
+    TimeUnitChoice::Days,
+    TimeUnitChoice::Weeks,
+    TimeUnitChoice::Months,
+];
+
+/// Express a quantity of one time unit in each of the others, like "504 hours".
+/// Amounts are rounded to two decimal places.
+fn convert_units(duration: i64, unit: &TimeUnitChoice) -> Vec<String> {
+    // Discord caps the quantity well below where this could saturate
+    let hundredths_of_seconds = duration.saturating_mul(unit_seconds(unit) * 100);
+    TIME_UNITS
+        .iter()
+        .filter(|other| other.name() != unit.name())
+        .map(|other| {
+            let per_unit = unit_seconds(other);
+            // work in hundredths to avoid floating point
+            let hundredths = (hundredths_of_seconds + per_unit / 2) / per_unit;
+            let (whole, fraction) = (hundredths / 100, hundredths % 100);
+            let amount = if fraction == 0 {
+                whole.to_string()
+            } else {
+                format!("{whole}.{fraction:02}")
+                    .trim_end_matches('0')
+                    .to_string()
+            };
+            format!("{amount} {}", other.name())
+        })
+        .collect()
+}
+
+/// Describe how far away a time is in words, like "in about 3 hours" or "about 2 days ago".
+/// Used where Discord's `<t:...:R>` tags won't be rendered, such as console logs.
+fn relative_time(now: DateTime<Utc>, then: DateTime<Utc>) -> String {
+    let delta = then - now;
+    let Some(amount) = rough_span(delta.num_seconds().unsigned_abs()) else {
+        return if delta < TimeDelta::zero() {
+            "a few seconds ago".to_string()
+        } else {
+            "in a few seconds".to_string()
+        };
+    };
+    if delta < TimeDelta::zero() {
+        format!("about {amount} ago")
+    } else {
+        format!("in about {amount}")
+    }
+}
+
+/// Round a number of seconds to a rough amount of time in words, like "3 hours".
+/// Returns `None` for less than 45 seconds, which is just "a few seconds".
+fn rough_span(seconds: u64) -> Option<String> {
+    let minutes = (seconds + 30) / 60;
+    let hours = (minutes + 30) / 60;
+    let days = (hours + 12) / 24;
+
+    let amount = match seconds {
+        0..45 => return None,
+        45..90 => "a minute".to_string(),
+        90..2_700 => format!("{minutes} minutes"),
+        2_700..5_400 => "an hour".to_string(),
+        5_400..79_200 => format!("{hours} hours"),
+        79_200..129_600 => "a day".to_string(),
+        129_600..2_246_400 => format!("{days} days"),
+        2_246_400..3_888_000 => "a month".to_string(),
+        3_888_000..27_648_000 => format!("{} months", (days + 15) / 30),
+        27_648_000..47_347_200 => "a year".to_string(),
+        _ => format!("{} years", (days + 182) / 365),
+    };
+    Some(amount)
+}
+
+/// Spell out a time as it reads on a clock in the given time zone,
+/// for places where Discord won't localise a `<t:...>` tag for us
+fn local_time(time: DateTime<Utc>, offset: FixedOffset) -> String {
+    format!(
+        "{} UTC{offset}",
+        time.with_timezone(&offset).format("%a %-d %b %Y, %-I:%M%P")
+    )
+}
+
+/// Build the message delivering a reminder.
+/// If we know the user's time zone the due time is also written out in it,
+/// so it still reads correctly where the timestamp tag isn't rendered.
+fn reminder_message(reminder: &Reminder, offset: Option<FixedOffset>) -> CreateMessage {
+    let mut scheduled_for = format!("<t:{}>", reminder.due_at.timestamp());
+    if let Some(offset) = offset {
+        scheduled_for = format!("{scheduled_for}\n{}", local_time(reminder.due_at, offset));
+    }
+    let mut embed = embeds::branded_embed()
+        .title("Reminder")
+        .description(reminder.message.clone())
+        .field("Scheduled For", scheduled_for, false)
+        .field(
+            "Delivery Accuracy",
+            format!(
+                "{} seconds late",
+                (Utc::now() - reminder.due_at).num_seconds()
+            ),
+            false,
+        );
+    if let Some(link) = &reminder.source_link {
+        embed = embed.field("About", format!("[Jump to message]({link})"), false);
+    }
+    let mut message = CreateMessage::default();
+    match reminder.priority {
+        PriorityChoice::Low => {
+            message = message.flags(MessageFlags::SUPPRESS_NOTIFICATIONS);
+            embed = embed.colour(Colour::LIGHT_GREY);
+        }
+        PriorityChoice::Normal => {}
+        PriorityChoice::High => {
+            message = message.content(format!("<@{}>", reminder.user_id));
+            embed = embed.title("Important Reminder").colour(Colour::RED);
+        }
+        PriorityChoice::Critical => {
+            message = message.content(format!("<@{}>", reminder.user_id));
+            embed = embed.title("Critical Reminder").colour(Colour::DARK_RED);
+            if will_repeat(reminder) {
+                embed = embed.footer(serenity::CreateEmbedFooter::new(format!(
+                    "This will be sent again in {} minutes unless you acknowledge it",
+                    NAG_INTERVAL.num_minutes()
+                )));
+                message = message.components(vec![serenity::CreateActionRow::Buttons(vec![
+                    serenity::CreateButton::new(format!("{ACK_PREFIX}{}", reminder.id))
+                        .style(serenity::ButtonStyle::Success)
+                        .label("I got it"),
+                ])]);
+            }
+        }
+    }
+    message.add_embed(embed)
+}
+
+/// How long a critical reminder waits to be acknowledged before it is sent again
+const NAG_INTERVAL: TimeDelta = TimeDelta::minutes(10);
+/// Most times a critical reminder is sent, counting the first
+const MAX_DELIVERIES: i16 = 4;
+/// Start of the custom id for the button acknowledging a critical reminder, followed by its id
+const ACK_PREFIX: &str = "ack-reminder-";
+
+/// Whether this delivery of a reminder won't be the last, because it is critical
+/// and hasn't been sent as many times as we're willing to send it
+fn will_repeat(reminder: &Reminder) -> bool {
+    reminder.priority == PriorityChoice::Critical && reminder.deliveries + 1 < MAX_DELIVERIES
+}
+
+/// Handle a press of the button on a critical reminder, which stops it being sent again.
+/// Only the person the reminder is for can acknowledge it.
+pub(crate) async fn acknowledge(
+    ctx: &serenity::Context,
+    database: Option<&ReminderDatabase>,
+    press: &serenity::ComponentInteraction,
+) -> Result<(), Error> {
+    let Some(id) = press
+        .data
+        .custom_id
+        .strip_prefix(ACK_PREFIX)
+        .and_then(|id| id.parse().ok())
+    else {
+        return Ok(());
+    };
+    let database = database.ok_or("Reminders are disabled because the bot has no database")?;
+
+    let response = match database.get_reminder(id).await? {
+        Some(reminder) if reminder.user_id != press.user.id => {
+            serenity::CreateInteractionResponse::Message(
+                serenity::CreateInteractionResponseMessage::new()
+                    .content("Only the person this reminder is for can acknowledge it")
+                    .ephemeral(true),
+            )
+        }
+        found => {
+            // a missing reminder was already acknowledged or has run out of repeats
+            if let Some(reminder) = found {
+                database.remove_reminder(reminder).await?;
+            }
+            serenity::CreateInteractionResponse::UpdateMessage(
+                serenity::CreateInteractionResponseMessage::new()
+                    .content("Acknowledged")
+                    .components(Vec::new()),
+            )
+        }
+    };
+    press.create_response(ctx, response).await?;
+    Ok(())
+}
+
+/// Deliver a reminder to the user.
+/// If they have a webhook set the reminder is posted there, and only sent
+/// on Discord as well if they asked for that. A failed post to a webhook that
+/// is used alongside Discord is only logged, so retrying doesn't send the DM twice.
+async fn send_reminder(
+    bot: Arc<serenity::Http>,
+    reminder: &Reminder,
+    offset: Option<FixedOffset>,
+    webhook: Option<&Webhook>,
+) -> Result<(), Error> {
+    let Some(webhook) = webhook else {
+        return send_on_discord(bot, reminder, offset).await;
+    };
+    let posted = webhook::post(&webhook.url, &webhook_payload(reminder)).await;
+    if !webhook.also_dm {
+        return posted.map_err(|e| format!("Posting to webhook failed: {e}").into());
+    }
+    if let Err(e) = posted {
+        println!("Unable to post reminder {} to webhook: {e:?}", reminder.id);
+    }
+    send_on_discord(bot, reminder, offset).await
+}
+
+/// The JSON posted to a webhook for a reminder.
+/// Ids are strings because they are too big for JavaScript numbers.
+fn webhook_payload(reminder: &Reminder) -> serde_json::Value {
+    serde_json::json!({
+        "id": reminder.id.to_string(),
+        "user_id": reminder.user_id.to_string(),
+        "due_at": reminder.due_at.to_rfc3339(),
+        "message": reminder.message,
+        "priority": reminder.priority.name(),
+        "category": reminder.category,
+        "source_link": reminder.source_link,
+    })
+}
+
+/// Deliver a reminder to a user in their direct messages.
+/// If Discord won't let us DM them and the reminder was made in a server, it is posted
+/// in the channel or thread it was made in instead. Other failures are returned to be retried.
+/// Reminders set to reply to a message try that first, and fall back to the above.
+async fn send_on_discord(
+    bot: Arc<serenity::Http>,
+    reminder: &Reminder,
+    offset: Option<FixedOffset>,
+) -> Result<(), Error> {
+    discord_auth::check_token()?;
+    if let Some((channel, message)) = reminder.reply_to {
+        match send_reply(&bot, channel, message, reminder, offset).await {
+            Ok(()) => return Ok(()),
+            Err(e) if discord_auth::is_auth_failure(&e) => return Err(e),
+            // most likely the message was deleted, which is what DMs are for
+            Err(e) => println!(
+                "Unable to reply with reminder {} to message {message}, sending it by DM instead: {e:?}",
+                reminder.id
+            ),
+        }
+    }
+    let dm_error = match send_direct_message(bot.clone(), reminder, offset).await {
+        Ok(()) => return Ok(()),
+        Err(e) => e,
+    };
+    // a channel is public, so only use it when a DM can't work. Anything else, like
+    // Discord having a bad moment or refusing the token, is left to be retried
+    if !dms_closed(&dm_error) {
+        return Err(dm_error);
+    }
+    let Some(channel) = reminder.channel_id else {
+        return Err(dm_error);
+    };
+
+    println!(
+        "Unable to DM reminder {}, posting in channel {channel} instead: {dm_error:?}",
+        reminder.id
+    );
+    send_to_channel(&bot, channel, reminder, offset)
+        .await
+        .map_err(|e| format!("{dm_error}, then posting in <#{channel}> failed: {e}").into())
+}
+
+/// Discord's error code for a user the bot can't DM, because they've closed their DMs,
+/// blocked the bot or no longer share a server with it
+const CANNOT_DM_USER: isize = 50007;
+
+/// Whether a DM failed because Discord won't let us message the user,
+/// which sending it again won't fix
+fn dms_closed(error: &Error) -> bool {
+    let Some(serenity::Error::Http(serenity::HttpError::UnsuccessfulRequest(response))) =
+        error.downcast_ref::<serenity::Error>()
+    else {
+        return false;
+    };
+    response.error.code == CANNOT_DM_USER
+}
+
+/// Send a reminder to the user's DMs
+async fn send_direct_message(
+    bot: Arc<serenity::Http>,
+    reminder: &Reminder,
+    offset: Option<FixedOffset>,
+) -> Result<(), Error> {
+    send_dm(bot, reminder.user_id, reminder_message(reminder, offset)).await
+}
+
+/// Send a message to a user's DMs
+async fn send_dm(
+    bot: Arc<serenity::Http>,
+    user_id: UserId,
+    message: CreateMessage,
+) -> Result<(), Error> {
+    let user = bot.get_user(user_id).await?;
+    let dm_channel = user.create_dm_channel(bot.clone()).await?;
+    dm_channel.send_message(bot, message).await?;
+    Ok(())
+}
+
+/// Check at startup that we can DM people by messaging `user` that the bot is online.
+/// Reminders are delivered by DM, so failing here means they will fall back to
+/// channels or not arrive at all. The result is logged rather than returned
+/// because the rest of the bot works fine either way.
+pub(crate) async fn dm_self_check(
+    bot: Arc<serenity::Http>,
+    user_id: UserId,
+    log_channel: LogChannel,
+) {
+    let message = CreateMessage::default().content("Athena is online and able to send DMs.");
+    match send_dm(bot.clone(), user_id, message).await {
+        Ok(()) => println!("DM self check passed, messaged user {user_id}"),
+        Err(e) => {
+            log_channel
+                .log(
+                    &bot,
+                    "DM self check failed",
+                    &format!("Unable to DM user {user_id}, reminders may not be delivered: {e}"),
+                )
+                .await;
+        }
+    }
+}
+
+/// Post a reminder as a reply to the message it is about, pinging the user.
+/// Fails if the message has been deleted or the bot can't post in its channel.
+async fn send_reply(
+    bot: &serenity::Http,
+    channel: ChannelId,
+    message: MessageId,
+    reminder: &Reminder,
+    offset: Option<FixedOffset>,
+) -> Result<(), Error> {
+    let reply = reminder_message(reminder, offset)
+        .content(format!("<@{}>", reminder.user_id))
+        .reference_message((channel, message));
+    channel.send_message(bot, reply).await?;
+    Ok(())
+}
+
+/// Post a reminder in a channel or thread, pinging the user.
+/// Archived threads are reopened so the reminder lands in the thread it was made in.
+async fn send_to_channel(
+    bot: &serenity::Http,
+    channel: ChannelId,
+    reminder: &Reminder,
+    offset: Option<FixedOffset>,
+) -> Result<(), Error> {
+    let message = || reminder_message(reminder, offset).content(format!("<@{}>", reminder.user_id));
+    let Err(e) = channel.send_message(bot, message()).await else {
+        return Ok(());
+    };
+
+    let archived = match channel.to_channel(bot).await {
+        Ok(serenity::Channel::Guild(channel)) => channel
+            .thread_metadata
+            .is_some_and(|metadata| metadata.archived),
+        _ => false,
+    };
+    if !archived {
+        return Err(e.into());
+    }
+    channel
+        .edit_thread(bot, serenity::EditThread::new().archived(false))
+        .await?;
+    channel.send_message(bot, message()).await?;
+    Ok(())
+}
+
+/// What came of adding a reminder
+enum Added {
+    New(Reminder),
+    /// The user made the same reminder moments ago, so this is its id rather than a new one
+    Duplicate(i64),
+}
+
+/// What happened when we tried to deliver a reminder
+enum DeliveryOutcome {
+    Delivered,
+    /// Sent, but it is critical so it will be sent again unless acknowledged.
+    /// Holds the reminder as it is now rescheduled.
+    Snoozed(Reminder),
+    /// Sending failed. The description says which reminder and why.
+    Failed {
+        description: String,
+        /// Set once the reminder has failed too many times to be retried
+        gave_up: bool,
+    },
+}
+
+/// Send a reminder to the user.
+/// If successful, remove it from the database.
+/// If not, count the failure and leave the reminder in the database
+/// so it can be retired later. Once a reminder has failed too many times
+/// it is skipped on future startups until it is purged.
+/// Reporting a failed send is left to the caller.
+async fn deliver_reminder(
+    database: &ReminderDatabase,
+    bot: Arc<serenity::Http>,
+    log_channel: LogChannel,
+    reminder: Reminder,
+) -> DeliveryOutcome {
+    match database.claim_for_sending(&[reminder.id]).await {
+        Ok(claimed) if claimed.is_empty() => {
+            // removed, or already going out some other way, so there's nothing left to do
+            return DeliveryOutcome::Delivered;
+        }
+        Ok(_) => {}
+        // without the mark a crash could send it twice, so don't send it yet
+        Err(e) => {
+            return DeliveryOutcome::Failed {
+                description: format!(
+                    "Reminder {} for <@{}>: unable to mark it as being sent: {e:?}",
+                    reminder.id, reminder.user_id
+                ),
+                gave_up: false,
+            }
+        }
+    }
+    deliver_claimed(database, bot, log_channel, reminder).await
+}
+
+/// The body of [`deliver_reminder`], for a reminder already marked as being sent
+async fn deliver_claimed(
+    database: &ReminderDatabase,
+    bot: Arc<serenity::Http>,
+    log_channel: LogChannel,
+    reminder: Reminder,
+) -> DeliveryOutcome {
+    // the time zone is a nicety, so don't let a failed lookup hold up delivery
+    let offset = database
+        .get_timezone(reminder.user_id)
+        .await
+        .unwrap_or_else(|e| {
+            println!(
+                "Unable to look up time zone for reminder {}: {e:?}",
+                reminder.id
+            );
+            None
+        });
+    // without the webhook we can still try Discord, which beats not sending anything
+    let webhook = database
+        .get_webhook(reminder.user_id)
+        .await
+        .unwrap_or_else(|e| {
+            println!(
+                "Unable to look up webhook for reminder {}: {e:?}",
+                reminder.id
+            );
+            None
+        });
+    if let Err(e) = send_reminder(bot.clone(), &reminder, offset, webhook.as_ref()).await {
+        let description = format!(
+            "Reminder {} for <@{}>, due {}: {e:?}",
+            reminder.id,
+            reminder.user_id,
+            relative_time(Utc::now(), reminder.due_at)
+        );
+        // that's our problem rather than the reminder's, so it stays queued for a restart
+        if discord_auth::note_failure(&e) {
+            database.release_sending(reminder.id).await;
+            return DeliveryOutcome::Failed {
+                description,
+                gave_up: false,
+            };
+        }
+        let gave_up = match database.record_failure(reminder.id).await {
+            Ok(gave_up) => gave_up,
+            Err(e) => {
+                println!(
+                    "Unable to record failure for reminder {}: {e:?}",
+                    reminder.id
+                );
+                false
+            }
+        };
+        return DeliveryOutcome::Failed {
+            description,
+            gave_up,
+        };
+    }
+    finish_delivery(database, &bot, log_channel, reminder).await
+}
+
+/// Tidy up after a reminder has been sent: count it, and either reschedule it
+/// if it is critical and will be sent again, or remove it from the database
+async fn finish_delivery(
+    database: &ReminderDatabase,
+    bot: &serenity::Http,
+    log_channel: LogChannel,
+    reminder: Reminder,
+) -> DeliveryOutcome {
+    // repeats of a critical reminder are still the one reminder
+    if reminder.deliveries == 0 {
+        database.record_delivered(reminder.user_id).await;
+        database.record_receipt(&reminder, Utc::now()).await;
+    }
+    let id = reminder.id;
+    if let Some(next) = next_on_schedule(database, &reminder).await {
+        return DeliveryOutcome::Snoozed(next);
+    }
+    if will_repeat(&reminder) {
+        match database
+            .snooze_reminder(id, Utc::now() + NAG_INTERVAL)
+            .await
+        {
+            Ok(Some(next)) => return DeliveryOutcome::Snoozed(next),
+            // acknowledged already, so there's nothing left to do
+            Ok(None) => return DeliveryOutcome::Delivered,
+            // fall through to removing it, sending it once is better than never stopping
+            Err(e) => println!("Unable to snooze reminder {id}: {e:?}"),
+        }
+    }
+    if let Err(e) = database.remove_reminder(reminder).await {
+        let description = format!("Reminder {id}: {e:?}");
+        log_channel
+            .log(bot, "Unable to remove reminder", &description)
+            .await;
+        // it was delivered, so earlier failures shouldn't count against a retry
+        if let Err(e) = database.reset_failures(id).await {
+            println!("Unable to reset failures for reminder {id}: {e:?}");
+        }
+    }
+    DeliveryOutcome::Delivered
+}
+
+/// Move a recurring reminder on to its next occurrence.
+/// Returns `None` if it doesn't recur or its schedule has ended, so it should be removed as usual.
+async fn next_on_schedule(database: &ReminderDatabase, reminder: &Reminder) -> Option<Reminder> {
+    let id = reminder.id;
+    let schedule = match database.get_schedule(id).await {
+        Ok(schedule) => schedule?,
+        // like a failed snooze, it's removed, sending it once is better than never stopping
+        Err(e) => {
+            println!("Unable to get the schedule for reminder {id}: {e:?}");
+            return None;
+        }
+    };
+    // users update their offset by hand when the clocks change, so use whatever it is now
+    let offset = database
+        .get_timezone(reminder.user_id)
+        .await
+        .ok()
+        .flatten()
+        .unwrap_or(Utc.fix());
+    let next = next_occurrence(&schedule, Utc::now().max(reminder.due_at), offset)?;
+    match database.recur_reminder(id, next).await {
+        Ok(next) => next,
+        Err(e) => {
+            println!("Unable to move reminder {id} on to its next occurrence: {e:?}");
+            None
+        }
+    }
+}
+
+/// Deliver a single reminder, logging it if that fails.
+/// Returns the reminder if it needs to be sent again later.
+async fn send_and_remove_reminder(
+    database: Arc<ReminderDatabase>,
+    bot: Arc<serenity::Http>,
+    log_channel: LogChannel,
+    reminder: Reminder,
+) -> Option<Reminder> {
+    let outcome = deliver_reminder(&database, bot.clone(), log_channel, reminder).await;
+    log_outcome(&bot, log_channel, outcome).await
+}
+
+/// Log a reminder that failed to deliver, and whether we're giving up on it.
+/// Returns the reminder if it needs to be sent again later.
+async fn log_outcome(
+    bot: &serenity::Http,
+    log_channel: LogChannel,
+    outcome: DeliveryOutcome,
+) -> Option<Reminder> {
+    if let DeliveryOutcome::Snoozed(next) = outcome {
+        return Some(next);
+    }
+    if let DeliveryOutcome::Failed {
+        description,
+        gave_up,
+    } = outcome
+    {
+        log_channel
+            .log(bot, "Unable to send reminder", &description)
+            .await;
+        if gave_up {
+            log_channel
+                .log(
+                    bot,
+                    "Giving up on reminder",
+                    &format!("{description}\nIt failed too many times and won't be retried"),
+                )
+                .await;
+        }
+    }
+    None
+}
+
+/// Deliver a batch of reminders at once and log a single summary of how it went,
+/// rather than a message for every failure
+async fn deliver_batch(
+    database: Arc<ReminderDatabase>,
+    bot: Arc<serenity::Http>,
+    log_channel: LogChannel,
+    reminders: Vec<Reminder>,
+) {
+    let total = reminders.len();
+    // none of these have tasks of their own, but keep newer reminders' digests off them
+    let ids: Vec<i64> = reminders.iter().map(|r| r.id).collect();
+    database.digest_claims.lock().await.extend(&ids);
+    let (digests, singles) = group_digests(reminders);
+    let (single_outcomes, digest_outcomes) = future::join(
+        future::join_all(
+            singles
+                .into_iter()
+                .map(|reminder| deliver_reminder(&database, bot.clone(), log_channel, reminder)),
+        ),
+        future::join_all(
+            digests
+                .into_iter()
+                .map(|digest| deliver_digest(&database, bot.clone(), log_channel, digest)),
+        ),
+    )
+    .await;
+    database.release_claims(&ids).await;
+    let outcomes = single_outcomes
+        .into_iter()
+        .chain(digest_outcomes.into_iter().flatten());
+
+    let mut failures = Vec::new();
+    for outcome in outcomes {
+        match outcome {
+            DeliveryOutcome::Delivered => (),
+            DeliveryOutcome::Snoozed(next) => {
+                tokio::spawn(sleeping_reminder(
+                    database.clone(),
+                    bot.clone(),
+                    log_channel,
+                    next,
+                ));
+            }
+            DeliveryOutcome::Failed {
+                description,
+                gave_up: false,
+            } => failures.push(format!("- {description}")),
+            DeliveryOutcome::Failed {
+                description,
+                gave_up: true,
+            } => failures.push(format!("- {description} (giving up)")),
+        }
+    }
+
+    let mut summary = format!(
+        "Delivered {} of {total} overdue reminders",
+        total - failures.len()
+    );
+    if failures.is_empty() {
+        println!("{summary}");
+        return;
+    }
+    summary = format!(
+        "{summary}, {} failed:\n{}",
+        failures.len(),
+        failures.join("\n")
+    );
+    log_channel
+        .log(&bot, "Some overdue reminders failed", &summary)
+        .await;
+}
+
+/// Reminders for the same person that come due within this long of each other
+/// are sent together in one DM, rather than flooding their DMs
+const DIGEST_WINDOW: TimeDelta = TimeDelta::minutes(1);
+/// Most reminders sent in one digest, so it fits in one embed
+const MAX_DIGEST: usize = 10;
+/// Longest a reminder's message is shown in a digest
+const MAX_DIGEST_ENTRY: usize = 300;
+
+/// Whether a reminder can be sent as part of a digest. Replies have to be sent on their own,
+/// and critical reminders should stand out and can be sent again, so they go alone too.
+fn digestible(reminder: &Reminder) -> bool {
+    reminder.reply_to.is_none() && reminder.priority != PriorityChoice::Critical
+}
+
+/// Split reminders into digests, one for each person with more than one [`digestible`]
+/// reminder (in groups of at most [`MAX_DIGEST`]), and the reminders to send on their own.
+/// Digests are sorted by who they're for and hold reminders in the order given.
+fn group_digests(reminders: Vec<Reminder>) -> (Vec<Vec<Reminder>>, Vec<Reminder>) {
+    let (digestible, mut singles): (Vec<_>, Vec<_>) = reminders.into_iter().partition(digestible);
+    let mut by_user: BTreeMap<UserId, Vec<Reminder>> = BTreeMap::new();
+    for reminder in digestible {
+        by_user.entry(reminder.user_id).or_default().push(reminder);
+    }
+
+    let mut digests = Vec::new();
+    for reminders in by_user.into_values() {
+        let mut reminders = reminders.into_iter().peekable();
+        while reminders.peek().is_some() {
+            let mut group: Vec<Reminder> = reminders.by_ref().take(MAX_DIGEST).collect();
+            if group.len() == 1 {
+                singles.append(&mut group);
+            } else {
+                digests.push(group);
+            }
+        }
+    }
+    (digests, singles)
+}
+
+/// List the reminders in a digest, one line each with when it was due
+fn digest_description(reminders: &[Reminder]) -> String {
+    reminders
+        .iter()
+        .map(|reminder| {
+            let mut line = format!(
+                "- <t:{}:t> {}",
+                reminder.due_at.timestamp(),
+                embeds::truncate(reminder.message.clone(), MAX_DIGEST_ENTRY)
+            );
+            if reminder.priority == PriorityChoice::High {
+                line = format!("{line} **(important)**");
+            }
+            if let Some(link) = &reminder.source_link {
+                line = format!("{line} ([jump to message]({link}))");
+            }
+            line
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Build the DM sending several reminders at once.
+/// It pings if any of them are high priority, and is silent if they're all low priority.
+fn digest_message(reminders: &[Reminder]) -> CreateMessage {
+    let mut embed = embeds::branded_embed()
+        .title(format!("{} Reminders", reminders.len()))
+        .description(digest_description(reminders));
+    let mut message = CreateMessage::default();
+    if reminders.iter().any(|r| r.priority == PriorityChoice::High) {
+        message = message.content(format!("<@{}>", reminders[0].user_id));
+        embed = embed.colour(Colour::RED);
+    } else if reminders.iter().all(|r| r.priority == PriorityChoice::Low) {
+        message = message.flags(MessageFlags::SUPPRESS_NOTIFICATIONS);
+        embed = embed.colour(Colour::LIGHT_GREY);
+    }
+    message.add_embed(embed)
+}
+
+/// Send several of one person's reminders together in a DM.
+/// If that can't be done, or they have a webhook that should get each reminder,
+/// the reminders are delivered one by one as usual. Returns how each reminder went.
+async fn deliver_digest(
+    database: &ReminderDatabase,
+    bot: Arc<serenity::Http>,
+    log_channel: LogChannel,
+    reminders: Vec<Reminder>,
+) -> Vec<DeliveryOutcome> {
+    let ids: Vec<i64> = reminders.iter().map(|r| r.id).collect();
+    let claimed =
+        match database.claim_for_sending(&ids).await {
+            Ok(claimed) => claimed,
+            // each will try to mark itself, and fail the same way if the database is down
+            Err(e) => {
+                println!("Unable to mark reminders {ids:?} as being sent: {e:?}");
+                return future::join_all(reminders.into_iter().map(|reminder| {
+                    deliver_reminder(database, bot.clone(), log_channel, reminder)
+                }))
+                .await;
+            }
+        };
+    // ones that weren't marked have been removed, or are going out some other way
+    let reminders: Vec<Reminder> = reminders
+        .into_iter()
+        .filter(|r| claimed.contains(&r.id))
+        .collect();
+    if reminders.len() < 2 {
+        return future::join_all(
+            reminders
+                .into_iter()
+                .map(|reminder| deliver_claimed(database, bot.clone(), log_channel, reminder)),
+        )
+        .await;
+    }
+
+    let user_id = reminders[0].user_id;
+    let sent = match database.get_webhook(user_id).await {
+        Ok(None) => match discord_auth::check_token() {
+            Ok(()) => send_dm(bot.clone(), user_id, digest_message(&reminders)).await,
+            Err(e) => Err(e),
+        },
+        Ok(Some(_)) => Err("they have a webhook".into()),
+        Err(e) => Err(e),
+    };
+    if let Err(e) = sent {
+        println!(
+            "Unable to send {} reminders to {user_id} together, sending them one by one: {e:?}",
+            reminders.len()
+        );
+        return future::join_all(
+            reminders
+                .into_iter()
+                .map(|reminder| deliver_claimed(database, bot.clone(), log_channel, reminder)),
+        )
+        .await;
+    }
+
+    let mut outcomes = Vec::new();
+    for reminder in reminders {
+        outcomes.push(finish_delivery(database, &bot, log_channel, reminder).await);
+    }
+    outcomes
+}
+
+/// What a reminder's task should do about digests once the reminder is due
+enum Gathered {
+    /// Another reminder's digest is sending this one, so leave it be
+    Claimed,
+    /// Nothing else is due nearby, so send the reminder on its own
+    Alone,
+    /// Send these reminders, including this one, together
+    Digest(Vec<Reminder>),
+}
+
+/// Look for other reminders for the same person due within [`DIGEST_WINDOW`] of this one,
+/// claiming them for a digest if there are any. Reminders due shortly after now
+/// are sent with the digest a little early, rather than in a DM of their own just after it.
+async fn gather_digest(database: &ReminderDatabase, reminder: &Reminder) -> Gathered {
+    if !digestible(reminder) {
+        return Gathered::Alone;
+    }
+    // the claims are shared by every reminder task, so only hold them briefly and never
+    // across the query. Claiming this one first keeps other digests off it meanwhile.
+    if !database.digest_claims.lock().await.insert(reminder.id) {
+        return Gathered::Claimed;
+    }
+    let nearby = match database
+        .due_between(
+            reminder,
+            reminder.due_at - DIGEST_WINDOW,
+            Utc::now() + DIGEST_WINDOW,
+        )
+        .await
+    {
+        Ok(nearby) => nearby,
+        // digests are a nicety, sending the reminder alone still works
+        Err(e) => {
+            println!(
+                "Unable to look for reminders to send with reminder {}: {e:?}",
+                reminder.id
+            );
+            database.release_claims(&[reminder.id]).await;
+            return Gathered::Alone;
+        }
+    };
+    let mut claims = database.digest_claims.lock().await;
+    let others: Vec<Reminder> = nearby
+        .into_iter()
+        .filter(|r| digestible(r) && claims.insert(r.id))
+        .take(MAX_DIGEST - 1)
+        .collect();
+    if others.is_empty() {
+        claims.remove(&reminder.id);
+        return Gathered::Alone;
+    }
+    Gathered::Digest(std::iter::once(reminder.clone()).chain(others).collect())
+}
+
+/// Longest a reminder task sleeps in one go before checking on its reminder again
+const MAX_SLEEP: TimeDelta = TimeDelta::hours(1);
+
+/// Sleep until a reminder is due, then deliver it and remove it from the database.
+/// Sleeping happens in chunks of at most [`MAX_SLEEP`]. After each chunk the reminder is
+/// reloaded from the database so the task notices if it was cancelled or rescheduled.
+// boxed so the task can spawn more of itself for reminders from a digest that come back around
+fn sleeping_reminder(
+    database: Arc<ReminderDatabase>,
+    bot: Arc<serenity::Http>,
+    log_channel: LogChannel,
+    reminder: Reminder,
+) -> future::BoxFuture<'static, ()> {
+    Box::pin(sleep_and_deliver(database, bot, log_channel, reminder))
+}
+
+/// The body of [`sleeping_reminder`]
+async fn sleep_and_deliver(
+    database: Arc<ReminderDatabase>,
+    bot: Arc<serenity::Http>,
+    log_channel: LogChannel,
+    mut reminder: Reminder,
+) {
+    loop {
+        if !wait_until_due(&database, &mut reminder).await {
+            return;
+        }
+        match gather_digest(&database, &reminder).await {
+            Gathered::Claimed => return,
+            Gathered::Alone => {}
+            Gathered::Digest(digest) => {
+                let ids: Vec<i64> = digest.iter().map(|r| r.id).collect();
+                let outcomes = deliver_digest(&database, bot.clone(), log_channel, digest).await;
+                database.release_claims(&ids).await;
+                // recurring reminders come back around, each with a task of its own
+                for outcome in outcomes {
+                    if let Some(next) = log_outcome(&bot, log_channel, outcome).await {
+                        tokio::spawn(sleeping_reminder(
+                            database.clone(),
+                            bot.clone(),
+                            log_channel,
+                            next,
+                        ));
+                    }
+                }
+                return;
+            }
+        }
+        // critical reminders come back around until they're acknowledged
+        match send_and_remove_reminder(database.clone(), bot.clone(), log_channel, reminder).await {
+            Some(next) => reminder = next,
+            None => return,
+        }
+    }
+}
+
+/// Sleep until a reminder is due, keeping it up to date with the database as we go.
+/// A paused reminder that comes due is held until it is resumed, then sent straight away.
+/// Returns false if the reminder was removed or can't be waited on, or if it was moved.
+/// Whatever moves a reminder starts a new task for it, so this one can bow out.
+async fn wait_until_due(database: &ReminderDatabase, reminder: &mut Reminder) -> bool {
+    let mut resumes = database.resumes.subscribe();
+    loop {
+        let delta = reminder.due_at - Utc::now();
+        if delta <= TimeDelta::zero() {
+            if !reminder.paused {
+                return true;
+            }
+            // check in now and then anyway, in case a resume was missed
+            let _ = tokio::time::timeout(MAX_SLEEP.to_std().unwrap_or_default(), resumes.changed())
+                .await;
+            // anything resumed from here on wakes us again
+            resumes.borrow_and_update();
+            match database.get_reminder(reminder.id).await {
+                Ok(Some(latest)) if latest.version != reminder.version => return false,
+                Ok(Some(latest)) => *reminder = latest,
+                Ok(None) => return false,
+                Err(e) => println!("Unable to refresh reminder {}: {e:?}", reminder.id),
+            }
+            continue;
+        }
+
+        let duration = match delta.min(MAX_SLEEP).to_std() {
+            Ok(v) => v,
+            Err(e) => {
+                println!(
+                    "Unable to calculate reminder instant for reminder {}, due {}: {e}",
+                    reminder.id,
+                    relative_time(Utc::now(), reminder.due_at)
+                );
+                return false;
+            }
+        };
+        tokio::time::sleep(duration).await;
+
+        // check the reminder still exists and hasn't been moved while we were asleep
+        resumes.borrow_and_update();
+        match database.get_reminder(reminder.id).await {
+            Ok(Some(latest)) if latest.version != reminder.version => return false,
+            Ok(Some(latest)) => *reminder = latest,
+            Ok(None) => return false,
+            // carry on with what we already know, we'll try again next time
+            Err(e) => println!("Unable to refresh reminder {}: {e:?}", reminder.id),
+        }
+    }
+}
+
+/// Split the reminders stored in the database into those that came due while we were
+/// offline and those still to come, each exactly once and in the order given.
+/// Paused reminders are still to come, however overdue, since they wait to be resumed.
+fn plan_restart(stored: Vec<Reminder>, now: DateTime<Utc>) -> (Vec<Reminder>, Vec<Reminder>) {
+    stored
+        .into_iter()
+        .partition(|reminder| reminder.due_at <= now && !reminder.paused)
+}
+
+/// Tidy up reminders that were being sent when the bot stopped, as if they had been delivered.
+/// They may or may not have gone out, and a reminder sent twice is worse than one lost
+/// in the rare crash between marking it and sending it. Recurring and critical reminders
+/// come back around as usual, everything else is removed.
+async fn finish_interrupted(
+    database: &ReminderDatabase,
+    bot: &serenity::Http,
+    log_channel: LogChannel,
+) -> Result<(), Error> {
+    let interrupted = database.get_interrupted().await?;
+    if interrupted.is_empty() {
+        return Ok(());
+    }
+    let ids: Vec<String> = interrupted.iter().map(|r| format!("#{}", r.id)).collect();
+    for reminder in interrupted {
+        finish_delivery(database, bot, log_channel, reminder).await;
+    }
+    log_channel
+        .log(
+            bot,
+            "Reminders cut off mid send",
+            &format!(
+                "{} were being sent when the bot stopped, so they won't be sent again",
+                ids.join(", ")
+            ),
+        )
+        .await;
+    Ok(())
+}
+
+/// For every active reminder spawn a task that will sleep until it is
+/// due then deliver it.
+///
+/// The database is the only record of what's scheduled. Tasks and digest claims hold
+/// nothing that isn't stored, and snoozes and removals are written before a task moves on,
+/// so after a crash or restart this rebuilds exactly the reminders that were pending.
+/// Reminders are marked before they're sent, so one sent just before a crash
+/// isn't sent again, see [`finish_interrupted`].
+pub(crate) async fn spawn_reminder_tasks(
+    database: Arc<ReminderDatabase>,
+    bot: Arc<serenity::Http>,
+    log_channel: LogChannel,
+) {
+    // first, so any that come back around are picked up below
+    if let Err(e) = finish_interrupted(&database, &bot, log_channel).await {
+        log_channel
+            .log(
+                &bot,
+                "Unable to tidy up reminders cut off mid send",
+                &format!("{e:?}"),
+            )
+            .await;
+    }
+    let rows = match database.get_reminders().await {
+        Ok(rows) => rows,
+        Err(e) => {
+            log_channel
+                .log(&bot, "Unable to get reminders", &format!("{e:?}"))
+                .await;
+            return;
+        }
+    };
+
+    // reminders that came due while we were offline all go out together
+    let (overdue, upcoming) =
+        plan_restart(rows.iter().map(Reminder::from_row).collect(), Utc::now());
+
+    for reminder in upcoming {
+        tokio::spawn(sleeping_reminder(
+            database.clone(),
+            bot.clone(),
+            log_channel,
+            reminder,
+        ));
+    }
+    if !overdue.is_empty() {
+        tokio::spawn(deliver_batch(database, bot, log_channel, overdue));
+    }
+}
+
+/// Create a reminder about something
+#[poise::command(
+    slash_command,
+    subcommands(
+        "remindin",
+        "remindat",
+        "remindevery",
+        "remindrelative",
+        "import",
+        "list",
+        "next",
+        "search",
+        "cancel",
+        "shift",
+        "snooze",
+        "pause",
+        "resume",
+        "timezone",
+        "convert",
+        "webhook",
+        "replies",
+        "stats"
+    )
+)]
+pub(crate) async fn remindme(ctx: Context<'_>) -> Result<(), Error> {
+    ctx.say("Please use a subcommand").await?;
+    Ok(())
+}
+
+/// Remind me at a time, e.g. a Discord timestamp like <t:1700000000:F>
+///
+/// Times without an offset are read in the timezone set with `/remindme timezone`, or UTC.
+#[poise::command(slash_command, rename = "at")]
+pub(crate) async fn remindat(
+    ctx: Context<'_>,
+    #[description = "A Discord timestamp, a time like 2024-01-31 14:30, or unix seconds"]
+    #[max_length = 100]
+    time: String,
+    #[description = "Reminder message"] message: String,
+    #[description = "low is silent, high pings you, critical repeats until acknowledged"] priority: Option<PriorityChoice>,
+    #[description = "Category to file the reminder under, e.g. work"]
+    #[max_length = 32]
+    category: Option<String>,
+    #[description = "Also remind me this many minutes before"]
+    #[min = 1]
+    #[max = 10080]
+    lead: Option<i64>,
+) -> Result<(), Error> {
+    check_can_remind(ctx.author())?;
+    let database = reminder_database(ctx)?;
+    let author = ctx.author().id;
+    let (end_time, _) = read_time(ctx, &time).await?;
+    if end_time <= Utc::now() {
+        return Err("That time has already passed".into());
+    }
+    let lead_at = lead.map(|minutes| end_time - Duration::minutes(minutes));
+    if lead_at.is_some_and(|at| at <= Utc::now()) {
+        return Err("That heads up would be in the past, try a shorter lead time".into());
+    }
+    let reminder = match database
+        .add_reminder(
+            author,
+            origin_channel(ctx),
+            end_time,
+            message,
+            priority.unwrap_or_default(),
+            category.as_deref().and_then(normalise_category),
+            None,
+            None,
+        )
+        .await?
+    {
+        Added::New(reminder) => reminder,
+        Added::Duplicate(id) => return confirm_duplicate(ctx, id).await,
+    };
+    let lead = match lead_at {
+        Some(at) => Some(database.add_lead(&reminder, at).await?),
+        None => None,
+    };
+
+    schedule_and_confirm(ctx, reminder, lead, None).await
+}
+
+/// Remind me on some days of the week at a time, e.g. every Tuesday and Thursday at 10am
+///
+/// Times are on your clock, in the timezone set with `/remindme timezone`, or UTC.
+/// Timezones are fixed offsets from UTC that don't follow daylight saving on their own.
+/// Each time is worked out with your timezone as it is then, so if you update it when
+/// the clocks change the reminder stays at the same time on your clock.
+#[poise::command(slash_command, rename = "every")]
+pub(crate) async fn remindevery(
+    ctx: Context<'_>,
+    #[description = "Days to be reminded on, e.g. tue, thu or weekdays"]
+    #[max_length = 100]
+    days: String,
+    #[description = "Time of day, e.g. 10am or 14:30"]
+    #[max_length = 20]
+    time: String,
+    #[description = "Reminder message"] message: String,
+    #[description = "Last day to be reminded, e.g. 2024-12-01"]
+    #[max_length = 20]
+    until: Option<String>,
+    #[description = "low is silent, high pings you"] priority: Option<PriorityChoice>,
+    #[description = "Category to file the reminder under, e.g. work"]
+    #[max_length = 32]
+    category: Option<String>,
+) -> Result<(), Error> {
+    check_can_remind(ctx.author())?;
+    // critical reminders are deleted when acknowledged, which would end the schedule
+    if priority == Some(PriorityChoice::Critical) {
+        return Err("Recurring reminders can't be critical".into());
+    }
+    let schedule = Schedule {
+        weekdays: parse_weekdays(&days)?,
+        time: parse_time_of_day(&time).ok_or(format!(
+            "I couldn't read `{time}` as a time of day, try something like `10am` or `14:30`"
+        ))?,
+        until: until
+            .map(|until| {
+                NaiveDate::parse_from_str(until.trim(), "%Y-%m-%d")
+                    .map_err(|_| format!("`{until}` isn't a date, use the form 2024-12-01"))
+            })
+            .transpose()?,
+    };
+
+    let database = reminder_database(ctx)?;
+    let author = ctx.author().id;
+    let offset = database.get_timezone(author).await?.unwrap_or(Utc.fix());
+    let first = next_occurrence(&schedule, Utc::now(), offset)
+        .ok_or("That schedule ends before it would first remind you")?;
+    let reminder = match database
+        .add_reminder(
+            author,
+            origin_channel(ctx),
+            first,
+            message,
+            priority.unwrap_or_default(),
+            category.as_deref().and_then(normalise_category),
+            None,
+            None,
+        )
+        .await?
+    {
+        Added::New(reminder) => reminder,
+        Added::Duplicate(id) => return confirm_duplicate(ctx, id).await,
+    };
+    // without its schedule it would only be sent once, which isn't what was asked for
+    if let Err(e) = database.add_schedule(reminder.id, &schedule).await {
+        database.remove_reminder(reminder).await?;
+        return Err(e);
+    }
+
+    schedule_and_confirm(ctx, reminder, None, Some(&schedule)).await
+}
+
+/// Remind me in...
+#[poise::command(slash_command, rename = "in")]
+// every argument is an option on the command
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn remindin(
+    ctx: Context<'_>,
+    #[description = "Time till reminder"]
+    #[min = 1]
+    #[max = 10000]
+    duration: i64,
+    #[description = "Time units"] unit: TimeUnitChoice,
+    #[description = "Reminder message"] message: String,
+    #[description = "low is silent, high pings you, critical repeats until acknowledged"] priority: Option<PriorityChoice>,
+    #[description = "Round the time up to the next boundary"] round_to: Option<RoundChoice>,
+    #[description = "Category to file the reminder under, e.g. work"]
+    #[max_length = 32]
+    category: Option<String>,
+    #[description = "Also remind me this many minutes before"]
+    #[min = 1]
+    #[max = 10080]
+    lead: Option<i64>,
+) -> Result<(), Error> {
+    check_can_remind(ctx.author())?;
+    // write the reminder to the database
+    let database = reminder_database(ctx)?;
+    let author = ctx.author().id;
+    let start_time = ctx.created_at();
+    let mut end_time = calculate_wait(start_time, duration, &unit)?;
+    if let Some(round) = round_to {
+        let offset = database.get_timezone(author).await?.unwrap_or(Utc.fix());
+        end_time = round_up(end_time, round, offset);
+    }
+    let lead_at = lead.map(|minutes| end_time - Duration::minutes(minutes));
+    if lead_at.is_some_and(|at| at <= Utc::now()) {
+        return Err("That heads up would be in the past, try a shorter lead time".into());
+    }
+    let reminder = match database
+        .add_reminder(
+            author,
+            origin_channel(ctx),
+            end_time,
+            message,
+            priority.unwrap_or_default(),
+            category.as_deref().and_then(normalise_category),
+            None,
+            None,
+        )
+        .await?
+    {
+        Added::New(reminder) => reminder,
+        Added::Duplicate(id) => return confirm_duplicate(ctx, id).await,
+    };
+    let lead = match lead_at {
+        Some(at) => Some(database.add_lead(&reminder, at).await?),
+        None => None,
+    };
+
+    schedule_and_confirm(ctx, reminder, lead, None).await
+}
+
+/// Whether a relative reminder comes before or after its anchor
+#[derive(Debug, poise::ChoiceParameter)]
+enum RelativeChoice {
+    #[name = "before"]
+    Before,
+    #[name = "after"]
+    After,
+}
+
+/// Remind me some time before or after another reminder
+///
+/// The new reminder's time is worked out when it is created and it isn't linked to the anchor
+/// afterwards. If the anchor is later delivered, cancelled or moved the new reminder is left
+/// where it is, so a "1 hour before" reminder still fires even if the anchor is gone.
+#[poise::command(slash_command, rename = "relative")]
+pub(crate) async fn remindrelative(
+    ctx: Context<'_>,
+    #[description = "ID of the reminder to schedule relative to"] anchor: i64,
+    #[description = "Time between the reminders"]
+    #[min = 1]
+    #[max = 10000]
+    duration: i64,
+    #[description = "Time units"] unit: TimeUnitChoice,
+    #[description = "Before or after the other reminder"] direction: RelativeChoice,
+    #[description = "Reminder message"] message: String,
+    #[description = "low is silent, high pings you, critical repeats until acknowledged"] priority: Option<PriorityChoice>,
+) -> Result<(), Error> {
+    check_can_remind(ctx.author())?;
+    // only let people anchor to their own reminders, and don't reveal whether other people's exist
+    let database = reminder_database(ctx)?;
+    let author = ctx.author().id;
+    let anchor = database
+        .get_reminder(anchor)
+        .await?
+        .filter(|r| r.user_id == author)
+        .ok_or(format!("You don't have a reminder with ID {anchor}"))?;
+
+    let offset = unit_duration(duration, &unit).ok_or(TOO_FAR)?;
+    let end_time = match direction {
+        RelativeChoice::Before => anchor.due_at.checked_sub_signed(offset),
+        RelativeChoice::After => anchor.due_at.checked_add_signed(offset),
+    }
+    .ok_or(TOO_FAR)?;
+    if end_time <= Utc::now() {
+        return Err("That would be in the past".into());
+    }
+
+    let reminder = match database
+        .add_reminder(
+            author,
+            origin_channel(ctx),
+            end_time,
+            message,
+            priority.unwrap_or_default(),
+            // it's probably about the same thing as the anchor
+            anchor.category,
+            None,
+            None,
+        )
+        .await?
+    {
+        Added::New(reminder) => reminder,
+        Added::Duplicate(id) => return confirm_duplicate(ctx, id).await,
+    };
+    schedule_and_confirm(ctx, reminder, None, None).await
+}
+
+/// How long someone has to pick when to be reminded about a message
+const MESSAGE_DELAY_WINDOW: std::time::Duration = std::time::Duration::from_mins(2);
+
+/// Delays offered when making a reminder from a message, as labels and minutes
+const MESSAGE_DELAYS: [(&str, i64); 6] = [
+    ("In 20 minutes", 20),
+    ("In 1 hour", 60),
+    ("In 2 hours", 2 * 60),
+    ("In 4 hours", 4 * 60),
+    ("Tomorrow", 24 * 60),
+    ("Next week", 7 * 24 * 60),
+];
+
+/// Longest excerpt of a message used as the text of a reminder about it
+const MAX_EXCERPT: usize = 200;
+
+/// Check a message link is a Discord jump URL, like `https://discord.com/channels/<guild>/<channel>/<message>`.
+/// Direct messages use `@me` in place of the guild.
+fn validate_message_link(link: &str) -> Result<(), Error> {
+    let invalid = || Error::from("That isn't a link to a Discord message");
+    let url = reqwest::Url::parse(link).map_err(|_| invalid())?;
+    if url.scheme() != "https" || url.host_str() != Some("discord.com") {
+        return Err(invalid());
+    }
+    let segments: Vec<&str> = url.path_segments().ok_or_else(invalid)?.collect();
+    let is_id = |s: &str| !s.is_empty() && s.chars().all(|c| c.is_ascii_digit());
+    match segments.as_slice() {
+        ["channels", guild, channel, message]
+            if (*guild == "@me" || is_id(guild)) && is_id(channel) && is_id(message) =>
+        {
+            Ok(())
+        }
+        _ => Err(invalid()),
+    }
+}
+
+/// The text of a reminder about a message: the start of the message, or a placeholder
+/// if it has no text of its own, like a message that is only an image.
+fn message_excerpt(message: &serenity::Message) -> String {
+    let content = message.content.trim();
+    if content.is_empty() {
+        return format!("A message from {}", message.author.name);
+    }
+    embeds::truncate(content.to_string(), MAX_EXCERPT)
+}
+
+/// Remind me about a message later
+#[poise::command(context_menu_command = "Remind me about this", ephemeral)]
+pub(crate) async fn remind_about_message(
+    ctx: Context<'_>,
+    #[description = "Message to be reminded about"] message: serenity::Message,
+) -> Result<(), Error> {
+    check_can_remind(ctx.author())?;
+    // the resolved message has no guild id, so `message.link()` would point into DMs
+    let source_link = message.id.link(message.channel_id, ctx.guild_id());
+    validate_message_link(&source_link)?;
+    let database = reminder_database(ctx)?;
+
+    let menu_id = format!("{}delay", ctx.id());
+    let options = MESSAGE_DELAYS
+        .iter()
+        .map(|(label, minutes)| serenity::CreateSelectMenuOption::new(*label, minutes.to_string()))
+        .collect();
+    let menu = serenity::CreateSelectMenu::new(
+        &menu_id,
+        serenity::CreateSelectMenuKind::String { options },
+    )
+    .placeholder("Remind me…");
+    ctx.send(
+        poise::CreateReply::default()
+            .content("When should I remind you about this message?")
+            .components(vec![serenity::CreateActionRow::SelectMenu(menu)]),
+    )
+    .await?;
+    // waiting on the menu doesn't count as the command hanging
+    ctx.data().watchdog.finish(ctx);
+
+    let filter_id = menu_id.clone();
+    let press = serenity::ComponentInteractionCollector::new(ctx)
+        .author_id(ctx.author().id)
+        .channel_id(ctx.channel_id())
+        .timeout(MESSAGE_DELAY_WINDOW)
+        .filter(move |press| press.data.custom_id == filter_id)
+        .await;
+    let Some(press) = press else {
+        return Ok(());
+    };
+    let minutes = match &press.data.kind {
+        serenity::ComponentInteractionDataKind::StringSelect { values } => values
+            .first()
+            .and_then(|v| v.parse::<i64>().ok())
+            .ok_or("That isn't one of the options")?,
+        _ => return Err("That isn't one of the options".into()),
+    };
+    press
+        .create_response(
+            ctx,
+            serenity::CreateInteractionResponse::UpdateMessage(
+                serenity::CreateInteractionResponseMessage::new()
+                    .content("Setting your reminder…")
+                    .components(Vec::new()),
+            ),
+        )
+        .await?;
+
+    let due_at = calculate_wait(
+        serenity::Timestamp::now(),
+        minutes,
+        &TimeUnitChoice::Minutes,
+    )?;
+    // replying only makes sense in a server, in DMs the reminder lands next to the message anyway.
+    // the message comes from the interaction's resolved data, which never has a guild id
+    let reply_to = if ctx.guild_id().is_some() && database.get_replies(ctx.author().id).await? {
+        Some((message.channel_id, message.id))
+    } else {
+        None
+    };
+    let reminder = match database
+        .add_reminder(
+            ctx.author().id,
+            origin_channel(ctx),
+            due_at,
+            message_excerpt(&message),
+            PriorityChoice::default(),
+            None,
+            Some(source_link),
+            reply_to,
+        )
+        .await?
+    {
+        Added::New(reminder) => reminder,
+        Added::Duplicate(id) => return confirm_duplicate(ctx, id).await,
+    };
+    schedule_and_confirm(ctx, reminder, None, None).await
+}
+
+/// Tell someone they already have the reminder they just tried to make.
+/// That's usually a retried submission rather than a mistake, so it isn't treated as an error.
+async fn confirm_duplicate(ctx: Context<'_>, id: i64) -> Result<(), Error> {
+    ctx.say(format!("You already have that reminder, its ID is {id}"))
+        .await?;
+    Ok(())
+}
+
+/// How long the undo button on a new reminder stays active
+const UNDO_WINDOW: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// Spawn a task to deliver a freshly created reminder, then tell the user when it is due.
+/// The confirmation has an undo button that deletes the reminder if pressed within [`UNDO_WINDOW`].
+/// A heads up for the reminder, if there is one, is scheduled alongside it and undone with it.
+async fn schedule_and_confirm(
+    ctx: Context<'_>,
+    reminder: Reminder,
+    lead: Option<Reminder>,
+    schedule: Option<&Schedule>,
+) -> Result<(), Error> {
+    let database = reminder_database(ctx)?;
+    let id = reminder.id;
+    let author = reminder.user_id;
+    let end_time = reminder.due_at;
+
+    // spawn a task to deliver the reminder
+    let task = tokio::spawn(sleeping_reminder(
+        database.clone(),
+        ctx.serenity_context().http.clone(),
+        ctx.data().log_channel,
+        reminder.clone(),
+    ));
+    let mut lead_note = lead.as_ref().map_or_else(String::new, |lead| {
+        format!("\nYou'll get a heads up <t:{}:R>", lead.due_at.timestamp())
+    });
+    if let Some(schedule) = schedule {
+        lead_note = format!("{lead_note}\nIt repeats {}", describe_schedule(schedule));
+    }
+    let lead_task = lead.map(|lead| {
+        tokio::spawn(sleeping_reminder(
+            database.clone(),
+            ctx.serenity_context().http.clone(),
+            ctx.data().log_channel,
+            lead,
+        ))
+    });
+
+    // tell the user that everything is hunky-dory
+    // discord localises the timestamp tag, but spell out their local time too if we know it
+    // (a failed lookup shouldn't stop us confirming the reminder that was created)
+    let timezone = database.get_timezone(author).await;
+    let local_note = match timezone {
+        Ok(Some(offset)) => format!(" (your local time: {})", local_time(end_time, offset)),
+        _ => String::new(),
+    };
+    let name = guild_string(ctx, GuildString::ReminderName).await;
+    let content = format!(
+        "{name} #{id} created for <t:{}>{local_note}{lead_note}",
+        end_time.timestamp()
+    );
+    let undo_id = format!("{}undo", ctx.id());
+    let undo_button = |disabled| {
+        vec![serenity::CreateActionRow::Buttons(vec![
+            serenity::CreateButton::new(&undo_id)
+                .style(serenity::ButtonStyle::Secondary)
+                .label("Undo")
+                .disabled(disabled),
+        ])]
+    };
+    let handle = ctx
+        .send(
+            poise::CreateReply::default()
+                .content(&content)
+                .components(undo_button(false)),
+        )
+        .await?;
+    // the reminder is made, waiting on the button doesn't count as taking too long
+    ctx.data().watchdog.finish(ctx);
+    if matches!(timezone, Ok(None)) {
+        offer_timezones(ctx, &database).await;
+    }
+
+    // give the user a short window to take it back
+    let filter_id = undo_id.clone();
+    let press = serenity::ComponentInteractionCollector::new(ctx)
+        .author_id(author)
+        .channel_id(ctx.channel_id())
+        .timeout(UNDO_WINDOW)
+        .filter(move |press| press.data.custom_id == filter_id)
+        .await;
+
+    let Some(press) = press else {
+        // too slow, leave the reminder be and grey out the button
+        handle
+            .edit(
+                ctx,
+                poise::CreateReply::default()
+                    .content(content)
+                    .components(undo_button(true)),
+            )
+            .await?;
+        return Ok(());
+    };
+
+    // stop the task first so it can't deliver the reminder while we're deleting it,
+    // the sleeping task's existence check would also catch the deletion eventually
+    // (the heads up is deleted along with the reminder)
+    task.abort();
+    if let Some(lead_task) = lead_task {
+        lead_task.abort();
+    }
+    database.remove_reminder(reminder).await?;
+    press
+        .create_response(
+            ctx,
+            serenity::CreateInteractionResponse::UpdateMessage(
+                serenity::CreateInteractionResponseMessage::new()
+                    .content(format!("{name} #{id} cancelled"))
+                    .components(Vec::new()),
+            ),
+        )
+        .await?;
+    Ok(())
+}
+
+/// Delete overdue reminders that keep failing to deliver
+#[poise::command(slash_command, owners_only, ephemeral, rename = "purge-expired")]
+pub(crate) async fn purge_expired(
+    ctx: Context<'_>,
+    #[description = "Only purge reminders overdue by at least this many days, defaults to 7"]
+    #[min = 0]
+    #[max = 10000]
+    days_overdue: Option<i64>,
+    #[description = "Only purge reminders that failed at least this many times, defaults to 3"]
+    #[min = 1]
+    min_failures: Option<i32>,
+) -> Result<(), Error> {
+    let days_overdue = days_overdue.unwrap_or(7);
+    let min_failures = min_failures.unwrap_or(3);
+    let due_before = Utc::now() - Duration::days(days_overdue);
+    let database = reminder_database(ctx)?;
+    let purged = database.purge_failed(due_before, min_failures).await?;
+    audit(
+        ctx,
+        &database,
+        &format!("Overdue by {days_overdue} days and failed {min_failures} times"),
+        purged,
+    )
+    .await;
+
+    println!("Purged {purged} undeliverable reminders");
+    ctx.say(format!("Purged {purged} undeliverable reminders"))
+        .await?;
+    Ok(())
+}
+
+/// How a reminder will be delivered, given the webhook its owner has set, if any
+fn delivery_route(reminder: &Reminder, webhook: Option<&Webhook>) -> String {
+    let discord = match (reminder.reply_to, reminder.channel_id) {
+        (Some(_), _) => "Reply to the message it is about, falling back to DM",
+        (None, Some(_)) => "DM, falling back to the channel it was made in",
+        (None, None) => "DM",
+    };
+    match webhook {
+        Some(webhook) if webhook.also_dm => format!("Webhook and {discord}"),
+        Some(_) => "Webhook".to_string(),
+        None => discord.to_string(),
+    }
+}
+
+/// Show everything stored about a reminder, whoever it belongs to
+///
+/// Reminders are private, so every use is logged.
+#[poise::command(slash_command, owners_only, ephemeral, rename = "reminder")]
+pub(crate) async fn inspect_reminder(
+    ctx: Context<'_>,
+    #[description = "ID of the reminder"] id: i64,
+) -> Result<(), Error> {
+    let database = reminder_database(ctx)?;
+    ctx.data()
+        .log_channel
+        .log(
+            ctx.http(),
+            "Reminder inspected",
+            &format!("<@{}> looked up reminder {id}", ctx.author().id),
+        )
+        .await;
+    let details = database
+        .inspect_reminder(id)
+        .await?
+        .ok_or_else(|| format!("There's no reminder with ID {id}"))?;
+    let webhook = database.get_webhook(details.reminder.user_id).await?;
+
+    let reminder = details.reminder;
+    let mut embed = embeds::branded_embed()
+        .title(format!("Reminder {id}"))
+        .description(embeds::truncate(
+            reminder.message.clone(),
+            embeds::MAX_DESCRIPTION,
+        ))
+        .field("User", format!("<@{}>", reminder.user_id), true)
+        .field("Due", format!("<t:{}>", reminder.due_at.timestamp()), true)
+        .field(
+            "Created",
+            format!("<t:{}>", details.created_at.timestamp()),
+            true,
+        )
+        .field("Priority", reminder.priority.name(), true)
+        .field("Failed attempts", details.failure_count.to_string(), true)
+        .field("Deliveries", reminder.deliveries.to_string(), true)
+        .field(
+            "Delivery",
+            delivery_route(&reminder, webhook.as_ref()),
+            false,
+        );
+    if let Some(category) = &reminder.category {
+        embed = embed.field("Category", category, true);
+    }
+    if let Some(channel) = reminder.channel_id {
+        embed = embed.field("Channel", format!("<#{channel}>"), true);
+    }
+    if let Some(link) = &reminder.source_link {
+        embed = embed.field("About", format!("[Jump to message]({link})"), true);
+    }
+    ctx.send(poise::CreateReply::default().embed(embed)).await?;
+    Ok(())
+}
+
+/// Show the most recent destructive reminder operations, like purges and shifts
+///
+/// The log says who did what to whose reminders, so every use is logged.
+#[poise::command(slash_command, owners_only, ephemeral, rename = "audit")]
+pub(crate) async fn audit_log(
+    ctx: Context<'_>,
+    #[description = "Number of entries to show, defaults to 10"]
+    #[min = 1]
+    #[max = 100]
+    count: Option<i64>,
+) -> Result<(), Error> {
+    let database = reminder_database(ctx)?;
+    let count = count.unwrap_or(10);
+    ctx.data()
+        .log_channel
+        .log(
+            ctx.http(),
+            "Audit log read",
+            &format!(
+                "<@{}> read the last {count} audit log entries",
+                ctx.author().id
+            ),
+        )
+        .await;
+    let entries = database.list_audit(count).await?;
+    if entries.is_empty() {
+        ctx.say("No destructive operations recorded").await?;
+        return Ok(());
+    }
+
+    let fields = entries
+        .into_iter()
+        .map(|e| {
+            (
+                format!("/{}", e.command),
+                format!(
+                    "<t:{}:R> by <@{}>, {} reminders affected\n{}",
+                    e.happened_at.timestamp(),
+                    e.user_id,
+                    e.affected,
+                    e.details
+                ),
+                false,
+            )
+        })
+        .collect();
+    let title = "Recent destructive reminder operations";
+    let template = embeds::branded_embed().title(title);
+    embeds::send_paginated(ctx, &template, title.len(), fields).await
+}
+
+/// Largest file accepted by `/remindme import`, in bytes
+const MAX_IMPORT_SIZE: u32 = 256 * 1024;
+
+/// Create reminders in bulk from a .json or .ics file
+///
+/// JSON files hold an array of reminders like
+/// `{"due_at": "2025-01-01T09:00:00+13:00", "message": "Happy new year", "priority": "high"}`.
+/// In iCalendar files every event becomes a reminder due when the event starts.
+#[poise::command(slash_command)]
+pub(crate) async fn import(
+    ctx: Context<'_>,
+    #[description = "A .json or .ics file of reminders"] file: serenity::Attachment,
+) -> Result<(), Error> {
+    check_can_remind(ctx.author())?;
+    if file.size > MAX_IMPORT_SIZE {
+        return Err(format!("Files must be under {} KiB", MAX_IMPORT_SIZE / 1024).into());
+    }
+    let database = reminder_database(ctx)?;
+    let author = ctx.author().id;
+    let contents =
+        String::from_utf8(file.download().await?).map_err(|_| "The file isn't valid UTF-8 text")?;
+    // times without an offset are in the user's time zone, if we know it
+    let offset = database
+        .get_timezone(author)
+        .await?
+        .unwrap_or(FixedOffset::east_opt(0).expect("zero is a valid offset"));
+
+    let (valid, invalid): (Vec<_>, Vec<_>) =
+        import::parse_file(&file.filename, &contents, offset, Utc::now())?
+            .into_iter()
+            .partition(Result::is_ok);
+    let valid: Vec<_> = valid.into_iter().filter_map(Result::ok).collect();
+    let invalid: Vec<_> = invalid.into_iter().filter_map(Result::err).collect();
+
+    let created = if valid.is_empty() {
+        Vec::new()
+    } else {
+        database
+            .add_reminders(author, origin_channel(ctx), valid)
+            .await?
+    };
+    let mut reply = format!("Imported {} reminders", created.len());
+    for reminder in created {
+        tokio::spawn(sleeping_reminder(
+            database.clone(),
+            ctx.serenity_context().http.clone(),
+            ctx.data().log_channel,
+            reminder,
+        ));
+    }
+
+    if !invalid.is_empty() {
+        reply = format!(
+            "{reply}, skipped {}:\n{}",
+            invalid.len(),
+            invalid.join("\n")
+        );
+    }
+    ctx.say(embeds::truncate(reply, embeds::MAX_MESSAGE_LENGTH))
+        .await?;
+    Ok(())
+}
+
+/// Show your next upcoming reminder
+#[poise::command(slash_command)]
+pub(crate) async fn next(ctx: Context<'_>) -> Result<(), Error> {
+    let Some(reminder) = reminder_database(ctx)?
+        .next_reminder(ctx.author().id)
+        .await?
+    else {
+        ctx.say("You have nothing scheduled").await?;
+        return Ok(());
+    };
+
+    let due = reminder.due_at.timestamp();
+    let embed = embeds::branded_embed()
+        .title(format!("Reminder #{}", reminder.id))
+        .description(reminder.message)
+        .field("Due", format!("<t:{due}> (<t:{due}:R>)"), false);
+    ctx.send(poise::CreateReply::default().embed(embed)).await?;
+    Ok(())
+}
+
+/// Summarise a reminder as an embed field, for lists of reminders
+fn reminder_field(reminder: &Reminder) -> embeds::Field {
+    let mut name = match &reminder.category {
+        Some(category) => format!("Reminder #{} [{category}]", reminder.id),
+        None => format!("Reminder #{}", reminder.id),
+    };
+    if reminder.paused {
+        name = format!("{name} (paused)");
+    }
+    let value = format!(
+        "Due <t:{}:R>\n{}",
+        reminder.due_at.timestamp(),
+        reminder.message
+    );
+    (name, value, false)
+}
+
+/// Summarise a delivered reminder as an embed field, for lists of reminders
+fn receipt_field(receipt: &Receipt) -> embeds::Field {
+    let value = format!(
+        "Due <t:{}:R>, delivered <t:{}:R>\n{}",
+        receipt.due_at.timestamp(),
+        receipt.delivered_at.timestamp(),
+        receipt.message
+    );
+    (format!("Delivered #{}", receipt.id), value, false)
+}
+
+/// List your reminders, optionally only those in one category
+///
+/// Reminders delivered in the last day can be shown too, with when they were actually sent.
+#[poise::command(slash_command)]
+pub(crate) async fn list(
+    ctx: Context<'_>,
+    #[description = "Only show reminders in this category"]
+    #[max_length = 32]
+    category: Option<String>,
+    #[description = "Also show reminders delivered in the last day"] delivered: Option<bool>,
+) -> Result<(), Error> {
+    let category = category.as_deref().and_then(normalise_category);
+    let database = reminder_database(ctx)?;
+    let reminders = database
+        .list_reminders(ctx.author().id, category.as_deref())
+        .await?;
+    let receipts = if delivered.unwrap_or(false) {
+        database
+            .list_receipts(ctx.author().id, category.as_deref())
+            .await?
+    } else {
+        Vec::new()
+    };
+    if reminders.is_empty() && receipts.is_empty() {
+        let reply = match &category {
+            Some(category) => format!("You have no reminders in \"{category}\""),
+            None => "You have no reminders".to_string(),
+        };
+        ctx.say(reply).await?;
+        return Ok(());
+    }
+
+    let fields = reminders
+        .iter()
+        .map(reminder_field)
+        .chain(receipts.iter().map(receipt_field))
+        .collect();
+    let title = match &category {
+        Some(category) => format!("Your reminders in \"{category}\""),
+        None => "Your reminders".to_string(),
+    };
+    let header_length = title.chars().count();
+    let template = embeds::branded_embed().title(title);
+    embeds::send_paginated(ctx, &template, header_length, fields).await
+}
+
+/// Find your reminders containing some text
+#[poise::command(slash_command)]
+pub(crate) async fn search(
+    ctx: Context<'_>,
+    #[description = "Text to search for"]
+    #[max_length = 100]
+    text: String,
+) -> Result<(), Error> {
+    let reminders = reminder_database(ctx)?
+        .search_reminders(ctx.author().id, &text)
+        .await?;
+    if reminders.is_empty() {
+        ctx.say(format!("No reminders found containing \"{text}\""))
+            .await?;
+        return Ok(());
+    }
+
+    let fields = reminders.iter().map(reminder_field).collect();
+    let title = format!("Reminders containing \"{text}\"");
+    let header_length = title.chars().count();
+    let template = embeds::branded_embed().title(title);
+    embeds::send_paginated(ctx, &template, header_length, fields).await
+}
+
+/// Most reminders offered when a cancel matches several, the most a select menu can hold
+const MAX_CANCEL_CHOICES: usize = 25;
+/// Longest a select menu option label can be
+const MAX_OPTION_LABEL: usize = 100;
+
+/// Read a reminder ID the way lists show them, with or without the leading `#`
+fn parse_reminder_id(text: &str) -> Option<i64> {
+    let text = text.trim();
+    text.strip_prefix('#').unwrap_or(text).parse().ok()
+}
+
+/// Label for a reminder in the menu asking which one to cancel
+fn cancel_option_label(reminder: &Reminder) -> String {
+    embeds::truncate(
+        format!("#{}: {}", reminder.id, reminder.message),
+        MAX_OPTION_LABEL,
+    )
+}
+
+/// Ask which of several matching reminders to cancel.
+/// Returns the chosen reminder and the press to answer, or `None` if nothing was picked in time.
+async fn choose_reminder_to_cancel(
+    ctx: Context<'_>,
+    text: &str,
+    mut matches: Vec<Reminder>,
+) -> Result<Option<(Reminder, serenity::ComponentInteraction)>, Error> {
+    let mut content = format!(
+        "{} reminders contain \"{text}\", which should I cancel? \
+        You can also run `/remindme cancel` again with its ID.",
+        matches.len()
+    );
+    if matches.len() > MAX_CANCEL_CHOICES {
+        content = format!("{content}\nOnly the first {MAX_CANCEL_CHOICES} are shown.");
+        matches.truncate(MAX_CANCEL_CHOICES);
+    }
+
+    let menu_id = format!("{}cancel", ctx.id());
+    let options = matches
+        .iter()
+        .map(|r| serenity::CreateSelectMenuOption::new(cancel_option_label(r), r.id.to_string()))
+        .collect();
+    let menu = serenity::CreateSelectMenu::new(
+        &menu_id,
+        serenity::CreateSelectMenuKind::String { options },
+    )
+    .placeholder("Cancel reminder…");
+    let handle = ctx
+        .send(
+            poise::CreateReply::default()
+                .content(&content)
+                .components(vec![serenity::CreateActionRow::SelectMenu(menu)]),
+        )
+        .await?;
+    // waiting on the menu doesn't count as the command hanging
+    ctx.data().watchdog.finish(ctx);
+
+    let filter_id = menu_id.clone();
+    let press = serenity::ComponentInteractionCollector::new(ctx)
+        .author_id(ctx.author().id)
+        .channel_id(ctx.channel_id())
+        .timeout(CONFIRM_WINDOW)
+        .filter(move |press| press.data.custom_id == filter_id)
+        .await;
+    let Some(press) = press else {
+        handle
+            .edit(
+                ctx,
+                poise::CreateReply::default()
+                    .content("No reminder picked, nothing was cancelled")
+                    .components(Vec::new()),
+            )
+            .await?;
+        return Ok(None);
+    };
+    let id = match &press.data.kind {
+        serenity::ComponentInteractionDataKind::StringSelect { values } => {
+            values.first().and_then(|v| v.parse::<i64>().ok())
+        }
+        _ => None,
+    };
+    let reminder = matches
+        .into_iter()
+        .find(|r| Some(r.id) == id)
+        .ok_or("That isn't one of the options")?;
+    Ok(Some((reminder, press)))
+}
+
+/// Cancel one of your reminders, by ID or by some text from its message
+///
+/// If the text matches several reminders you're asked to pick one.
+#[poise::command(slash_command)]
+pub(crate) async fn cancel(
+    ctx: Context<'_>,
+    #[description = "ID of the reminder, or some text from its message"]
+    #[max_length = 100]
+    reminder: String,
+) -> Result<(), Error> {
+    let database = reminder_database(ctx)?;
+    let author = ctx.author().id;
+
+    let (reminder, press) = if let Some(id) = parse_reminder_id(&reminder) {
+        // don't reveal whether other people's reminders exist
+        let reminder = database
+            .get_reminder(id)
+            .await?
+            .filter(|r| r.user_id == author)
+            .ok_or(format!("You don't have a reminder with ID {id}"))?;
+        (reminder, None)
+    } else {
+        let mut matches = database.search_reminders(author, &reminder).await?;
+        if matches.len() > 1 {
+            match choose_reminder_to_cancel(ctx, &reminder, matches).await? {
+                Some((reminder, press)) => (reminder, Some(press)),
+                None => return Ok(()),
+            }
+        } else {
+            let found = matches
+                .pop()
+                .ok_or(format!("No reminders found containing \"{reminder}\""))?;
+            (found, None)
+        }
+    };
+
+    // the sleeping task notices the reminder is gone and stops,
+    // and any heads up is deleted along with it
+    let id = reminder.id;
+    let details = format!("#{id}");
+    database.remove_reminder(reminder).await?;
+    audit(ctx, &database, &details, 1).await;
+    let name = guild_string(ctx, GuildString::ReminderName).await;
+    let content = format!("{name} #{id} cancelled");
+    match press {
+        Some(press) => {
+            press
+                .create_response(
+                    ctx,
+                    serenity::CreateInteractionResponse::UpdateMessage(
+                        serenity::CreateInteractionResponseMessage::new()
+                            .content(content)
+                            .components(Vec::new()),
+                    ),
+                )
+                .await?;
+        }
+        None => {
+            ctx.say(content).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Which way `/remindme shift` moves reminders
+#[derive(Debug, poise::ChoiceParameter)]
+enum ShiftChoice {
+    #[name = "later"]
+    Later,
+    #[name = "earlier"]
+    Earlier,
+}
+
+/// How long `/remindme shift` and `/remindme cancel` wait for an answer to their buttons or menu
+const CONFIRM_WINDOW: std::time::Duration = std::time::Duration::from_mins(1);
+
+/// Move all your upcoming reminders later or earlier
+///
+/// Asks for confirmation first because it changes every upcoming reminder at once.
+#[poise::command(slash_command)]
+pub(crate) async fn shift(
+    ctx: Context<'_>,
+    #[description = "How far to move them"]
+    #[min = 1]
+    #[max = 10000]
+    duration: i64,
+    #[description = "Time units"] unit: TimeUnitChoice,
+    #[description = "Later or earlier"] direction: ShiftChoice,
+) -> Result<(), Error> {
+    let database = reminder_database(ctx)?;
+    let author = ctx.author().id;
+    let now = Utc::now();
+    let upcoming: Vec<Reminder> = database
+        .list_reminders(author, None)
+        .await?
+        .into_iter()
+        .filter(|r| r.due_at > now)
+        .collect();
+    let Some(first) = upcoming.first() else {
+        ctx.say("You have no upcoming reminders to move").await?;
+        return Ok(());
+    };
+    let offset = unit_duration(duration, &unit).ok_or(TOO_FAR)?;
+    let offset = match direction {
+        ShiftChoice::Later => offset,
+        ShiftChoice::Earlier => -offset,
+    };
+    if first
+        .due_at
+        .checked_add_signed(offset)
+        .is_none_or(|due| due <= now)
+    {
+        return Err(format!("That would move reminder #{} into the past", first.id).into());
+    }
+
+    let confirm_id = format!("{}shift", ctx.id());
+    let cancel_id = format!("{}cancel", ctx.id());
+    let buttons = vec![serenity::CreateActionRow::Buttons(vec![
+        serenity::CreateButton::new(&confirm_id)
+            .style(serenity::ButtonStyle::Danger)
+            .label("Move them"),
+        serenity::CreateButton::new(&cancel_id)
+            .style(serenity::ButtonStyle::Secondary)
+            .label("Cancel"),
+    ])];
+    let handle = ctx
+        .send(
+            poise::CreateReply::default()
+                .content(format!(
+                    "Move all {} of your upcoming reminders {duration} {} {}?",
+                    upcoming.len(),
+                    unit.name(),
+                    direction.name()
+                ))
+                .components(buttons),
+        )
+        .await?;
+    // waiting on the user doesn't count as the command taking too long
+    ctx.data().watchdog.finish(ctx);
+
+    let ids = [confirm_id.clone(), cancel_id];
+    let press = serenity::ComponentInteractionCollector::new(ctx)
+        .author_id(author)
+        .channel_id(ctx.channel_id())
+        .timeout(CONFIRM_WINDOW)
+        .filter(move |press| ids.contains(&press.data.custom_id))
+        .await;
+    let Some(press) = press else {
+        handle
+            .edit(
+                ctx,
+                poise::CreateReply::default()
+                    .content("No answer, so nothing was moved")
+                    .components(Vec::new()),
+            )
+            .await?;
+        return Ok(());
+    };
+
+    let outcome = if press.data.custom_id == confirm_id {
+        let moved = database.shift_reminders(author, offset).await?;
+        audit(
+            ctx,
+            &database,
+            &format!("{duration} {} {}", unit.name(), direction.name()),
+            moved.len() as u64,
+        )
+        .await;
+        // the old tasks notice their reminders moved and stop, so start new ones
+        for reminder in &moved {
+            tokio::spawn(sleeping_reminder(
+                database.clone(),
+                ctx.serenity_context().http.clone(),
+                ctx.data().log_channel,
+                reminder.clone(),
+            ));
+        }
+        format!("Moved {} reminders", moved.len())
+    } else {
+        "Cancelled, nothing was moved".to_string()
+    };
+    press
+        .create_response(
+            ctx,
+            serenity::CreateInteractionResponse::UpdateMessage(
+                serenity::CreateInteractionResponseMessage::new()
+                    .content(outcome)
+                    .components(Vec::new()),
+            ),
+        )
+        .await?;
+    Ok(())
 }
 
-/// For every active reminder spawn a task that will sleep until it is
-/// due then deliver it
-pub(crate) async fn spawn_reminder_tasks(
-    database: Arc<ReminderDatabase>,
-    bot: Arc<serenity::Http>,
-) {
-    let Ok(rows) = database.get_reminders().await else {
-        println!("Unable to get reminders");
-        return;
+/// Push one of your reminders later, before it goes off
+#[poise::command(slash_command)]
+pub(crate) async fn snooze(
+    ctx: Context<'_>,
+    #[description = "ID of the reminder to push back"] id: i64,
+    #[description = "How much later it should go off"]
+    #[min = 1]
+    #[max = 10000]
+    duration: i64,
+    #[description = "Time units"] unit: TimeUnitChoice,
+) -> Result<(), Error> {
+    let database = reminder_database(ctx)?;
+    let author = ctx.author().id;
+    // don't reveal whether other people's reminders exist
+    let reminder = database
+        .get_reminder(id)
+        .await?
+        .filter(|r| r.user_id == author)
+        .ok_or(format!("You don't have a reminder with ID {id}"))?;
+    let due_at = calculate_wait(reminder.due_at.into(), duration, &unit)?;
+    let snoozed = database
+        .postpone_reminder(id, author, due_at)
+        .await?
+        .ok_or(format!("Reminder #{id} is already going off"))?;
+
+    // the old task notices the reminder moved and stops, so start a new one
+    tokio::spawn(sleeping_reminder(
+        database.clone(),
+        ctx.serenity_context().http.clone(),
+        ctx.data().log_channel,
+        snoozed,
+    ));
+
+    let local_note = match database.get_timezone(author).await {
+        Ok(Some(offset)) => format!(" (your local time: {})", local_time(due_at, offset)),
+        _ => String::new(),
     };
+    let due = due_at.timestamp();
+    ctx.say(format!(
+        "Reminder #{id} snoozed until <t:{due}> (<t:{due}:R>){local_note}"
+    ))
+    .await?;
+    Ok(())
+}
 
-    for ele in rows {
-        let reminder = Reminder::from_row(&ele);
-        tokio::spawn(sleeping_reminder(database.clone(), bot.clone(), reminder));
-    }
+/// Read the reminder given to `/remindme pause` or `resume`, where leaving it out means all of them
+fn parse_pause_target(reminder: Option<&str>) -> Result<Option<i64>, Error> {
+    reminder
+        .map(|text| {
+            parse_reminder_id(text).ok_or_else(|| format!("`{text}` isn't a reminder ID").into())
+        })
+        .transpose()
 }
 
-/// Create a reminder about something
-#[poise::command(slash_command, subcommands("remindin"))]
-pub(crate) async fn remindme(ctx: Context<'_>) -> Result<(), Error> {
-    ctx.say("Please use a subcommand").await?;
+/// Pause one of your reminders, or all of them, so they aren't sent until you resume them
+///
+/// Paused reminders are kept, and anything that comes due while paused is sent once you resume.
+#[poise::command(slash_command)]
+pub(crate) async fn pause(
+    ctx: Context<'_>,
+    #[description = "ID of the reminder, leave out to pause all of them"]
+    #[max_length = 20]
+    reminder: Option<String>,
+) -> Result<(), Error> {
+    let database = reminder_database(ctx)?;
+    let author = ctx.author().id;
+    let id = parse_pause_target(reminder.as_deref())?;
+    let paused = database.set_paused(author, id, true).await?;
+    let name = guild_string(ctx, GuildString::ReminderName).await;
+    let reply = match id {
+        Some(id) if paused.iter().any(|r| r.id == id) => {
+            format!("{name} #{id} paused, resume it with `/remindme resume`")
+        }
+        Some(id) => match database.get_reminder(id).await? {
+            // don't reveal whether other people's reminders exist
+            Some(r) if r.user_id == author => format!("{name} #{id} is already paused"),
+            _ => format!("You don't have a reminder with ID {id}"),
+        },
+        None if paused.is_empty() => "You have no reminders to pause".to_string(),
+        None => format!(
+            "Paused {} reminders, resume them with `/remindme resume`",
+            paused.len()
+        ),
+    };
+    ctx.say(reply).await?;
     Ok(())
 }
 
-// Space is open for a `/remindme at` command
+/// Resume one of your paused reminders, or all of them
+///
+/// Reminders that came due while paused are sent now. Recurring ones skip to their next time,
+/// or are removed if their schedule has ended.
+#[poise::command(slash_command)]
+pub(crate) async fn resume(
+    ctx: Context<'_>,
+    #[description = "ID of the reminder, leave out to resume all of them"]
+    #[max_length = 20]
+    reminder: Option<String>,
+) -> Result<(), Error> {
+    let database = reminder_database(ctx)?;
+    let author = ctx.author().id;
+    let id = parse_pause_target(reminder.as_deref())?;
 
-/// Remind me in...
-#[poise::command(slash_command, rename = "in")]
-pub(crate) async fn remindin(
+    // missed occurrences of recurring reminders are skipped rather than sent late.
+    // they're moved while still paused, so their tasks never send the missed one
+    let now = Utc::now();
+    let mut moved = HashSet::new();
+    let mut ended = 0;
+    for missed in database.list_reminders(author, None).await? {
+        if !missed.paused || missed.due_at > now || id.is_some_and(|id| missed.id != id) {
+            continue;
+        }
+        if database.get_schedule(missed.id).await?.is_none() {
+            continue;
+        }
+        if let Some(next) = next_on_schedule(&database, &missed).await {
+            moved.insert(next.id);
+        } else {
+            // its schedule is over, or it couldn't be moved on. Either way the missed
+            // occurrence is skipped like any other, taking its heads up with it
+            database.remove_reminder(missed).await?;
+            ended += 1;
+        }
+    }
+    // a heads up for an occurrence that was skipped is no use late
+    let skipped: Vec<i64> = moved.iter().copied().collect();
+    database.drop_due_leads(&skipped, now).await?;
+
+    let resumed = database.set_paused(author, id, false).await?;
+    // the old tasks of the moved reminders bow out, so start new ones
+    for reminder in resumed.iter().filter(|r| moved.contains(&r.id)) {
+        tokio::spawn(sleeping_reminder(
+            database.clone(),
+            ctx.serenity_context().http.clone(),
+            ctx.data().log_channel,
+            reminder.clone(),
+        ));
+    }
+
+    let overdue = resumed.iter().filter(|r| r.due_at <= now).count();
+    let late_note = match overdue {
+        0 => String::new(),
+        1 => ", 1 came due while paused and is being sent now".to_string(),
+        n => format!(", {n} came due while paused and are being sent now"),
+    };
+    let ended_note = match ended {
+        0 => String::new(),
+        1 => "\n1 recurring reminder had no time left to move on to, so it was removed rather than sent late".to_string(),
+        n => format!("\n{n} recurring reminders had no time left to move on to, so they were removed rather than sent late"),
+    };
+    let name = guild_string(ctx, GuildString::ReminderName).await;
+    let reply = match id {
+        Some(id) if resumed.iter().any(|r| r.id == id) => {
+            format!("{name} #{id} resumed{late_note}")
+        }
+        Some(id) if ended > 0 => format!(
+            "{name} #{id} had no time left to move on to, so it was removed rather than sent late"
+        ),
+        Some(id) => match database.get_reminder(id).await? {
+            // don't reveal whether other people's reminders exist
+            Some(r) if r.user_id == author => format!("{name} #{id} isn't paused"),
+            _ => format!("You don't have a reminder with ID {id}"),
+        },
+        None if resumed.is_empty() && ended == 0 => "You have no paused reminders".to_string(),
+        None => format!("Resumed {} reminders{late_note}{ended_note}", resumed.len()),
+    };
+    ctx.say(reply).await?;
+    Ok(())
+}
+
+/// Convert an amount of time into other units
+///
+/// Months are always 28 days, to match how reminders count them.
+#[poise::command(slash_command)]
+pub(crate) async fn convert(
     ctx: Context<'_>,
-    #[description = "Time till reminder"]
+    #[description = "Amount of time"]
     #[min = 1]
     #[max = 10000]
     duration: i64,
     #[description = "Time units"] unit: TimeUnitChoice,
-    #[description = "Reminder message"] message: String,
 ) -> Result<(), Error> {
-    // yes discord, we're working on it
-    // don't time us out yet
-    ctx.defer().await?;
+    let lines: Vec<String> = convert_units(duration, &unit)
+        .into_iter()
+        .map(|line| format!("- {line}"))
+        .collect();
+    ctx.say(format!(
+        "{duration} {} is\n{}\n-# A month here is always 28 days, the same as for reminders",
+        unit.name(),
+        lines.join("\n")
+    ))
+    .await?;
+    Ok(())
+}
 
-    // write the reminder to the database
-    let database = ctx.data().database.clone();
+/// Show how you've been using reminders
+#[poise::command(slash_command)]
+pub(crate) async fn stats(ctx: Context<'_>) -> Result<(), Error> {
+    let database = reminder_database(ctx)?;
+    let stats = database.get_stats(ctx.author().id).await?;
+    if stats.created == 0 && stats.pending == 0 && stats.delivered == 0 {
+        ctx.say("You haven't set any reminders yet, try `/remindme in`")
+            .await?;
+        return Ok(());
+    }
+
+    let embed = embeds::branded_embed()
+        .title("Your reminders")
+        .field("Created", stats.created.to_string(), true)
+        .field("Pending", stats.pending.to_string(), true)
+        .field("Delivered", stats.delivered.to_string(), true)
+        .field("Average notice", average_lead(&stats), true)
+        .footer(serenity::CreateEmbedFooter::new(
+            "Created and delivered are counted from when stats started being kept",
+        ));
+    ctx.send(poise::CreateReply::default().embed(embed)).await?;
+    Ok(())
+}
+
+/// Describe how far ahead a user usually sets reminders
+fn average_lead(stats: &ReminderStats) -> String {
+    let Some(average) = stats.lead_seconds.checked_div(stats.created) else {
+        return "Not enough data".to_string();
+    };
+    rough_span(average.unsigned_abs()).map_or_else(
+        || "Under a minute".to_string(),
+        |span| format!("About {span}"),
+    )
+}
+
+/// Where reminders go when a webhook is set
+#[derive(Debug, Clone, Copy, poise::ChoiceParameter)]
+enum WebhookChoice {
+    #[name = "instead of Discord"]
+    Instead,
+    #[name = "as well as Discord"]
+    Also,
+}
+
+/// Reply to messages you set reminders about, instead of sending a direct message
+///
+/// Only applies to reminders made from now on with the "Remind me about this" message menu.
+/// If the message has been deleted by the time the reminder is due, it goes to your DMs as usual.
+/// Run it without options to see your current setting.
+#[poise::command(slash_command, ephemeral)]
+pub(crate) async fn replies(
+    ctx: Context<'_>,
+    #[description = "Deliver reminders about a message as a reply to it"] enabled: Option<bool>,
+) -> Result<(), Error> {
+    let database = reminder_database(ctx)?;
     let author = ctx.author().id;
-    let start_time = ctx.created_at();
-    let end_time = calculate_wait(start_time, duration, &unit);
-    let reminder = database.add_reminder(author, end_time, message).await?;
+    let enabled = match enabled {
+        Some(enabled) => {
+            database.set_replies(author, enabled).await?;
+            enabled
+        }
+        None => database.get_replies(author).await?,
+    };
+    let message = if enabled {
+        "Reminders about a message will be sent as a reply to it"
+    } else {
+        "Reminders about a message will be sent to your DMs"
+    };
+    ctx.say(message).await?;
+    Ok(())
+}
 
-    // spawn a task to deliver the reminder
-    tokio::spawn(sleeping_reminder(
-        database,
-        ctx.serenity_context().http.clone(),
-        reminder,
-    ));
+/// Post your reminders to a webhook, for other apps to pick up
+///
+/// Reminders are sent as JSON with `id`, `user_id`, `due_at`, `message`, `priority`, `category`
+/// and `source_link`, the message the reminder is about if there is one.
+/// Run it without options to see your current webhook.
+#[poise::command(slash_command, ephemeral)]
+pub(crate) async fn webhook(
+    ctx: Context<'_>,
+    #[description = "HTTPS URL to post reminders to"]
+    #[max_length = 500]
+    url: Option<String>,
+    #[description = "Send reminders to the webhook instead of Discord (default) or as well"]
+    mode: Option<WebhookChoice>,
+    #[description = "Stop posting reminders to your webhook"] clear: Option<bool>,
+) -> Result<(), Error> {
+    let database = reminder_database(ctx)?;
+    let author = ctx.author().id;
 
-    // tell the user that everything is hunky-dory
-    ctx.say(format!("Reminder created for <t:{}>", end_time.timestamp()))
+    if clear.unwrap_or(false) {
+        let message = if database.clear_webhook(author).await? {
+            "Removed your webhook, reminders will only be sent on Discord"
+        } else {
+            "You don't have a webhook set"
+        };
+        ctx.say(message).await?;
+        return Ok(());
+    }
+
+    let current = database.get_webhook(author).await?;
+    let webhook = match (url, current) {
+        (Some(url), _) => Webhook {
+            url: webhook::validate_url(&url)?.to_string(),
+            also_dm: matches!(mode, Some(WebhookChoice::Also)),
+        },
+        // only changing the mode
+        (None, Some(current)) if mode.is_some() => Webhook {
+            also_dm: matches!(mode, Some(WebhookChoice::Also)),
+            ..current
+        },
+        (None, Some(current)) => {
+            ctx.say(format!(
+                "Your reminders are posted to <{}> {}",
+                current.url,
+                describe_webhook_mode(&current)
+            ))
+            .await?;
+            return Ok(());
+        }
+        (None, None) => {
+            ctx.say("You don't have a webhook set. Give a URL to set one.")
+                .await?;
+            return Ok(());
+        }
+    };
+    database.set_webhook(author, &webhook).await?;
+    ctx.say(format!(
+        "Reminders will be posted to <{}> {}",
+        webhook.url,
+        describe_webhook_mode(&webhook)
+    ))
+    .await?;
+    Ok(())
+}
+
+/// Say whether reminders still go to Discord alongside a webhook
+fn describe_webhook_mode(webhook: &Webhook) -> &'static str {
+    if webhook.also_dm {
+        "as well as sent on Discord"
+    } else {
+        "instead of sent on Discord"
+    }
+}
+
+/// Custom id of the menu offering timezones to new users
+const TIMEZONE_MENU: &str = "choose-timezone";
+
+/// Offsets offered to new users, with the places that use them.
+/// Offsets don't follow daylight saving, so some places appear twice.
+const COMMON_OFFSETS: [(&str, &str); 18] = [
+    ("+13:00", "New Zealand daylight time"),
+    ("+12:00", "New Zealand standard time, Fiji"),
+    ("+11:00", "Sydney and Melbourne daylight time"),
+    ("+10:00", "Brisbane, Sydney and Melbourne standard time"),
+    ("+09:30", "Adelaide standard time, Darwin"),
+    ("+09:00", "Japan, Korea"),
+    ("+08:00", "Perth, Singapore, China"),
+    ("+05:30", "India"),
+    ("+02:00", "Central European summer time, South Africa"),
+    ("+01:00", "Central European winter time, UK summer time"),
+    ("+00:00", "UTC, UK winter time"),
+    ("-03:00", "Brazil, Argentina"),
+    ("-04:00", "US Eastern daylight time"),
+    ("-05:00", "US Eastern standard time, Central daylight time"),
+    ("-06:00", "US Central standard time"),
+    ("-07:00", "US Mountain standard time, Pacific daylight time"),
+    ("-08:00", "US Pacific standard time"),
+    ("-10:00", "Hawaii"),
+];
+
+/// Offer someone making their first reminder a menu of timezones, if they haven't set one.
+/// Their choice is saved by [`choose_timezone`]. This is only a nicety, so failures are printed.
+async fn offer_timezones(ctx: Context<'_>, database: &ReminderDatabase) {
+    match database.get_stats(ctx.author().id).await {
+        Ok(stats) if stats.created <= 1 => {}
+        Ok(_) => return,
+        Err(e) => {
+            println!(
+                "Unable to check if {} is new to reminders: {e:?}",
+                ctx.author().id
+            );
+            return;
+        }
+    }
+
+    let options = COMMON_OFFSETS
+        .iter()
+        .map(|(offset, places)| {
+            serenity::CreateSelectMenuOption::new(format!("UTC{offset}"), *offset)
+                .description(*places)
+        })
+        .collect();
+    let menu = serenity::CreateSelectMenu::new(
+        TIMEZONE_MENU,
+        serenity::CreateSelectMenuKind::String { options },
+    )
+    .placeholder("Choose your UTC offset");
+    let reply = poise::CreateReply::default()
+        .content(
+            "Pick your timezone and reminders will show your local time. \
+            You can change it any time with `/remindme timezone`.",
+        )
+        .components(vec![serenity::CreateActionRow::SelectMenu(menu)])
+        .ephemeral(true);
+    if let Err(e) = ctx.send(reply).await {
+        println!("Unable to offer timezones to {}: {e:?}", ctx.author().id);
+    }
+}
+
+/// Handle a choice from the timezone menu sent by [`offer_timezones`]
+pub(crate) async fn choose_timezone(
+    ctx: &serenity::Context,
+    database: Option<&ReminderDatabase>,
+    press: &serenity::ComponentInteraction,
+) -> Result<(), Error> {
+    if press.data.custom_id != TIMEZONE_MENU {
+        return Ok(());
+    }
+    let serenity::ComponentInteractionDataKind::StringSelect { values } = &press.data.kind else {
+        return Ok(());
+    };
+    let database = database.ok_or("Reminders are disabled because the bot has no database")?;
+    let offset = values
+        .first()
+        .and_then(|v| parse_utc_offset(v))
+        .ok_or("That isn't a timezone we offered")?;
+    database.set_timezone(press.user.id, offset).await?;
+
+    let response = serenity::CreateInteractionResponse::UpdateMessage(
+        serenity::CreateInteractionResponseMessage::new()
+            .content(format!(
+                "Timezone set to UTC{offset}. You can change it any time with `/remindme timezone`."
+            ))
+            .components(Vec::new()),
+    );
+    press.create_response(ctx, response).await?;
+    Ok(())
+}
+
+/// Set your timezone as a UTC offset
+#[poise::command(slash_command)]
+pub(crate) async fn timezone(
+    ctx: Context<'_>,
+    #[description = "UTC offset, e.g. +12, -05:30 or UTC+13:45"] offset: String,
+) -> Result<(), Error> {
+    let Some(parsed) = parse_utc_offset(&offset) else {
+        return Err(format!(
+            "`{offset}` isn't a valid UTC offset. Try something like `+12` or `-05:30`"
+        )
+        .into());
+    };
+    reminder_database(ctx)?
+        .set_timezone(ctx.author().id, parsed)
         .await?;
+
+    ctx.say(format!("Timezone set to UTC{parsed}")).await?;
     Ok(())
 }
+
+/// Read a time someone gave, either one that says exactly when it is (see [`parse_absolute_time`])
+/// or a local time in their saved timezone. Returns the time and the offset it was read in,
+/// if it needed one.
+async fn read_time(
+    ctx: Context<'_>,
+    time: &str,
+) -> Result<(DateTime<Utc>, Option<FixedOffset>), Error> {
+    if let Some(parsed) = parse_absolute_time(time) {
+        return Ok((parsed, None));
+    }
+    // a saved timezone is a nicety here, so without one (or a database) we use UTC
+    let offset = match ctx.data().database.as_ref() {
+        Some(database) => database.get_timezone(ctx.author().id).await.ok().flatten(),
+        None => None,
+    }
+    .unwrap_or(Utc.fix());
+    let parsed = parse_local_time(time, offset).ok_or(format!(
+        "I couldn't read `{time}` as a time, try something like `2024-01-31 14:30`"
+    ))?;
+    Ok((parsed, Some(offset)))
+}
+
+/// Show a date and time in every Discord timestamp style, ready to copy
+///
+/// Times without an offset are read in the timezone set with `/remindme timezone`, or UTC.
+#[poise::command(slash_command)]
+pub(crate) async fn timestamp(
+    ctx: Context<'_>,
+    #[description = "A time like 2024-01-31 14:30, unix seconds, or a Discord timestamp"]
+    #[max_length = 100]
+    time: String,
+) -> Result<(), Error> {
+    let (parsed, read_in) = read_time(ctx, &time).await?;
+
+    let mut reply = timestamp_styles(parsed);
+    if let Some(offset) = read_in {
+        reply = format!("{reply}\n-# Read as UTC{offset}");
+    }
+    ctx.say(reply).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        average_lead, calculate_wait, cancel_option_label, check_can_remind, convert_units,
+        delivery_route, describe_schedule, finish_interrupted, local_time, next_occurrence,
+        normalise_category, parse_absolute_time, parse_local_time, parse_reminder_id,
+        parse_time_of_day, parse_utc_offset, parse_weekdays, plan_restart, relative_time, round_up,
+        timestamp_styles, validate_message_link, will_repeat, Added, PriorityChoice, Reminder,
+        ReminderDatabase, ReminderStats, RoundChoice, Schedule, TimeUnitChoice, COMMON_OFFSETS,
+        MAX_DELIVERIES,
+    };
+    use crate::database::StatementCache;
+    use crate::log_channel::LogChannel;
+    use crate::serenity;
+    use crate::webhook::Webhook;
+    use chrono::{DateTime, FixedOffset, NaiveDate, NaiveTime, Offset, TimeDelta, Utc};
+    use poise::serenity_prelude::UserId;
+
+    /// 2023-11-14T22:13:20Z
+    const START: i64 = 1_700_000_000;
+
+    fn start() -> serenity::Timestamp {
+        serenity::Timestamp::from_unix_timestamp(START).unwrap()
+    }
+
+    fn at(seconds_after_start: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(START + seconds_after_start, 0).unwrap()
+    }
+
+    #[test]
+    fn single_unit() {
+        let cases = [
+            (TimeUnitChoice::Seconds, 1),
+            (TimeUnitChoice::Minutes, 60),
+            (TimeUnitChoice::Hours, 60 * 60),
+            (TimeUnitChoice::Days, 24 * 60 * 60),
+            (TimeUnitChoice::Weeks, 7 * 24 * 60 * 60),
+            (TimeUnitChoice::Months, 28 * 24 * 60 * 60),
+        ];
+        for (unit, seconds) in cases {
+            assert_eq!(
+                calculate_wait(start(), 1, &unit).unwrap(),
+                at(seconds),
+                "{unit:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn maximum_duration() {
+        let cases = [
+            (TimeUnitChoice::Seconds, 10_000),
+            (TimeUnitChoice::Minutes, 10_000 * 60),
+            (TimeUnitChoice::Hours, 10_000 * 60 * 60),
+            (TimeUnitChoice::Days, 10_000 * 24 * 60 * 60),
+            (TimeUnitChoice::Weeks, 10_000 * 7 * 24 * 60 * 60),
+            (TimeUnitChoice::Months, 10_000 * 28 * 24 * 60 * 60),
+        ];
+        for (unit, seconds) in cases {
+            assert_eq!(
+                calculate_wait(start(), 10_000, &unit).unwrap(),
+                at(seconds),
+                "{unit:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn overflowing_durations_are_errors() {
+        assert!(calculate_wait(start(), i64::MAX, &TimeUnitChoice::Seconds).is_err());
+        assert!(calculate_wait(start(), i64::MAX / 60, &TimeUnitChoice::Weeks).is_err());
+        // chrono stops at the year 262143, about 13.6 million weeks away
+        assert!(calculate_wait(start(), 14_000_000, &TimeUnitChoice::Weeks).is_err());
+        assert!(calculate_wait(start(), 13_000_000, &TimeUnitChoice::Weeks).is_ok());
+    }
+
+    #[test]
+    fn rounding_goes_up_to_the_next_boundary() {
+        let utc = Utc.fix();
+        // START is 22:13:20 UTC
+        assert_eq!(round_up(at(0), RoundChoice::FiveMinutes, utc), at(100));
+        assert_eq!(round_up(at(0), RoundChoice::Hour, utc), at(2800));
+        // a time on the boundary stays put, but a moment past it moves on
+        assert_eq!(round_up(at(100), RoundChoice::FiveMinutes, utc), at(100));
+        let just_after = at(100) + chrono::Duration::milliseconds(1);
+        assert_eq!(round_up(just_after, RoundChoice::FiveMinutes, utc), at(400));
+    }
+
+    #[test]
+    fn rounding_follows_the_local_clock() {
+        // 22:13:20 UTC is 03:58:20 in Nepal (UTC+05:45)
+        let nepal = FixedOffset::east_opt(5 * 3600 + 45 * 60).unwrap();
+        assert_eq!(round_up(at(0), RoundChoice::Hour, nepal), at(100));
+        assert_eq!(round_up(at(0), RoundChoice::FiveMinutes, nepal), at(100));
+    }
+
+    /// A plain reminder for `user`, due `seconds` after the start
+    fn reminder(id: i64, user: u64, seconds: i64) -> Reminder {
+        Reminder {
+            id,
+            user_id: UserId::new(user),
+            due_at: at(seconds),
+            message: format!("reminder {id}"),
+            priority: PriorityChoice::Normal,
+            channel_id: None,
+            category: None,
+            deliveries: 0,
+            source_link: None,
+            reply_to: None,
+            paused: false,
+            version: 0,
+        }
+    }
+
+    #[test]
+    fn restarts_schedule_each_stored_reminder_once() {
+        let mut database: Vec<Reminder> = (1..=6).map(|id| reminder(id, 1, id * 60)).collect();
+        // while running: 1 is delivered and removed, 2 and 3 are marked as sending for a digest
+        // that's cut off mid-send, and 4 is a critical reminder that was snoozed for later
+        database.retain(|r| r.id != 1);
+        let sending = [2, 3];
+        database[2].priority = PriorityChoice::Critical;
+        database[2].due_at = at(600);
+        database[2].deliveries = 1;
+        // and 6 was paused, then came due while we were down
+        database[4].paused = true;
+        database[4].due_at = at(120);
+
+        // then we crash and restart from what was stored. Reminders still marked as sending
+        // aren't fetched with the rest, they're finished off by `finish_interrupted` instead
+        let stored = database
+            .iter()
+            .filter(|r| !sending.contains(&r.id))
+            .cloned()
+            .collect();
+        let (overdue, upcoming) = plan_restart(stored, at(300));
+        let ids = |reminders: &[Reminder]| reminders.iter().map(|r| r.id).collect::<Vec<_>>();
+        assert_eq!(ids(&overdue), [5]);
+        // 6 waits for its resume rather than going out with the overdue batch
+        assert_eq!(ids(&upcoming), [4, 6]);
+        assert_eq!(upcoming[0].due_at, at(600));
+        assert_eq!(upcoming[0].deliveries, 1);
+
+        // every stored reminder is either scheduled or left to `finish_interrupted`, once
+        let mut handled = [ids(&overdue), ids(&upcoming), sending.to_vec()].concat();
+        handled.sort_unstable();
+        assert_eq!(handled, ids(&database));
+    }
+
+    /// Reminders in a schema of their own, on the postgres server in `TEST_DATABASE_URL`
+    async fn test_database(schema: &str) -> ReminderDatabase {
+        let url = std::env::var("TEST_DATABASE_URL")
+            .expect("TEST_DATABASE_URL should point at a database the tests can write to");
+        let client = crate::database::connect(&url).await.unwrap();
+        client
+            .batch_execute(&format!(
+                "DROP SCHEMA IF EXISTS {schema} CASCADE; CREATE SCHEMA {schema}; SET search_path TO {schema}"
+            ))
+            .await
+            .unwrap();
+        crate::database::check_schema_version(&client)
+            .await
+            .unwrap();
+        ReminderDatabase::new(&StatementCache::new(client), None, None, 5)
+            .await
+            .unwrap()
+    }
+
+    /// Add a reminder due `minutes` from now
+    async fn add_due_in(database: &ReminderDatabase, minutes: i64, message: &str) -> Reminder {
+        let added = database
+            .add_reminder(
+                UserId::new(1),
+                None,
+                Utc::now() + TimeDelta::minutes(minutes),
+                message.to_string(),
+                PriorityChoice::Normal,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        match added {
+            Added::New(reminder) => reminder,
+            Added::Duplicate(id) => panic!("reminder {id} was added twice"),
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a postgres database in TEST_DATABASE_URL"]
+    async fn repeated_reminders_point_at_the_first() {
+        let database = test_database("athena_duplicate_test").await;
+        let first = add_due_in(&database, 30, "stretch").await;
+        let again = database
+            .add_reminder(
+                first.user_id,
+                None,
+                first.due_at,
+                first.message.clone(),
+                PriorityChoice::Normal,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        assert!(matches!(again, Added::Duplicate(id) if id == first.id));
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a postgres database in TEST_DATABASE_URL"]
+    async fn restarts_never_resend_reminders_cut_off_mid_send() {
+        let database = test_database("athena_restart_test").await;
+        let sent = add_due_in(&database, -2, "sent").await;
+        let daily = add_due_in(&database, -3, "daily").await;
+        database
+            .add_schedule(daily.id, &ten_am("daily"))
+            .await
+            .unwrap();
+        let overdue = add_due_in(&database, -1, "overdue").await;
+        let upcoming = add_due_in(&database, 60, "upcoming").await;
+
+        // the bot marks two reminders and starts sending them, then crashes
+        let claimed = database
+            .claim_for_sending(&[sent.id, daily.id])
+            .await
+            .unwrap();
+        assert_eq!(claimed.len(), 2);
+        // nothing else can send them in the meantime
+        assert!(database
+            .claim_for_sending(&[sent.id])
+            .await
+            .unwrap()
+            .is_empty());
+
+        // and restarts
+        let bot = serenity::Http::new("");
+        finish_interrupted(&database, &bot, LogChannel::new(None))
+            .await
+            .unwrap();
+        let stored = database.get_reminders().await.unwrap();
+        let (due, later) =
+            plan_restart(stored.iter().map(Reminder::from_row).collect(), Utc::now());
+        let ids = |reminders: &[Reminder]| {
+            let mut ids: Vec<i64> = reminders.iter().map(|r| r.id).collect();
+            ids.sort_unstable();
+            ids
+        };
+        assert_eq!(ids(&due), [overdue.id]);
+        // the recurring one moved on to its next time rather than going out again
+        assert_eq!(ids(&later), [daily.id, upcoming.id]);
+        assert!(later.iter().all(|r| r.due_at > Utc::now()));
+        assert!(database.get_reminder(sent.id).await.unwrap().is_none());
+    }
+
+    /// Parse an RFC 3339 time, for schedule tests that cross days and months
+    fn utc(time: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(time).unwrap().to_utc()
+    }
+
+    /// A schedule at 10am on the given days, with no end
+    fn ten_am(days: &str) -> Schedule {
+        Schedule {
+            weekdays: parse_weekdays(days).unwrap(),
+            time: NaiveTime::from_hms_opt(10, 0, 0).unwrap(),
+            until: None,
+        }
+    }
+
+    #[test]
+    fn schedules_come_around_on_their_days() {
+        let schedule = ten_am("tue, thu");
+        let next = |after| next_occurrence(&schedule, utc(after), Utc.fix());
+        // 2024-01-01 is a Monday
+        assert_eq!(
+            next("2024-01-01T12:00:00Z"),
+            Some(utc("2024-01-02T10:00:00Z"))
+        );
+        // the occurrence that was just delivered doesn't count
+        assert_eq!(
+            next("2024-01-02T10:00:00Z"),
+            Some(utc("2024-01-04T10:00:00Z"))
+        );
+        // across the end of a month, a leap day and the end of a year
+        assert_eq!(
+            next("2024-01-31T09:00:00Z"),
+            Some(utc("2024-02-01T10:00:00Z"))
+        );
+        assert_eq!(
+            next("2024-02-28T00:00:00Z"),
+            Some(utc("2024-02-29T10:00:00Z"))
+        );
+        assert_eq!(
+            next("2024-12-31T11:00:00Z"),
+            Some(utc("2025-01-02T10:00:00Z"))
+        );
+    }
+
+    #[test]
+    fn schedules_end_after_their_last_day() {
+        let mut schedule = ten_am("tue, thu");
+        schedule.until = NaiveDate::from_ymd_opt(2024, 1, 4);
+        let next = |after| next_occurrence(&schedule, utc(after), Utc.fix());
+        assert_eq!(
+            next("2024-01-02T11:00:00Z"),
+            Some(utc("2024-01-04T10:00:00Z"))
+        );
+        assert_eq!(next("2024-01-04T10:00:00Z"), None);
+        assert_eq!(next("2024-02-01T00:00:00Z"), None);
+    }
+
+    #[test]
+    fn schedules_follow_the_users_clock() {
+        let schedule = ten_am("mondays");
+        let summer = FixedOffset::east_opt(13 * 3600).unwrap();
+        let winter = FixedOffset::east_opt(12 * 3600).unwrap();
+        // 2024-03-31T00:00Z is Sunday afternoon in New Zealand
+        assert_eq!(
+            next_occurrence(&schedule, utc("2024-03-31T00:00:00Z"), summer),
+            Some(utc("2024-03-31T21:00:00Z"))
+        );
+        // it's already Monday afternoon there, even though it's Sunday in UTC
+        assert_eq!(
+            next_occurrence(&schedule, utc("2024-04-01T00:00:00Z"), summer),
+            Some(utc("2024-04-07T21:00:00Z"))
+        );
+        // once daylight saving ends and they update their timezone, it's still 10am for them
+        assert_eq!(
+            next_occurrence(&schedule, utc("2024-04-07T00:00:00Z"), winter),
+            Some(utc("2024-04-07T22:00:00Z"))
+        );
+    }
+
+    #[test]
+    fn weekdays_are_read_in_words() {
+        assert_eq!(parse_weekdays("Mondays and Fridays").unwrap(), 0b001_0001);
+        assert_eq!(parse_weekdays("tue,thu").unwrap(), 0b000_1010);
+        assert_eq!(parse_weekdays("weekdays").unwrap(), 0b001_1111);
+        assert_eq!(parse_weekdays("weekends & mon").unwrap(), 0b110_0001);
+        assert_eq!(parse_weekdays("daily").unwrap(), 0b111_1111);
+        assert_eq!(
+            parse_weekdays("mon, funday").unwrap_err().to_string(),
+            "`funday` isn't a day of the week"
+        );
+        assert!(parse_weekdays(" and ").is_err());
+    }
+
+    #[test]
+    fn times_of_day_are_read_either_way() {
+        let time = |h, m| NaiveTime::from_hms_opt(h, m, 0);
+        assert_eq!(parse_time_of_day("10am"), time(10, 0));
+        assert_eq!(parse_time_of_day("2:30 PM"), time(14, 30));
+        assert_eq!(parse_time_of_day("12am"), time(0, 0));
+        assert_eq!(parse_time_of_day("12pm"), time(12, 0));
+        assert_eq!(parse_time_of_day("14:30"), time(14, 30));
+        assert_eq!(parse_time_of_day("13pm"), None);
+        assert_eq!(parse_time_of_day("25:00"), None);
+        assert_eq!(parse_time_of_day("10:60"), None);
+        assert_eq!(parse_time_of_day("soon"), None);
+    }
+
+    #[test]
+    fn schedules_are_described() {
+        let mut schedule = ten_am("thu, tue");
+        schedule.until = NaiveDate::from_ymd_opt(2024, 12, 1);
+        assert_eq!(
+            describe_schedule(&schedule),
+            "every Tue, Thu at 10:00 until 2024-12-01"
+        );
+        assert_eq!(describe_schedule(&ten_am("daily")), "every day at 10:00");
+    }
+
+    #[test]
+    fn times_are_read_in_any_form() {
+        let nzdt = FixedOffset::east_opt(13 * 3600).unwrap();
+        assert_eq!(parse_absolute_time("<t:1700000000:R>"), Some(at(0)));
+        assert_eq!(parse_absolute_time("<t:1700000000>"), Some(at(0)));
+        assert_eq!(parse_absolute_time(" 1700000000 "), Some(at(0)));
+        assert_eq!(
+            parse_absolute_time("2023-11-15T11:13:20+13:00"),
+            Some(at(0))
+        );
+        assert_eq!(parse_absolute_time("2023-11-15 11:13"), None);
+        assert_eq!(parse_absolute_time("<t:1700000000:X>"), None);
+        assert_eq!(parse_absolute_time("<t:1700000000:R:F>"), None);
+        assert_eq!(parse_absolute_time("<t:soon:R>"), None);
+        assert_eq!(parse_absolute_time("<t:99999999999999999:R>"), None);
+
+        assert_eq!(parse_local_time("2023-11-15 11:13:20", nzdt), Some(at(0)));
+        assert_eq!(
+            parse_local_time("2023-11-14T22:13", Utc.fix()),
+            Some(at(-20))
+        );
+        assert_eq!(
+            parse_local_time("2023-11-15", nzdt),
+            Some(at(-(11 * 3600 + 13 * 60 + 20)))
+        );
+        assert_eq!(parse_local_time("next tuesday", nzdt), None);
+        assert_eq!(parse_local_time("2023-02-30 10:00", nzdt), None);
+    }
+
+    #[test]
+    fn timestamps_come_in_every_style() {
+        let styles = timestamp_styles(at(0));
+        assert_eq!(styles.lines().count(), 7);
+        assert!(styles.starts_with("`<t:1700000000:t>` short time: <t:1700000000:t>"));
+        assert!(styles.ends_with("`<t:1700000000:R>` relative: <t:1700000000:R>"));
+    }
+
+    #[test]
+    fn local_time_reads_like_a_clock() {
+        let nzdt = FixedOffset::east_opt(13 * 3600).unwrap();
+        assert_eq!(
+            local_time(at(0), nzdt),
+            "Wed 15 Nov 2023, 11:13am UTC+13:00"
+        );
+        assert_eq!(
+            local_time(at(0), Utc.fix()),
+            "Tue 14 Nov 2023, 10:13pm UTC+00:00"
+        );
+    }
+
+    #[test]
+    fn reminders_can_be_picked_by_id() {
+        assert_eq!(parse_reminder_id("42"), Some(42));
+        assert_eq!(parse_reminder_id(" #42 "), Some(42));
+        assert_eq!(parse_reminder_id("water the plants"), None);
+        assert_eq!(parse_reminder_id("#"), None);
+
+        let reminder = Reminder {
+            message: "a".repeat(200),
+            ..reminder(7, 1, 0)
+        };
+        let label = cancel_option_label(&reminder);
+        assert!(label.starts_with("#7: aaa"));
+        assert_eq!(label.chars().count(), 100);
+    }
+
+    #[test]
+    fn only_jump_urls_are_message_links() {
+        assert!(validate_message_link("https://discord.com/channels/1/2/3").is_ok());
+        assert!(validate_message_link("https://discord.com/channels/@me/2/3").is_ok());
+        assert!(validate_message_link("http://discord.com/channels/1/2/3").is_err());
+        assert!(validate_message_link("https://example.com/channels/1/2/3").is_err());
+        assert!(validate_message_link("https://discord.com/channels/1/2").is_err());
+        assert!(validate_message_link("https://discord.com/channels/1/2/three").is_err());
+    }
+
+    #[test]
+    fn delivery_route_follows_the_webhook() {
+        let mut reminder = reminder(1, 1, 0);
+        assert_eq!(delivery_route(&reminder, None), "DM");
+        let webhook = |also_dm| Webhook {
+            url: "https://example.com/hook".to_string(),
+            also_dm,
+        };
+        assert_eq!(delivery_route(&reminder, Some(&webhook(false))), "Webhook");
+        reminder.channel_id = Some(serenity::ChannelId::new(2));
+        assert_eq!(
+            delivery_route(&reminder, Some(&webhook(true))),
+            "Webhook and DM, falling back to the channel it was made in"
+        );
+        reminder.reply_to = Some((serenity::ChannelId::new(2), serenity::MessageId::new(3)));
+        assert_eq!(
+            delivery_route(&reminder, None),
+            "Reply to the message it is about, falling back to DM"
+        );
+    }
+
+    #[test]
+    fn categories_are_normalised() {
+        assert_eq!(normalise_category(" Work "), Some("work".to_string()));
+        assert_eq!(normalise_category("personal"), Some("personal".to_string()));
+        assert_eq!(normalise_category("   "), None);
+    }
+
+    #[test]
+    fn only_critical_reminders_repeat() {
+        let mut reminder = Reminder {
+            priority: PriorityChoice::High,
+            ..reminder(1, 1, 0)
+        };
+        assert!(!will_repeat(&reminder));
+
+        reminder.priority = PriorityChoice::Critical;
+        assert!(will_repeat(&reminder));
+        // the last allowed delivery doesn't ask to be acknowledged
+        reminder.deliveries = MAX_DELIVERIES - 1;
+        assert!(!will_repeat(&reminder));
+    }
+
+    #[test]
+    fn months_are_28_days() {
+        // A "month" is a fixed 28 days, so it doesn't line up with the calendar
+        let due = calculate_wait(start(), 1, &TimeUnitChoice::Months).unwrap();
+        assert_eq!(due.to_rfc3339(), "2023-12-12T22:13:20+00:00");
+        // and twelve of them fall a month short of a year
+        let due = calculate_wait(start(), 12, &TimeUnitChoice::Months).unwrap();
+        assert_eq!(due.to_rfc3339(), "2024-10-15T22:13:20+00:00");
+    }
+
+    #[test]
+    fn crosses_leap_day() {
+        // 2024 is a leap year, so 16 weeks from the start includes the 29th of February
+        let due = calculate_wait(start(), 16, &TimeUnitChoice::Weeks).unwrap();
+        assert_eq!(due.to_rfc3339(), "2024-03-05T22:13:20+00:00");
+    }
+
+    #[test]
+    fn average_notice_handles_no_reminders() {
+        let mut stats = ReminderStats {
+            created: 0,
+            delivered: 0,
+            lead_seconds: 0,
+            pending: 0,
+        };
+        assert_eq!(average_lead(&stats), "Not enough data");
+        stats.created = 3;
+        stats.lead_seconds = 3 * 2 * 60 * 60;
+        assert_eq!(average_lead(&stats), "About 2 hours");
+        stats.lead_seconds = 30;
+        assert_eq!(average_lead(&stats), "Under a minute");
+    }
+
+    #[test]
+    fn bots_cannot_set_reminders() {
+        let mut user = serenity::User::default();
+        assert!(check_can_remind(&user).is_ok());
+        user.bot = true;
+        assert!(check_can_remind(&user).is_err());
+        user.bot = false;
+        user.system = true;
+        assert!(check_can_remind(&user).is_err());
+    }
+
+    #[test]
+    fn units_convert_both_ways() {
+        assert_eq!(
+            convert_units(3, &TimeUnitChoice::Weeks),
+            [
+                "1814400 seconds",
+                "30240 minutes",
+                "504 hours",
+                "21 days",
+                "0.75 months"
+            ]
+        );
+        // months are 28 days, not calendar months
+        assert_eq!(convert_units(1, &TimeUnitChoice::Months)[4], "4 weeks");
+        assert_eq!(convert_units(90, &TimeUnitChoice::Minutes)[1], "1.5 hours");
+        assert_eq!(convert_units(1, &TimeUnitChoice::Seconds)[1], "0 hours");
+    }
+
+    #[test]
+    fn relative_time_in_words() {
+        let now = at(0);
+        let cases = [
+            (10, "in a few seconds"),
+            (-10, "a few seconds ago"),
+            (60, "in about a minute"),
+            (-25 * 60, "about 25 minutes ago"),
+            (60 * 60, "in about an hour"),
+            (3 * 60 * 60 + 10 * 60, "in about 3 hours"),
+            (-2 * 24 * 60 * 60, "about 2 days ago"),
+            (40 * 24 * 60 * 60, "in about a month"),
+            (100 * 24 * 60 * 60, "in about 3 months"),
+            (400 * 24 * 60 * 60, "in about a year"),
+            (-3 * 365 * 24 * 60 * 60, "about 3 years ago"),
+        ];
+        for (offset, expected) in cases {
+            assert_eq!(relative_time(now, at(offset)), expected, "{offset}");
+        }
+    }
+
+    #[test]
+    fn offered_timezones_are_valid_and_distinct() {
+        let mut seen = std::collections::HashSet::new();
+        for (offset, _) in COMMON_OFFSETS {
+            let parsed = parse_utc_offset(offset).unwrap();
+            assert_eq!(format!("{parsed}"), offset);
+            assert!(seen.insert(offset));
+        }
+    }
+}
@@ -0,0 +1,165 @@
+use super::database::{from_db_id, to_db_id, StatementCache};
+use super::embeds;
+use super::errors::Error;
+use super::Context;
+use poise::serenity_prelude::{GuildId, UserId};
+use std::sync::Arc;
+use tokio_postgres::{types::Type, Client, Statement};
+
+/// How long natural 20s are kept for leaderboards, in days. Older ones are cleared out as new ones come in.
+const HISTORY_DAYS: i64 = 30;
+/// Leaderboard window used when none is given, in days
+const DEFAULT_DAYS: i64 = 7;
+/// Most people shown on a leaderboard
+const LEADERBOARD_SIZE: i64 = 10;
+
+/// Helper struct for storing the natural 20s rolled in each server, for leaderboards.
+/// Use the methods on this struct rather than directly acessing the fields.
+pub(crate) struct RollHistoryDatabase {
+    /// The database client used to interact with postgres
+    client: Arc<Client>,
+    /// A prepared database statement that stores a natural 20, clearing out old ones
+    record: Statement,
+    /// A prepared database statement that ranks a server's rollers by natural 20s
+    most_twenties: Statement,
+}
+
+impl RollHistoryDatabase {
+    /// Set up the history table and statements on the connection the cache is for
+    pub(crate) async fn new(statements: &StatementCache) -> Result<Self, Error> {
+        let client = statements.client();
+        // Init table if required.
+        // roll_history stored every roll with its total and a count of natural 20s,
+        // which let people farm the leaderboard with huge pools of d20s, so it's dropped
+        client
+            .batch_execute(
+                "DROP TABLE IF EXISTS roll_history;
+                CREATE TABLE IF NOT EXISTS natural_twenties (
+                            guild_id BIGINT NOT NULL,
+                            user_id BIGINT NOT NULL,
+                            rolled_at TIMESTAMPTZ NOT NULL DEFAULT now()
+                        );
+                CREATE INDEX IF NOT EXISTS natural_twenties_guild_time ON natural_twenties (guild_id, rolled_at);",
+            )
+            .await?;
+
+        let record = statements
+            .prepare_typed(
+                "WITH expired AS (
+                        DELETE FROM natural_twenties WHERE rolled_at < now() - $3 * interval '1 day'
+                    )
+                    INSERT INTO natural_twenties (guild_id, user_id) values ($1, $2)",
+                &[Type::INT8, Type::INT8, Type::INT8],
+            )
+            .await?;
+        let most_twenties = statements
+            .prepare_typed(
+                "SELECT user_id, COUNT(*) AS score FROM natural_twenties
+                    WHERE guild_id = $1 AND rolled_at > now() - $2 * interval '1 day'
+                    GROUP BY user_id ORDER BY score DESC, MIN(rolled_at) LIMIT $3",
+                &[Type::INT8, Type::INT8, Type::INT8],
+            )
+            .await?;
+
+        Ok(RollHistoryDatabase {
+            client,
+            record,
+            most_twenties,
+        })
+    }
+
+    /// Store a natural 20 rolled in a server
+    async fn record(&self, guild_id: GuildId, user_id: UserId) -> Result<(), Error> {
+        self.client
+            .execute(
+                &self.record,
+                &[&to_db_id(guild_id), &to_db_id(user_id), &HISTORY_DAYS],
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// The top rollers in a server over the last `days` days, best first, with their natural 20s
+    async fn leaderboard(&self, guild_id: GuildId, days: i64) -> Result<Vec<(UserId, i64)>, Error> {
+        let rows = self
+            .client
+            .query(
+                &self.most_twenties,
+                &[&to_db_id(guild_id), &days, &LEADERBOARD_SIZE],
+            )
+            .await?;
+        Ok(rows
+            .iter()
+            .map(|row| (UserId::new(from_db_id(row.get(0))), row.get(1)))
+            .collect())
+    }
+}
+
+/// Store a natural 20 for the server's leaderboard in the background.
+/// Rolls outside servers aren't stored, and like analytics this never
+/// blocks the roll and failures are only printed.
+pub(crate) fn record_natural_twenty(ctx: Context<'_>) {
+    let (Some(history), Some(guild_id)) = (ctx.data().roll_history.clone(), ctx.guild_id()) else {
+        return;
+    };
+    let user_id = ctx.author().id;
+    tokio::spawn(async move {
+        if let Err(e) = history.record(guild_id, user_id).await {
+            println!("Unable to record roll for leaderboard: {e:?}");
+        }
+    });
+}
+
+/// Format a leaderboard as numbered lines, like `1. @someone: 3`
+fn leaderboard_lines(entries: &[(UserId, i64)]) -> String {
+    entries
+        .iter()
+        .enumerate()
+        .map(|(i, (user, score))| format!("{}. <@{user}>: {score}", i + 1))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Shows who in this server has rolled the most natural 20s lately
+#[poise::command(slash_command, guild_only)]
+pub(crate) async fn rollleaderboard(
+    ctx: Context<'_>,
+    #[description = "Only count rolls from the last this many days, defaults to 7"]
+    #[min = 1]
+    #[max = 30]
+    days: Option<i64>,
+) -> Result<(), Error> {
+    let history = ctx
+        .data()
+        .roll_history
+        .as_ref()
+        .ok_or("Rolls aren't stored because the bot has no database")?;
+    let guild_id = ctx.guild_id().ok_or("Leaderboards only work in servers")?;
+    let days = days.unwrap_or(DEFAULT_DAYS);
+
+    let entries = history.leaderboard(guild_id, days).await?;
+    if entries.is_empty() {
+        ctx.say(format!(
+            "Nobody here has rolled a natural 20 in the last {days} days"
+        ))
+        .await?;
+        return Ok(());
+    }
+    let embed = embeds::branded_embed()
+        .title(format!("Most natural 20s in the last {days} days"))
+        .description(leaderboard_lines(&entries));
+    ctx.send(poise::CreateReply::default().embed(embed)).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::leaderboard_lines;
+    use poise::serenity_prelude::UserId;
+
+    #[test]
+    fn leaderboards_are_numbered() {
+        let entries = [(UserId::new(1), 3), (UserId::new(2), 1)];
+        assert_eq!(leaderboard_lines(&entries), "1. <@1>: 3\n2. <@2>: 1");
+    }
+}
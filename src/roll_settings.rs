@@ -0,0 +1,168 @@
+use super::database::{to_db_id, StatementCache};
+use super::dice::{OutputChoice, RollerChoice};
+use super::errors::Error;
+use super::Context;
+use poise::serenity_prelude::UserId;
+use poise::ChoiceParameter;
+use std::sync::Arc;
+use tokio_postgres::{types::Type, Client, Statement};
+
+/// A user's defaults for `/roll`, used when they leave the matching option out.
+/// `None` means they haven't picked one, so the usual default applies.
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct RollSettings {
+    pub(crate) roller: Option<RollerChoice>,
+    pub(crate) output: Option<OutputChoice>,
+}
+
+/// Helper struct for storing each user's `/roll` defaults.
+/// Use the methods on this struct rather than directly acessing the fields.
+pub(crate) struct RollSettingsDatabase {
+    /// The database client used to interact with postgres
+    client: Arc<Client>,
+    /// A prepared database statement that fetches a user's settings
+    get: Statement,
+    /// A prepared database statement that updates a user's settings, keeping any left out
+    set: Statement,
+    /// A prepared database statement that deletes a user's settings
+    clear: Statement,
+}
+
+impl RollSettingsDatabase {
+    /// Set up the settings table and statements on the connection the cache is for
+    pub(crate) async fn new(statements: &StatementCache) -> Result<Self, Error> {
+        let client = statements.client();
+        // Init table if required
+        client
+            .execute(
+                "CREATE TABLE IF NOT EXISTS roll_settings (
+                            user_id BIGINT PRIMARY KEY,
+                            roller SMALLINT,
+                            output SMALLINT
+                        )",
+                &[],
+            )
+            .await?;
+
+        let get = statements
+            .prepare_typed(
+                "SELECT roller, output FROM roll_settings WHERE user_id = $1",
+                &[Type::INT8],
+            )
+            .await?;
+        let set = statements
+            .prepare_typed(
+                "INSERT INTO roll_settings (user_id, roller, output) values ($1, $2, $3)
+                    ON CONFLICT (user_id) DO UPDATE SET
+                        roller = COALESCE(EXCLUDED.roller, roll_settings.roller),
+                        output = COALESCE(EXCLUDED.output, roll_settings.output)
+                    RETURNING roller, output",
+                &[Type::INT8, Type::INT2, Type::INT2],
+            )
+            .await?;
+        let clear = statements
+            .prepare_typed(
+                "DELETE FROM roll_settings WHERE user_id = $1",
+                &[Type::INT8],
+            )
+            .await?;
+
+        Ok(RollSettingsDatabase {
+            client,
+            get,
+            set,
+            clear,
+        })
+    }
+
+    /// Get a user's settings, which are all `None` if they haven't set any
+    async fn get(&self, user_id: UserId) -> Result<RollSettings, Error> {
+        let row = self
+            .client
+            .query_opt(&self.get, &[&to_db_id(user_id)])
+            .await?;
+        Ok(row.map_or_else(RollSettings::default, |row| RollSettings {
+            roller: row.get::<_, Option<i16>>(0).map(RollerChoice::from_db),
+            output: row.get::<_, Option<i16>>(1).map(OutputChoice::from_db),
+        }))
+    }
+
+    /// Change the settings that are `Some`, keeping the rest. Returns the settings as they now are.
+    async fn set(&self, user_id: UserId, settings: RollSettings) -> Result<RollSettings, Error> {
+        let row = self
+            .client
+            .query_one(
+                &self.set,
+                &[
+                    &to_db_id(user_id),
+                    &settings.roller.map(RollerChoice::to_db),
+                    &settings.output.map(OutputChoice::to_db),
+                ],
+            )
+            .await?;
+        Ok(RollSettings {
+            roller: row.get::<_, Option<i16>>(0).map(RollerChoice::from_db),
+            output: row.get::<_, Option<i16>>(1).map(OutputChoice::from_db),
+        })
+    }
+
+    /// Forget all of a user's settings
+    async fn clear(&self, user_id: UserId) -> Result<(), Error> {
+        self.client
+            .execute(&self.clear, &[&to_db_id(user_id)])
+            .await?;
+        Ok(())
+    }
+}
+
+/// Look up the settings for whoever used a command.
+/// Settings are a convenience, so without a database or if the lookup fails
+/// this falls back to the usual defaults rather than failing the roll.
+pub(crate) async fn settings_for(ctx: Context<'_>) -> RollSettings {
+    let Some(database) = &ctx.data().roll_settings else {
+        return RollSettings::default();
+    };
+    database.get(ctx.author().id).await.unwrap_or_else(|e| {
+        println!("Unable to get roll settings for {}: {e:?}", ctx.author().id);
+        RollSettings::default()
+    })
+}
+
+/// Set your defaults for /roll, used when you leave an option out
+#[poise::command(slash_command, ephemeral)]
+pub(crate) async fn rollsettings(
+    ctx: Context<'_>,
+    #[description = "Roller to use by default"] roller: Option<RollerChoice>,
+    #[description = "Output style to use by default"] output: Option<OutputChoice>,
+    #[description = "Forget your settings and go back to the usual defaults"] reset: Option<bool>,
+) -> Result<(), Error> {
+    let database = ctx
+        .data()
+        .roll_settings
+        .as_ref()
+        .ok_or("Roll settings are disabled because the bot has no database")?;
+    let author = ctx.author().id;
+    if reset.unwrap_or(false) {
+        database.clear(author).await?;
+        ctx.say("Your roll settings have been reset").await?;
+        return Ok(());
+    }
+
+    let settings = database
+        .set(author, RollSettings { roller, output })
+        .await?;
+    let not_set = "not set";
+    ctx.say(format!(
+        "Your roll settings are now\nRoller: {}\nOutput: {}",
+        settings
+            .roller
+            .as_ref()
+            .map_or(not_set, ChoiceParameter::name),
+        settings
+            .output
+            .as_ref()
+            .map_or(not_set, ChoiceParameter::name),
+    ))
+    .await?;
+    Ok(())
+}
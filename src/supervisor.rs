@@ -0,0 +1,75 @@
+use super::log_channel::LogChannel;
+use crate::serenity;
+use std::any::Any;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Wait before the first restart of a task that panicked
+const BASE_BACKOFF: Duration = Duration::from_secs(5);
+/// Backoff stops doubling once it reaches this. A task that runs this long
+/// without panicking is counted as healthy and its backoff starts over.
+const MAX_BACKOFF: Duration = Duration::from_mins(10);
+
+/// How long to wait before restarting a task, given the wait before its last restart
+/// and how long it ran this time. Repeated quick panics back off exponentially,
+/// so a task that can't get going doesn't spin.
+fn restart_delay(previous: Option<Duration>, ran_for: Duration) -> Duration {
+    match previous {
+        Some(previous) if ran_for < MAX_BACKOFF => (previous * 2).min(MAX_BACKOFF),
+        _ => BASE_BACKOFF,
+    }
+}
+
+/// Pull the message out of a panic, if it has one
+fn panic_message(panic: &(dyn Any + Send)) -> &str {
+    panic
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("no message")
+}
+
+/// Run a long-lived background task, restarting it if it panics.
+/// Panics are posted to the log channel so a dead task doesn't go unnoticed.
+/// `start` is called to create the task each time it (re)starts, so it can't carry
+/// state over from a run that panicked. If the task finishes normally it isn't restarted.
+pub(crate) fn supervise<F, Fut>(
+    name: &'static str,
+    bot: Arc<serenity::Http>,
+    log_channel: LogChannel,
+    start: F,
+) where
+    F: Fn() -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    tokio::spawn(async move {
+        let mut previous = None;
+        loop {
+            let started = Instant::now();
+            let Err(e) = tokio::spawn(start()).await else {
+                println!("Background task `{name}` finished");
+                return;
+            };
+            // the runtime only cancels tasks when it shuts down, so there's nothing to restart
+            let Ok(panic) = e.try_into_panic() else {
+                return;
+            };
+
+            let backoff = restart_delay(previous, started.elapsed());
+            previous = Some(backoff);
+            log_channel
+                .log(
+                    &bot,
+                    &format!("Background task `{name}` panicked"),
+                    &format!(
+                        "{}\nRestarting it in {} seconds.",
+                        panic_message(&*panic),
+                        backoff.as_secs()
+                    ),
+                )
+                .await;
+            tokio::time::sleep(backoff).await;
+        }
+    });
+}
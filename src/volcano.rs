@@ -0,0 +1,99 @@
+use super::embeds;
+use super::errors::Error;
+use super::Context;
+use crate::serenity;
+use poise::serenity_prelude::Colour;
+use serde::Deserialize;
+use std::cmp::Reverse;
+
+/// This structure corresponds to the `properties` compound of a volcano
+/// in geonet's volcanic alert level data
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct VolcanoProperties {
+    #[serde(rename = "volcanoTitle")]
+    pub(crate) title: String,
+    /// Volcanic alert level, from 0 (no unrest) to 5 (major eruption)
+    pub(crate) level: i8,
+    pub(crate) activity: String,
+    pub(crate) hazards: String,
+}
+
+/// A volcano and its alert level, as represented by geonet
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct Volcano {
+    // Like with quakes we only care about the `properties`
+    pub(crate) properties: VolcanoProperties,
+}
+
+/// Colour for a volcanic alert level, following geonet's green to red scale
+fn level_colour(level: i8) -> Colour {
+    match level {
+        i8::MIN..=0 => Colour::DARK_GREEN,
+        1 => Colour::GOLD,
+        2 => Colour::ORANGE,
+        3..=i8::MAX => Colour::RED,
+    }
+}
+
+/// Build an embed listing volcanoes from most to least active
+fn create_volcano_embed(mut volcanoes: Vec<Volcano>) -> serenity::CreateEmbed {
+    volcanoes.sort_by_key(|v| Reverse(v.properties.level));
+    let highest = volcanoes.first().map_or(0, |v| v.properties.level);
+    let embed = embeds::branded_embed()
+        .title("Volcanic alert levels")
+        .url("https://www.geonet.org.nz/volcano")
+        .colour(level_colour(highest));
+
+    // there are only a dozen or so monitored volcanoes, well under the field limit
+    volcanoes
+        .into_iter()
+        .take(embeds::MAX_FIELDS)
+        .fold(embed, |embed, volcano| {
+            let properties = volcano.properties;
+            let mut details = properties.activity;
+            if !properties.hazards.is_empty() {
+                details = format!("{details}\nHazards: {}", properties.hazards);
+            }
+            embed.field(
+                format!("{}: level {}", properties.title, properties.level),
+                embeds::truncate(details, embeds::MAX_FIELD_VALUE),
+                false,
+            )
+        })
+}
+
+/// Shows the current volcanic alert level of New Zealand's volcanoes
+#[poise::command(slash_command)]
+pub(crate) async fn volcano(ctx: Context<'_>) -> Result<(), Error> {
+    embeds::with_placeholder(ctx, "Fetching volcanic alert levels from GeoNet…", async {
+        let volcanoes = ctx.data().geonet.volcano_alerts().await?;
+        Ok(poise::CreateReply::default().embed(create_volcano_embed(volcanoes)))
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{level_colour, Volcano};
+    use poise::serenity_prelude::Colour;
+
+    #[test]
+    fn geonet_volcano_data_parses() {
+        let volcano: Volcano = serde_json::from_value(serde_json::json!({
+            "type": "Feature",
+            "geometry": {"type": "Point", "coordinates": [175.563, -39.281]},
+            "properties": {
+                "volcanoID": "ruapehu",
+                "volcanoTitle": "Ruapehu",
+                "level": 1,
+                "acc": "Green",
+                "activity": "Minor volcanic unrest.",
+                "hazards": "Volcanic unrest hazards, potential for eruption hazards."
+            }
+        }))
+        .unwrap();
+        assert_eq!(volcano.properties.title, "Ruapehu");
+        assert_eq!(volcano.properties.level, 1);
+        assert_eq!(level_colour(volcano.properties.level), Colour::GOLD);
+    }
+}
@@ -0,0 +1,70 @@
+use super::Context;
+use crate::serenity;
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+/// What we tell users when a command runs past the time limit
+const SLOW_MESSAGE: &str =
+    "This is taking longer than expected. It may still finish, but something could be stuck.";
+
+/// Keeps an eye on running commands so a hung database or web request doesn't leave the
+/// user staring at "Athena is thinking..." until Discord gives up on the interaction.
+/// Each command gets a timer when it starts. If the command hasn't finished when the timer
+/// runs out the user is sent a follow up message. The command itself is left running.
+pub(crate) struct Watchdog {
+    /// How long a command can run before we warn the user
+    limit: Duration,
+    /// Timers for running commands, keyed by invocation id
+    timers: Mutex<HashMap<u64, JoinHandle<()>>>,
+}
+
+impl Watchdog {
+    /// Create a watchdog that warns users about commands running longer than `limit`
+    pub(crate) fn new(limit: Duration) -> Self {
+        Watchdog {
+            limit,
+            timers: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Lock the timers. A panic while holding the lock can't leave the map
+    /// inconsistent, so we ignore poisoning.
+    fn lock(&self) -> MutexGuard<'_, HashMap<u64, JoinHandle<()>>> {
+        self.timers.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Start the timer for a command. Only slash commands are watched,
+    /// follow ups need the interaction to reply to.
+    pub(crate) fn start(&self, ctx: Context<'_>) {
+        let Context::Application(app_ctx) = ctx else {
+            return;
+        };
+        let interaction = app_ctx.interaction.clone();
+        let http = ctx.serenity_context().http.clone();
+        let name = ctx.command().qualified_name.clone();
+        let limit = self.limit;
+
+        let timer = tokio::spawn(async move {
+            tokio::time::sleep(limit).await;
+            println!("Command `{name}` has been running for over {limit:?}");
+            let followup = serenity::CreateInteractionResponseFollowup::new()
+                .content(SLOW_MESSAGE)
+                .ephemeral(true);
+            if let Err(e) = interaction.create_followup(&http, followup).await {
+                println!("Unable to warn about slow command `{name}`: {e:?}");
+            }
+        });
+        if let Some(old) = self.lock().insert(ctx.id(), timer) {
+            old.abort();
+        }
+    }
+
+    /// Stop the timer for a command, because it finished or no longer needs watching
+    pub(crate) fn finish(&self, ctx: Context<'_>) {
+        if let Some(timer) = self.lock().remove(&ctx.id()) {
+            timer.abort();
+        }
+    }
+}
@@ -0,0 +1,161 @@
+use super::errors::Error;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::{redirect, Url};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::LazyLock;
+use std::time::Duration;
+
+/// How long a webhook gets to accept a reminder before we count it as failed
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Client for posting to webhooks. Reminders are delivered from background tasks
+/// that don't have the bot's shared client, so webhooks get their own.
+/// Checking the URL when it's saved isn't enough to keep webhooks off internal addresses,
+/// since a public name can resolve to one or redirect to one, so this client never follows
+/// redirects and, after resolving, only connects to addresses [`is_internal`] doesn't flag.
+/// Proxies are skipped too, as they would resolve the name themselves.
+static CLIENT: LazyLock<reqwest::Client> = LazyLock::new(|| {
+    reqwest::Client::builder()
+        .redirect(redirect::Policy::none())
+        .dns_resolver(PublicResolver)
+        .no_proxy()
+        .build()
+        .expect("webhook client settings should be valid")
+});
+
+/// Resolves names with the system resolver, dropping any internal addresses
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let resolved = tokio::net::lookup_host((name.as_str(), 0)).await?;
+            let addrs: Addrs = Box::new(public_addrs(name.as_str(), resolved)?.into_iter());
+            Ok(addrs)
+        })
+    }
+}
+
+/// Keep only the public addresses a host resolved to, failing if there are none
+fn public_addrs(
+    host: &str,
+    resolved: impl IntoIterator<Item = SocketAddr>,
+) -> Result<Vec<SocketAddr>, Error> {
+    let public: Vec<SocketAddr> = resolved
+        .into_iter()
+        .filter(|addr| !is_internal(addr.ip()))
+        .collect();
+    if public.is_empty() {
+        return Err(format!("{host} doesn't resolve to a public address").into());
+    }
+    Ok(public)
+}
+
+/// Where a user's reminders are posted, besides or instead of Discord
+#[derive(Debug, Clone)]
+pub(crate) struct Webhook {
+    pub(crate) url: String,
+    /// Whether reminders still go to the user's DMs too
+    pub(crate) also_dm: bool,
+}
+
+/// Check a webhook URL is one we're willing to post reminders to.
+/// Only HTTPS is allowed, so reminders aren't sent in the clear, and addresses
+/// on the bot's own machine or network are refused so the bot can't be used to reach them.
+pub(crate) fn validate_url(input: &str) -> Result<Url, String> {
+    let url = Url::parse(input.trim()).map_err(|e| format!("That isn't a valid URL: {e}"))?;
+    if url.scheme() != "https" {
+        return Err("Webhook URLs must start with https://".to_string());
+    }
+    let host = url.host_str().unwrap_or_default();
+    // IPv6 hosts come wrapped in brackets
+    let internal = match host.trim_start_matches('[').trim_end_matches(']').parse() {
+        Ok(ip) => is_internal(ip),
+        Err(_) => {
+            host.is_empty()
+                || host.eq_ignore_ascii_case("localhost")
+                || host.ends_with(".localhost")
+        }
+    };
+    if internal {
+        return Err("Webhooks can't point at local or private addresses".to_string());
+    }
+    Ok(url)
+}
+
+/// Whether an address is loopback, private or otherwise not on the public internet
+fn is_internal(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            ip.is_loopback()
+                || ip.is_private()
+                // shared address space (100.64.0.0/10), used for carrier grade NAT
+                || (ip.octets()[0] == 100 && (ip.octets()[1] & 0xc0) == 64)
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+        }
+        IpAddr::V6(ip) => {
+            ip.to_ipv4_mapped().is_some_and(|v4| is_internal(IpAddr::V4(v4)))
+                || ip.is_loopback()
+                || ip.is_unspecified()
+                // unique local (fc00::/7) and link local (fe80::/10)
+                || (ip.segments()[0] & 0xfe00) == 0xfc00
+                || (ip.segments()[0] & 0xffc0) == 0xfe80
+        }
+    }
+}
+
+/// Post a JSON payload to a webhook, failing on any response other than success.
+/// Redirects count as failures, since following them could lead anywhere.
+pub(crate) async fn post(url: &str, payload: &serde_json::Value) -> Result<(), Error> {
+    // check again in case the rules have tightened since the URL was saved
+    validate_url(url)?;
+    let response = CLIENT
+        .post(url)
+        .timeout(WEBHOOK_TIMEOUT)
+        .json(payload)
+        .send()
+        .await?
+        .error_for_status()?;
+    if response.status().is_redirection() {
+        return Err(format!("Webhook redirected with status {}", response.status()).into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{public_addrs, validate_url};
+    use std::net::SocketAddr;
+
+    #[test]
+    fn only_public_https_urls_are_allowed() {
+        assert!(validate_url("https://example.com/hooks/athena").is_ok());
+        assert!(validate_url("http://example.com/hook").is_err());
+        assert!(validate_url("example.com").is_err());
+        assert!(validate_url("https://localhost:8080/").is_err());
+        assert!(validate_url("https://127.0.0.1/").is_err());
+        assert!(validate_url("https://192.168.1.10/").is_err());
+        assert!(validate_url("https://[::1]/").is_err());
+        assert!(validate_url("https://[::ffff:10.0.0.1]/").is_err());
+        assert!(validate_url("https://100.64.0.1/").is_err());
+        assert!(validate_url("https://100.127.255.254/").is_err());
+        assert!(validate_url("https://100.128.0.1/").is_ok());
+    }
+
+    #[test]
+    fn names_resolving_to_internal_addresses_are_refused() {
+        let addr = |s: &str| s.parse::<SocketAddr>().unwrap();
+        assert!(public_addrs("evil.example", [addr("127.0.0.1:0")]).is_err());
+        assert!(public_addrs("evil.example", [addr("10.1.2.3:0"), addr("[::1]:0")]).is_err());
+        assert_eq!(
+            public_addrs(
+                "mixed.example",
+                [addr("10.1.2.3:0"), addr("93.184.215.14:0")]
+            )
+            .unwrap(),
+            [addr("93.184.215.14:0")]
+        );
+    }
+}